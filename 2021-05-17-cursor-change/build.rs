@@ -1,7 +1,7 @@
 use anyhow::*;
 use glob::glob;
 use std::fs::{read_to_string, write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 struct ShaderData {
     src: String,
@@ -55,6 +55,25 @@ fn main() -> Result<()> {
 
     let mut compiler = shaderc::Compiler::new().context("Unable to create shader compiler")?;
 
+    // Shared snippets (*.glsl) are pulled in with `#include`, relative to the including shader.
+    let mut options =
+        shaderc::CompileOptions::new().context("Unable to create shader compiler options")?;
+    options.set_include_callback(|name, _include_type, source, _depth| {
+        let path = Path::new(source)
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(name);
+        let content = read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(shaderc::ResolvedInclude {
+            resolved_name: path.to_string_lossy().into_owned(),
+            content,
+        })
+    });
+
+    for include in glob("./src/**/*.glsl")? {
+        println!("cargo:rerun-if-changed={}", include?.display());
+    }
+
     // This can't be parallelized. The [shaderc::Compiler] is not
     // thread safe. Also, it creates a lot of resources. You could
     // spawn multiple processes to handle this, but it would probably
@@ -72,7 +91,7 @@ fn main() -> Result<()> {
             shader.kind,
             &shader.src_path.to_str().unwrap(),
             "main",
-            None,
+            Some(&options),
         )?;
         write(shader.spv_path, compiled.as_binary_u8())?;
     }
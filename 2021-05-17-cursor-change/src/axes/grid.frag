@@ -0,0 +1,12 @@
+#version 450
+
+layout(location=0) in vec4 v_color;
+layout(location=1) in float v_offset;
+layout(location=2) flat in float v_width;
+
+layout(location=0) out vec4 f_color;
+
+void main() {
+    float alpha = clamp(v_width / 2. + 0.5 - abs(v_offset), 0., 1.) * v_color.a;
+    f_color = vec4(v_color.rgb * alpha, alpha);
+}
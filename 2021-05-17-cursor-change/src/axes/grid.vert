@@ -0,0 +1,56 @@
+#version 450
+
+// End points in window pixels, with Y going down.
+layout(location=0) in vec2 a_start;
+layout(location=1) in vec2 a_end;
+layout(location=2) in vec4 a_color;
+layout(location=3) in float a_width;
+
+layout(location=0) out vec4 v_color;
+// Distance across the line from its center, in pixels.
+layout(location=1) out float v_offset;
+layout(location=2) flat out float v_width;
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_transform;
+    vec2 u_viewport_size;
+};
+
+void main() {
+    vec2 line = normalize(a_end - a_start);
+    vec2 perp = vec2(-line.y, line.x);
+    // A pixel of margin for antialiasing.
+    float extent = a_width / 2. + 1.;
+
+    vec2 position;
+    switch (gl_VertexIndex) {
+        case 0:
+            position = a_start - perp * extent;
+            v_offset = -extent;
+            break;
+        case 1:
+        case 3:
+            position = a_start + perp * extent;
+            v_offset = extent;
+            break;
+        case 2:
+        case 4:
+            position = a_end - perp * extent;
+            v_offset = -extent;
+            break;
+        case 5:
+            position = a_end + perp * extent;
+            v_offset = extent;
+    }
+
+    gl_Position = vec4(
+        2. * position.x / u_viewport_size.x - 1.,
+        1. - 2. * position.y / u_viewport_size.y,
+        0.,
+        1.
+    );
+
+    v_color = a_color;
+    v_width = a_width;
+}
@@ -0,0 +1,275 @@
+use wgpu::{
+    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, Queue, RenderPass,
+    RenderPipeline, SwapChainDescriptor,
+};
+
+use crate::text::TextRenderer;
+use crate::zoom::ZoomState;
+
+pub use ticks::{AxisScale, Tick};
+
+mod ticks;
+
+// Major ticks are at least this far apart on screen.
+const MIN_TICK_SPACING_IN_PIXELS: f64 = 80.;
+// Enough for both axes at any window size, given the tick spacing.
+const MAX_GRID_LINES: usize = 1024;
+
+const MINOR_COLOR: [f32; 4] = [0., 0., 0., 0.04];
+const MAJOR_COLOR: [f32; 4] = [0., 0., 0., 0.12];
+const AXIS_COLOR: [f32; 4] = [0., 0., 0., 0.6];
+const AXIS_WIDTH: f32 = 1.5;
+
+const LABEL_COLOR: [f32; 4] = [0.05, 0.05, 0.05, 1.];
+const LABEL_SIZE: f32 = 14.;
+// Between labels and their grid line or the window edge, in pixels.
+const LABEL_MARGIN: f32 = 4.;
+
+// Distance of the legend from the right edge of the window, room for labels included.
+const LEGEND_WIDTH: f32 = 160.;
+const LEGEND_LINE_HEIGHT: f32 = 20.;
+const LEGEND_SWATCH_LENGTH: f32 = 20.;
+const LEGEND_SWATCH_WIDTH: f32 = 8.;
+
+/// An entry of the legend in the upper right corner of the window.
+#[derive(Clone, Debug)]
+pub struct LegendEntry {
+    pub label: String,
+    pub color: [f32; 4],
+}
+
+/// A grid line in window pixels, with Y going down.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct GridLineInstance {
+    start: [f32; 2],
+    end: [f32; 2],
+    color: [f32; 4],
+    width: f32,
+}
+
+/// A background grid with axes through zero, and tick labels along the bottom and left
+/// edges of the window. Ticks follow the view, with major ones 1, 2 or 5 times a power of
+/// ten apart. An optional legend goes in the upper right corner.
+pub struct Axes {
+    pub x_scale: AxisScale,
+    pub y_scale: AxisScale,
+    pub legend: Vec<LegendEntry>,
+
+    render_pipeline: RenderPipeline,
+    instance_buffer: Buffer,
+    num_lines: u32,
+}
+
+impl Axes {
+    /// The grid is drawn in the scene described by `scene_desc`.
+    pub fn new(
+        device: &Device,
+        scene_desc: &SwapChainDescriptor,
+        transform_layout: &BindGroupLayout,
+    ) -> Self {
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Grid instance buffer"),
+            size: (MAX_GRID_LINES * std::mem::size_of::<GridLineInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Axes {
+            x_scale: AxisScale::IDENTITY,
+            y_scale: AxisScale::IDENTITY,
+            legend: Vec::new(),
+            render_pipeline: create_pipeline(device, scene_desc, transform_layout),
+            instance_buffer,
+            num_lines: 0,
+        }
+    }
+
+    /// Recomputes the ticks for the current view, uploads the grid lines and queues the
+    /// labels, which are drawn on top of the post-processed frame so they stay crisp.
+    pub fn update(&mut self, queue: &Queue, zoom_state: &ZoomState, text: &mut TextRenderer) {
+        let [min_x, min_y, width, height] = zoom_state.view_box();
+        let pixel_size = zoom_state.pixel_size() as f64;
+        let size = zoom_state.size();
+        let (window_width, window_height) = (size.width as f32, size.height as f32);
+        let to_window_x = |x: f64| ((x - min_x) / pixel_size) as f32;
+        let to_window_y = |y: f64| window_height - ((y - min_y) / pixel_size) as f32;
+
+        let min_step = MIN_TICK_SPACING_IN_PIXELS * pixel_size;
+        let x_ticks = self.x_scale.ticks(min_x, min_x + width, min_step);
+        let y_ticks = self.y_scale.ticks(min_y, min_y + height, min_step);
+
+        let line = |start, end, color, width| GridLineInstance {
+            start,
+            end,
+            color,
+            width,
+        };
+        let tick_color = |tick: &Tick| {
+            if tick.label.is_some() {
+                MAJOR_COLOR
+            } else {
+                MINOR_COLOR
+            }
+        };
+        let mut lines = Vec::new();
+        for tick in &x_ticks {
+            let x = to_window_x(tick.position);
+            lines.push(line([x, 0.], [x, window_height], tick_color(tick), 1.));
+        }
+        for tick in &y_ticks {
+            let y = to_window_y(tick.position);
+            lines.push(line([0., y], [window_width, y], tick_color(tick), 1.));
+        }
+        // The axes themselves, where they are in view, and the legend swatches, which are short
+        // thick lines. They go in front of the grid, and only the grid is cut to make room.
+        let mut front = Vec::new();
+        let (zero_x, zero_y) = (self.x_scale.zero(), self.y_scale.zero());
+        if (min_x..min_x + width).contains(&zero_x) {
+            let x = to_window_x(zero_x);
+            front.push(line([x, 0.], [x, window_height], AXIS_COLOR, AXIS_WIDTH));
+        }
+        if (min_y..min_y + height).contains(&zero_y) {
+            let y = to_window_y(zero_y);
+            front.push(line([0., y], [window_width, y], AXIS_COLOR, AXIS_WIDTH));
+        }
+        let legend_x = window_width - LEGEND_WIDTH;
+        let legend_y = |i: usize| LABEL_MARGIN + i as f32 * LEGEND_LINE_HEIGHT;
+        for (i, entry) in self.legend.iter().enumerate() {
+            let y = legend_y(i) + LABEL_SIZE / 2.;
+            front.push(line(
+                [legend_x, y],
+                [legend_x + LEGEND_SWATCH_LENGTH, y],
+                entry.color,
+                LEGEND_SWATCH_WIDTH,
+            ));
+        }
+
+        front.truncate(MAX_GRID_LINES);
+        lines.truncate(MAX_GRID_LINES - front.len());
+        lines.append(&mut front);
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&lines));
+        self.num_lines = lines.len() as u32;
+
+        for tick in &x_ticks {
+            if let Some(label) = &tick.label {
+                text.queue(
+                    (
+                        to_window_x(tick.position) + LABEL_MARGIN,
+                        window_height - LABEL_MARGIN - LABEL_SIZE,
+                    ),
+                    label,
+                    LABEL_COLOR,
+                    LABEL_SIZE,
+                );
+            }
+        }
+        for tick in &y_ticks {
+            if let Some(label) = &tick.label {
+                text.queue(
+                    (
+                        LABEL_MARGIN,
+                        to_window_y(tick.position) - LABEL_MARGIN - LABEL_SIZE,
+                    ),
+                    label,
+                    LABEL_COLOR,
+                    LABEL_SIZE,
+                );
+            }
+        }
+        for (i, entry) in self.legend.iter().enumerate() {
+            text.queue(
+                (legend_x + LEGEND_SWATCH_LENGTH + LABEL_MARGIN, legend_y(i)),
+                &entry.label,
+                LABEL_COLOR,
+                LABEL_SIZE,
+            );
+        }
+    }
+
+    /// Draws the grid lines, before the layers so they end up behind them.
+    pub fn draw_grid<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.num_lines);
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    sc_desc: &SwapChainDescriptor,
+    transform_layout: &BindGroupLayout,
+) -> RenderPipeline {
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts: &[transform_layout],
+        push_constant_ranges: &[],
+    });
+
+    let vs_module = device.create_shader_module(&wgpu::include_spirv!("grid.vert.spv"));
+    let fs_module = device.create_shader_module(&wgpu::include_spirv!("grid.frag.spv"));
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &vs_module,
+            entry_point: "main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<GridLineInstance>() as wgpu::BufferAddress,
+                step_mode: wgpu::InputStepMode::Instance,
+                attributes: &[
+                    wgpu::VertexAttribute {
+                        offset: 0,
+                        shader_location: 0,
+                        format: wgpu::VertexFormat::Float32x2,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                        shader_location: 1,
+                        format: wgpu::VertexFormat::Float32x2,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                        shader_location: 2,
+                        format: wgpu::VertexFormat::Float32x4,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                        shader_location: 3,
+                        format: wgpu::VertexFormat::Float32,
+                    },
+                ],
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &fs_module,
+            entry_point: "main",
+            targets: &[wgpu::ColorTargetState {
+                format: sc_desc.format,
+                write_mask: wgpu::ColorWrite::ALL,
+                blend: Some(BlendState {
+                    color: BlendComponent::OVER,
+                    alpha: BlendComponent::REPLACE,
+                }),
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            clamp_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
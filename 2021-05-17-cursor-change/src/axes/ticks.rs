@@ -0,0 +1,183 @@
+/// How an axis maps scene coordinates to the values its labels show. Only linear for now;
+/// a logarithmic scale would label scene coordinate `x` with `10^x`, and put its major
+/// ticks at powers of ten.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AxisScale {
+    /// Scene coordinate `x` is labeled `offset + factor * x`.
+    Linear { offset: f64, factor: f64 },
+}
+
+#[derive(Clone, Debug)]
+pub struct Tick {
+    /// In scene units along the axis.
+    pub position: f64,
+    /// Only major ticks are labeled.
+    pub label: Option<String>,
+}
+
+impl AxisScale {
+    /// Labels scene coordinates with themselves.
+    pub const IDENTITY: AxisScale = AxisScale::Linear {
+        offset: 0.,
+        factor: 1.,
+    };
+
+    /// Major ticks at least `min_step` scene units apart between `min` and `max`, with minor
+    /// ticks in between.
+    pub fn ticks(&self, min: f64, max: f64, min_step: f64) -> Vec<Tick> {
+        match *self {
+            AxisScale::Linear { offset, factor } => {
+                let (a, b) = (offset + factor * min, offset + factor * max);
+                linear_ticks(a.min(b), a.max(b), min_step * factor.abs())
+                    .into_iter()
+                    .map(|tick| Tick {
+                        position: (tick.position - offset) / factor,
+                        ..tick
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// The scene coordinate labeled zero, where the axis is drawn.
+    pub fn zero(&self) -> f64 {
+        match *self {
+            AxisScale::Linear { offset, factor } => -offset / factor,
+        }
+    }
+}
+
+/// The smallest step of 1, 2 or 5 times a power of ten that is at least `min_step`, with
+/// the number of minor ticks it is divided into.
+fn nice_step(min_step: f64) -> (f64, i64) {
+    let magnitude = 10f64.powf(min_step.log10().floor());
+    let (mantissa, divisions) = match min_step / magnitude {
+        m if m <= 1. => (1., 5),
+        m if m <= 2. => (2., 4),
+        m if m <= 5. => (5., 5),
+        _ => (10., 5),
+    };
+    (mantissa * magnitude, divisions)
+}
+
+// Ticks between values `min` and `max`, with the values as positions.
+fn linear_ticks(min: f64, max: f64, min_step: f64) -> Vec<Tick> {
+    if !(min_step > 0. && min_step.is_finite()) {
+        return Vec::new();
+    }
+
+    let (step, divisions) = nice_step(min_step);
+    let minor_step = step / divisions as f64;
+    // Ticks are counted in minor steps, so positions don't accumulate rounding errors.
+    let first = (min / minor_step).ceil() as i64;
+    let last = (max / minor_step).floor() as i64;
+    (first..=last)
+        .map(|i| {
+            let position = i as f64 * minor_step;
+            let label = if i.rem_euclid(divisions) == 0 {
+                Some(format_value(position, step))
+            } else {
+                None
+            };
+            Tick { position, label }
+        })
+        .collect()
+}
+
+/// Formats `value` with just enough digits to tell ticks `step` apart.
+fn format_value(value: f64, step: f64) -> String {
+    if value == 0. {
+        return "0".to_string();
+    }
+
+    let step_exponent = step.log10().floor();
+    if !(-4.0..6.0).contains(&step_exponent) {
+        let digits = (value.abs().log10().floor() - step_exponent).max(0.) as usize;
+        format!("{:.*e}", digits, value)
+    } else {
+        format!("{:.*}", (-step_exponent).max(0.) as usize, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn labels(ticks: &[Tick]) -> Vec<&str> {
+        ticks
+            .iter()
+            .filter_map(|tick| tick.label.as_deref())
+            .collect()
+    }
+
+    #[test]
+    fn nice_steps() {
+        for &(min_step, step, divisions) in &[
+            (1., 1., 5),
+            (1.5, 2., 4),
+            (3., 5., 5),
+            (7., 10., 5),
+            (250., 500., 5),
+            (0.3, 0.5, 5),
+            (1.5e-6, 2e-6, 4),
+            (1e9, 1e9, 5),
+            (2e12, 2e12, 4),
+        ] {
+            let (actual_step, actual_divisions) = nice_step(min_step);
+            assert_close(actual_step, step);
+            assert_eq!(actual_divisions, divisions, "divisions of {}", min_step);
+        }
+    }
+
+    #[test]
+    fn range_spanning_zero() {
+        let ticks = linear_ticks(-1., 1., 1.);
+        assert_eq!(ticks.len(), 11);
+        assert_eq!(labels(&ticks), vec!["-1", "0", "1"]);
+        assert!(ticks.iter().any(|tick| tick.position == 0.));
+    }
+
+    #[test]
+    fn tiny_magnitudes() {
+        let ticks = linear_ticks(0., 4.1e-6, 1.5e-6);
+        assert_eq!(ticks.len(), 9);
+        assert_eq!(labels(&ticks), vec!["0", "2e-6", "4e-6"]);
+    }
+
+    #[test]
+    fn huge_magnitudes() {
+        let ticks = linear_ticks(0., 3.1e9, 1e9);
+        assert_eq!(ticks.len(), 16);
+        assert_eq!(labels(&ticks), vec!["0", "1e9", "2e9", "3e9"]);
+    }
+
+    #[test]
+    fn zero_width_range() {
+        assert!(linear_ticks(2., 2., 0.).is_empty());
+        assert!(linear_ticks(0.1, 0.1, 1.).is_empty());
+        let ticks = linear_ticks(1., 1., 1.);
+        assert_eq!(ticks.len(), 1);
+        assert_eq!(labels(&ticks), vec!["1"]);
+    }
+
+    #[test]
+    fn formatted_values() {
+        assert_eq!(format_value(0., 1e-9), "0");
+        assert_eq!(format_value(-1., 1.), "-1");
+        assert_eq!(format_value(1234., 1000.), "1234");
+        assert_eq!(format_value(3., 0.5), "3.0");
+        assert_eq!(format_value(0.25, 0.05), "0.25");
+        assert_eq!(format_value(1.5e9, 5e8), "1.5e9");
+        assert_eq!(format_value(-3e-5, 1e-5), "-3e-5");
+        assert_eq!(format_value(1.25e-5, 5e-7), "1.25e-5");
+    }
+}
@@ -0,0 +1,132 @@
+use std::time::Instant;
+
+use crate::circle::{Circle, CirclesLayer};
+use crate::colormap::Colormap;
+use crate::heatmap::{HeatPoint, HeatmapLayer};
+use crate::layer::{Layer, SIZE_IN_PIXELS};
+use crate::lod::LodLayer;
+use crate::point::{Marker, Point, PointCloudLayer, PointsLayer};
+
+const FPS_RESET_FRAMES: u32 = 60;
+// Half the width of the square the instances are scattered over, in scene units.
+const EXTENT: f64 = 1000.;
+const RADIUS_IN_PIXELS: f32 = 2.;
+
+#[derive(Copy, Clone, Debug)]
+pub enum BenchmarkKind {
+    Points(Marker),
+    Cloud,
+    Circles,
+    Lod(Option<Marker>),
+    Heatmap(Colormap),
+}
+
+/// Draws many identical markers with `PointsLayer`, `PointCloudLayer` or `CirclesLayer`,
+/// optionally aggregated by `LodLayer`, or their density with `HeatmapLayer`, and prints
+/// the frame rate.
+pub struct Benchmark {
+    kind: BenchmarkKind,
+    count: usize,
+    frame: u32,
+    last_time: Instant,
+}
+
+impl Benchmark {
+    /// Parses the arguments of `--bench <kind> <count>`, see `USAGE`.
+    pub fn from_args(kind: &str, count: &str) -> Option<Self> {
+        let kind = match kind {
+            "circles" => BenchmarkKind::Circles,
+            "points" => BenchmarkKind::Points(Marker::Round),
+            "squares" => BenchmarkKind::Points(Marker::Square),
+            "diamonds" => BenchmarkKind::Points(Marker::Diamond),
+            "cloud" => BenchmarkKind::Cloud,
+            "lod" => BenchmarkKind::Lod(None),
+            "lod-points" => BenchmarkKind::Lod(Some(Marker::Round)),
+            "heatmap" => BenchmarkKind::Heatmap(Colormap::Viridis),
+            "heatmap-magma" => BenchmarkKind::Heatmap(Colormap::Magma),
+            "heatmap-inferno" => BenchmarkKind::Heatmap(Colormap::Inferno),
+            "heatmap-plasma" => BenchmarkKind::Heatmap(Colormap::Plasma),
+            _ => return None,
+        };
+
+        Some(Benchmark {
+            kind,
+            count: count.parse().ok()?,
+            frame: 0,
+            last_time: Instant::now(),
+        })
+    }
+
+    pub fn layers(&self) -> Vec<Box<dyn Layer>> {
+        // A linear congruential generator is plenty for scattering markers, and keeps runs
+        // comparable.
+        let mut seed: u32 = 1;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            seed as f64 / u32::MAX as f64
+        };
+        let mut positions = Vec::with_capacity(self.count);
+        for _ in 0..self.count {
+            positions.push([EXTENT * (2. * next() - 1.), EXTENT * (2. * next() - 1.)]);
+        }
+        let color = [0.1, 0.3, 0.8, 0.5];
+        let point = |position| Point {
+            position,
+            color,
+            radius: RADIUS_IN_PIXELS,
+        };
+        let circle = |position| Circle {
+            position,
+            color,
+            radius: RADIUS_IN_PIXELS,
+            stroke_color: [0., 0., 0., 0.],
+            stroke_width: 0.,
+            flags: SIZE_IN_PIXELS,
+        };
+
+        let layer: Box<dyn Layer> = match self.kind {
+            BenchmarkKind::Points(marker) => Box::new(
+                PointsLayer::new(positions.into_iter().map(point).collect()).with_marker(marker),
+            ),
+            BenchmarkKind::Cloud => Box::new(PointCloudLayer::new(
+                &positions.into_iter().map(point).collect::<Vec<_>>(),
+            )),
+            BenchmarkKind::Circles => Box::new(CirclesLayer::new(
+                positions.into_iter().map(circle).collect(),
+            )),
+            BenchmarkKind::Lod(None) => Box::new(LodLayer::circles(
+                positions.into_iter().map(circle).collect(),
+            )),
+            BenchmarkKind::Lod(Some(marker)) => Box::new(LodLayer::points(
+                positions.into_iter().map(point).collect(),
+                marker,
+            )),
+            BenchmarkKind::Heatmap(colormap) => Box::new(
+                HeatmapLayer::new(
+                    positions
+                        .into_iter()
+                        .map(|position| HeatPoint {
+                            position,
+                            weight: 1.,
+                        })
+                        .collect(),
+                )
+                .with_colormap(colormap),
+            ),
+        };
+        vec![layer]
+    }
+
+    pub fn frame_rendered(&mut self) {
+        self.frame += 1;
+        if self.frame % FPS_RESET_FRAMES == 0 {
+            let duration = Instant::now() - self.last_time;
+            let fps = FPS_RESET_FRAMES as f32 / duration.as_secs_f32();
+            println!(
+                "FPS of last {} frames ({} {:?}): {}",
+                FPS_RESET_FRAMES, self.count, self.kind, fps
+            );
+            self.last_time = Instant::now();
+        }
+    }
+}
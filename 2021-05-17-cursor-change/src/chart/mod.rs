@@ -0,0 +1,345 @@
+use crate::axes::{AxisScale, LegendEntry};
+use crate::circle::{Circle, CirclesLayer};
+use crate::layer::{Layer, SIZE_IN_PIXELS};
+use crate::line::{Line, LinesLayer};
+use crate::rectangle::{Rectangle, RectanglesLayer};
+use crate::triangle::{Triangle, TrianglesLayer};
+
+// Scene size of the plot area, which starts at the scene origin. Data is stretched to fill it.
+const PLOT_SIZE: [f64; 2] = [1000., 600.];
+// Bars are this fraction of the closest distance between two of their points wide.
+const BAR_WIDTH_FRACTION: f64 = 0.8;
+const AREA_OPACITY: f32 = 0.4;
+
+// The first six Tableau 10 colors (as in matplotlib), in linear RGB.
+const PALETTE: [[f32; 4]; 6] = [
+    [0.014, 0.184, 0.456, 1.],
+    [1., 0.212, 0.004, 1.],
+    [0.025, 0.352, 0.025, 1.],
+    [0.672, 0.020, 0.021, 1.],
+    [0.296, 0.136, 0.509, 1.],
+    [0.262, 0.093, 0.071, 1.],
+];
+
+#[derive(Copy, Clone, Debug)]
+pub enum SeriesKind {
+    /// A marker at every point, with the radius in pixels.
+    Scatter { radius: f32 },
+    /// Segments between consecutive points. Like `Line::width`, the width is a half-width in
+    /// pixels: a width of 2 draws lines 4 pixels wide.
+    Line { width: f32 },
+    /// A bar from zero to every point.
+    Bar,
+    /// The region between zero and the line through the points, which should be sorted
+    /// by x.
+    Area,
+}
+
+/// A named list of `[x, y]` data points, drawn one way.
+#[derive(Clone, Debug)]
+pub struct Series {
+    pub name: String,
+    pub kind: SeriesKind,
+    pub data: Vec<[f64; 2]>,
+    /// Picked from a palette by the series' index if not given.
+    pub color: Option<[f32; 4]>,
+}
+
+impl Series {
+    fn new(name: &str, kind: SeriesKind, data: Vec<[f64; 2]>) -> Self {
+        Series {
+            name: name.to_string(),
+            kind,
+            data,
+            color: None,
+        }
+    }
+
+    pub fn scatter(name: &str, data: Vec<[f64; 2]>) -> Self {
+        Series::new(name, SeriesKind::Scatter { radius: 4. }, data)
+    }
+
+    pub fn line(name: &str, data: Vec<[f64; 2]>) -> Self {
+        Series::new(name, SeriesKind::Line { width: 2. }, data)
+    }
+
+    pub fn bar(name: &str, data: Vec<[f64; 2]>) -> Self {
+        Series::new(name, SeriesKind::Bar, data)
+    }
+
+    pub fn area(name: &str, data: Vec<[f64; 2]>) -> Self {
+        Series::new(name, SeriesKind::Area, data)
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Width of the bars in data units.
+    fn bar_width(&self) -> f64 {
+        let mut xs: Vec<f64> = self
+            .data
+            .iter()
+            .map(|&[x, _]| x)
+            .filter(|x| x.is_finite())
+            .collect();
+        xs.sort_by(f64::total_cmp);
+        let spacing = xs
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .filter(|&d| d > 0.)
+            .fold(f64::INFINITY, f64::min);
+        if spacing.is_finite() {
+            spacing * BAR_WIDTH_FRACTION
+        } else {
+            BAR_WIDTH_FRACTION
+        }
+    }
+}
+
+/// Maps data values to scene coordinates along one axis.
+#[derive(Copy, Clone, Debug)]
+struct Mapping {
+    min: f64,
+    factor: f64,
+}
+
+impl Mapping {
+    fn new(min: f64, max: f64, size: f64) -> Self {
+        // A single value still gets some room around it.
+        let (min, max) = if max > min {
+            (min, max)
+        } else {
+            (min - 1., max + 1.)
+        };
+        Mapping {
+            min,
+            factor: size / (max - min),
+        }
+    }
+
+    fn apply(&self, value: f64) -> f64 {
+        (value - self.min) * self.factor
+    }
+
+    /// Labels the axis with data values.
+    fn axis_scale(&self) -> AxisScale {
+        AxisScale::Linear {
+            offset: self.min,
+            factor: 1. / self.factor,
+        }
+    }
+}
+
+/// Triangles filling the region between `baseline` and the line through `points`. Segments
+/// that cross the baseline are split where they cross it, so no triangle overlaps another or
+/// reaches past the line.
+fn area_triangles(points: &[[f64; 2]], baseline: f64, color: [f32; 4]) -> Vec<Triangle> {
+    let triangle = |vertices| Triangle { vertices, color };
+    let mut triangles = Vec::new();
+    for pair in points.windows(2) {
+        let ([x0, y0], [x1, y1]) = (pair[0], pair[1]);
+        if (y0 - baseline) * (y1 - baseline) < 0. {
+            // One triangle on either side of the crossing.
+            let crossing = [x0 + (x1 - x0) * (baseline - y0) / (y1 - y0), baseline];
+            triangles.push(triangle([[x0, y0], crossing, [x0, baseline]]));
+            triangles.push(triangle([crossing, [x1, y1], [x1, baseline]]));
+        } else {
+            // Two triangles for the trapezoid under the segment.
+            triangles.push(triangle([[x0, y0], [x1, y1], [x1, baseline]]));
+            triangles.push(triangle([[x0, y0], [x1, baseline], [x0, baseline]]));
+        }
+    }
+    triangles
+}
+
+/// Scatter, line, bar and area series drawn with the shape layers. Data is scaled to a
+/// plot area in the scene, so the chart pans and zooms like any other layer, while the
+/// axes are labeled with data values.
+pub struct Chart {
+    series: Vec<Series>,
+    x: Mapping,
+    y: Mapping,
+}
+
+impl Chart {
+    pub fn new(series: Vec<Series>) -> Self {
+        let mut min = [f64::INFINITY; 2];
+        let mut max = [f64::NEG_INFINITY; 2];
+        for s in &series {
+            // Bars stick out beside their points, and bars and areas start from zero.
+            let (margin, from_zero) = match s.kind {
+                SeriesKind::Bar => (s.bar_width() / 2., true),
+                SeriesKind::Area => (0., true),
+                _ => (0., false),
+            };
+            for &[x, y] in &s.data {
+                min = [min[0].min(x - margin), min[1].min(y)];
+                max = [max[0].max(x + margin), max[1].max(y)];
+                if from_zero {
+                    min[1] = min[1].min(0.);
+                    max[1] = max[1].max(0.);
+                }
+            }
+        }
+        if !(min[0] <= max[0] && min[1] <= max[1]) {
+            min = [0.; 2];
+            max = [1.; 2];
+        }
+
+        Chart {
+            series,
+            x: Mapping::new(min[0], max[0], PLOT_SIZE[0]),
+            y: Mapping::new(min[1], max[1], PLOT_SIZE[1]),
+        }
+    }
+
+    fn color(&self, index: usize) -> [f32; 4] {
+        self.series[index]
+            .color
+            .unwrap_or(PALETTE[index % PALETTE.len()])
+    }
+
+    fn to_scene(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        [self.x.apply(x), self.y.apply(y)]
+    }
+
+    /// One layer per series, in order, so later series are drawn on top.
+    pub fn layers(&self) -> Vec<Box<dyn Layer>> {
+        let baseline = self.y.apply(0.);
+        self.series
+            .iter()
+            .enumerate()
+            .map(|(i, series)| {
+                let color = self.color(i);
+                let points = series.data.iter().map(|&point| self.to_scene(point));
+                let layer: Box<dyn Layer> = match series.kind {
+                    SeriesKind::Scatter { radius } => Box::new(CirclesLayer::new(
+                        points
+                            .map(|position| Circle {
+                                position,
+                                radius,
+                                color,
+                                stroke_color: [0., 0., 0., 0.],
+                                stroke_width: 0.,
+                                flags: SIZE_IN_PIXELS,
+                            })
+                            .collect(),
+                    )),
+                    SeriesKind::Line { width } => {
+                        let points: Vec<[f64; 2]> = points.collect();
+                        Box::new(LinesLayer::new(
+                            points
+                                .windows(2)
+                                .map(|pair| Line {
+                                    start: pair[0],
+                                    end: pair[1],
+                                    color,
+                                    width,
+                                    dash_array: [0.; 4],
+                                    dash_offset: 0.,
+                                    flags: SIZE_IN_PIXELS,
+                                })
+                                .collect(),
+                        ))
+                    }
+                    SeriesKind::Bar => {
+                        let half_width = series.bar_width() * self.x.factor / 2.;
+                        Box::new(RectanglesLayer::new(
+                            points
+                                .map(|[x, y]| Rectangle {
+                                    upper_left: [x - half_width, y],
+                                    bottom_right: [x + half_width, baseline],
+                                    color,
+                                    stroke_color: [0., 0., 0., 0.],
+                                    stroke_width: 0.,
+                                    corner_radius: 0.,
+                                    flags: 0,
+                                })
+                                .collect(),
+                        ))
+                    }
+                    SeriesKind::Area => {
+                        let points: Vec<[f64; 2]> = points.collect();
+                        let color = [color[0], color[1], color[2], color[3] * AREA_OPACITY];
+                        Box::new(TrianglesLayer::new(area_triangles(
+                            &points, baseline, color,
+                        )))
+                    }
+                };
+                layer
+            })
+            .collect()
+    }
+
+    /// Scales for `Axes`, so ticks are labeled with data values.
+    pub fn axis_scales(&self) -> (AxisScale, AxisScale) {
+        (self.x.axis_scale(), self.y.axis_scale())
+    }
+
+    pub fn legend(&self) -> Vec<LegendEntry> {
+        self.series
+            .iter()
+            .enumerate()
+            .map(|(i, series)| LegendEntry {
+                label: series.name.clone(),
+                color: self.color(i),
+            })
+            .collect()
+    }
+
+    /// The region of the scene covered by the chart, for `ZoomState::fit`.
+    pub fn scene_bounds(&self) -> ([f64; 2], [f64; 2]) {
+        ([0., 0.], PLOT_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(triangle: &Triangle) -> f64 {
+        let [[x0, y0], [x1, y1], [x2, y2]] = triangle.vertices;
+        ((x1 - x0) * (y2 - y0) - (x2 - x0) * (y1 - y0)).abs() / 2.
+    }
+
+    // Whether the triangle lies on one side of `baseline`, touching it at most.
+    fn one_sided(triangle: &Triangle, baseline: f64) -> bool {
+        let above = triangle.vertices.iter().all(|&[_, y]| y >= baseline);
+        let below = triangle.vertices.iter().all(|&[_, y]| y <= baseline);
+        above || below
+    }
+
+    #[test]
+    fn area_above_baseline() {
+        let triangles = area_triangles(&[[0., 1.], [2., 3.]], 0., [1.; 4]);
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles.iter().map(area).sum::<f64>(), 4.);
+        assert!(triangles.iter().all(|t| one_sided(t, 0.)));
+    }
+
+    #[test]
+    fn area_changing_sign_is_split_at_the_crossing() {
+        let baseline = 1.;
+        let triangles = area_triangles(
+            &[[0., 2.], [2., 0.], [3., 0.5], [4., 3.]],
+            baseline,
+            [1.; 4],
+        );
+        assert_eq!(triangles.len(), 6);
+        assert!(triangles.iter().all(|t| one_sided(t, baseline)));
+        // The crossings are at x = 1 and x = 3.2, and the area is that between the line and
+        // the baseline on either side.
+        let expected = 0.5 + 0.5 + 0.75 + 0.5 * 0.2 * 0.5 + 0.5 * 0.8 * 2.;
+        let total: f64 = triangles.iter().map(area).sum();
+        assert!((total - expected).abs() < 1e-9, "{} != {}", total, expected);
+    }
+
+    #[test]
+    fn area_touching_baseline_is_not_split() {
+        let triangles = area_triangles(&[[0., 0.], [1., 1.], [2., 0.]], 0., [1.; 4]);
+        assert_eq!(triangles.len(), 4);
+        assert!(triangles.iter().all(|t| one_sided(t, 0.)));
+    }
+}
@@ -1,53 +1,144 @@
+use std::fmt::{self, Write};
+
 use wgpu::util::DeviceExt;
 use wgpu::{
-    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, RenderPass,
+    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, Queue, RenderPass,
     RenderPipeline, SwapChainDescriptor,
 };
 
-use crate::layer::{Drawable, Layer};
+use crate::edit::{Shape, ShapeKind, ShapeLayer};
+use crate::fill::{Fill, FillBinding};
+use crate::layer::{
+    outline, relative_to, write_instances_at, Drawable, InstanceIds, Layer, LayerGroup,
+    HIGHLIGHT_WIDTH_IN_PIXELS, SIZE_IN_PIXELS,
+};
+use crate::line::LinesLayer;
+use crate::selection::Region;
+use crate::svg::{svg_paint, write_stroke, SvgIds};
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+// Sides of the polygon outlining a hovered circle.
+const HIGHLIGHT_SEGMENTS: usize = 48;
+
+#[derive(Copy, Clone, Debug)]
 pub struct Circle {
-    pub position: [f32; 2],
+    pub position: [f64; 2],
     pub color: [f32; 4],
     pub radius: f32,
+    /// Color of the outline, centered on the edge of the circle.
+    pub stroke_color: [f32; 4],
+    /// Width of the outline in scene units. Zero for no outline.
+    pub stroke_width: f32,
+    /// Either zero or `SIZE_IN_PIXELS`, which puts `radius` and `stroke_width` in pixels.
+    pub flags: u32,
+}
+
+/// A `Circle` as uploaded, with the position relative to the upload origin.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct CircleInstance {
+    position: [f32; 2],
+    color: [f32; 4],
+    radius: f32,
+    stroke_color: [f32; 4],
+    stroke_width: f32,
+    flags: u32,
+}
+
+impl Circle {
+    fn instance(&self, origin: [f64; 2]) -> CircleInstance {
+        CircleInstance {
+            position: relative_to(self.position, origin),
+            color: self.color,
+            radius: self.radius,
+            stroke_color: self.stroke_color,
+            stroke_width: self.stroke_width,
+            flags: self.flags,
+        }
+    }
+
+    fn contains(&self, position: [f64; 2], pixel_size: f32) -> bool {
+        let unit = if self.flags & SIZE_IN_PIXELS != 0 {
+            pixel_size
+        } else {
+            1.
+        };
+        let radius = ((self.radius + self.stroke_width / 2.) * unit) as f64;
+        let [dx, dy] = [
+            position[0] - self.position[0],
+            position[1] - self.position[1],
+        ];
+        dx * dx + dy * dy <= radius * radius
+    }
 }
 
 pub struct CirclesLayer {
     data: Vec<Circle>,
+    fill: Fill,
+    ids: InstanceIds,
 }
 
 impl CirclesLayer {
     pub fn new(data: Vec<Circle>) -> Self {
-        CirclesLayer { data }
+        CirclesLayer {
+            data,
+            fill: Fill::Solid,
+            ids: InstanceIds::default(),
+        }
+    }
+
+    pub fn with_fill(mut self, fill: Fill) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    /// Attaches an ID to every instance, see `InstanceIds`.
+    pub fn with_ids(mut self, ids: Vec<u64>) -> Self {
+        self.ids = InstanceIds::new(ids, self.data.len());
+        self
     }
 }
 
 pub struct CirclesLayerDrawable {
     render_pipeline: RenderPipeline,
     instance_buffer: Buffer,
+    fill: FillBinding,
     num_circles: u32,
 }
 
 impl Drawable for CirclesLayerDrawable {
     fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        // Layers can be emptied by editing.
+        if self.num_circles == 0 {
+            return;
+        }
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, &self.fill.bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
         render_pass.draw(0..6, 0..self.num_circles);
     }
+
+    fn write_instances(&self, queue: &Queue, first: usize, data: &[u8]) {
+        let offset = first * std::mem::size_of::<CircleInstance>();
+        queue.write_buffer(&self.instance_buffer, offset as wgpu::BufferAddress, data);
+    }
+
+    fn rebase(&self, queue: &Queue, origin: [f64; 2]) {
+        self.fill.rebase(queue, origin);
+    }
 }
 
 impl Layer for CirclesLayer {
     fn init_drawable(
         &self,
         device: &Device,
+        queue: &Queue,
         sc_desc: &SwapChainDescriptor,
         transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
     ) -> Box<dyn Drawable> {
         let instance_buffer_desc = wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Circle>() as wgpu::BufferAddress,
+            array_stride: std::mem::size_of::<CircleInstance>() as wgpu::BufferAddress,
             step_mode: wgpu::InputStepMode::Instance,
             attributes: &[
                 wgpu::VertexAttribute {
@@ -65,19 +156,40 @@ impl Layer for CirclesLayer {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 7]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 11]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Uint32,
+                },
             ],
         };
 
+        let instances: Vec<CircleInstance> = self.data.iter().map(|d| d.instance(origin)).collect();
         let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Instance buffer"),
-            contents: bytemuck::cast_slice(&self.data),
-            usage: wgpu::BufferUsage::VERTEX,
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
         });
 
+        let fill_layout = Fill::bind_group_layout(device);
+        let fill = self
+            .fill
+            .create_bind_group(device, queue, &fill_layout, origin);
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[transform_layout],
+                bind_group_layouts: &[transform_layout, &fill_layout],
                 push_constant_ranges: &[],
             });
 
@@ -126,7 +238,162 @@ impl Layer for CirclesLayer {
         Box::new(CirclesLayerDrawable {
             render_pipeline,
             instance_buffer,
+            fill,
             num_circles: self.data.len() as u32,
         })
     }
+
+    fn rebase(&self, queue: &Queue, drawable: &dyn Drawable, origin: [f64; 2]) -> bool {
+        let instances: Vec<CircleInstance> = self.data.iter().map(|d| d.instance(origin)).collect();
+        drawable.write_instances(queue, 0, bytemuck::cast_slice(&instances));
+        drawable.rebase(queue, origin);
+        true
+    }
+
+    fn update_instances(
+        &self,
+        queue: &Queue,
+        drawable: &dyn Drawable,
+        indices: &[usize],
+        origin: [f64; 2],
+    ) -> bool {
+        write_instances_at(queue, drawable, indices, |i| self.data[i].instance(origin));
+        true
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, pixel_size: f32, ids: &mut SvgIds) -> fmt::Result {
+        let fill_paint = self.fill.write_svg_defs(out, ids)?;
+        for circle in &self.data {
+            let (mut fill, opacity) = svg_paint(circle.color);
+            if let Some(paint) = &fill_paint {
+                fill = paint.clone();
+            }
+            let unit = if circle.flags & SIZE_IN_PIXELS != 0 {
+                pixel_size
+            } else {
+                1.
+            };
+            write!(
+                out,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="{}" fill-opacity="{}""#,
+                circle.position[0],
+                circle.position[1],
+                circle.radius * unit,
+                fill,
+                opacity
+            )?;
+            write_stroke(out, circle.stroke_color, circle.stroke_width * unit)?;
+            writeln!(out, "/>")?;
+        }
+        Ok(())
+    }
+
+    fn pick(&self, position: [f64; 2], pixel_size: f32) -> Option<usize> {
+        self.data
+            .iter()
+            .rposition(|circle| circle.contains(position, pixel_size))
+    }
+
+    fn instance_id(&self, index: usize) -> u64 {
+        self.ids.get(index)
+    }
+
+    fn as_shapes(&self) -> Option<&dyn ShapeLayer> {
+        Some(self)
+    }
+
+    fn as_shapes_mut(&mut self) -> Option<&mut dyn ShapeLayer> {
+        Some(self)
+    }
+
+    fn select(&self, region: &Region) -> Vec<usize> {
+        (0..self.data.len())
+            .filter(|&i| region.contains(self.data[i].position))
+            .collect()
+    }
+
+    fn highlight(&self, indices: &[usize], color: [f32; 4]) -> Option<Box<dyn Layer>> {
+        let (in_pixels, in_scene): (Vec<&Circle>, Vec<&Circle>) = indices
+            .iter()
+            .map(|&i| &self.data[i])
+            .partition(|circle| circle.flags & SIZE_IN_PIXELS != 0);
+
+        let mut layers: Vec<Box<dyn Layer>> = Vec::new();
+        if !in_pixels.is_empty() {
+            // Rings of the same size on screen, just outside the circles.
+            let rings = in_pixels
+                .iter()
+                .map(|circle| Circle {
+                    color: [0.; 4],
+                    radius: circle.radius
+                        + circle.stroke_width / 2.
+                        + HIGHLIGHT_WIDTH_IN_PIXELS / 2.,
+                    stroke_color: color,
+                    stroke_width: HIGHLIGHT_WIDTH_IN_PIXELS,
+                    ..**circle
+                })
+                .collect();
+            layers.push(Box::new(CirclesLayer::new(rings)));
+        }
+        if !in_scene.is_empty() {
+            // Scene-sized strokes would get thinner when zooming out, so the outlines are
+            // polygons of lines sized in pixels instead.
+            let mut lines = Vec::new();
+            for circle in in_scene {
+                let radius = (circle.radius + circle.stroke_width / 2.) as f64;
+                let corners: Vec<[f64; 2]> = (0..HIGHLIGHT_SEGMENTS)
+                    .map(|i| {
+                        let angle = i as f64 / HIGHLIGHT_SEGMENTS as f64 * std::f64::consts::TAU;
+                        [
+                            circle.position[0] + radius * angle.cos(),
+                            circle.position[1] + radius * angle.sin(),
+                        ]
+                    })
+                    .collect();
+                lines.extend(outline(&corners, color));
+            }
+            layers.push(Box::new(LinesLayer::new(lines)));
+        }
+        Some(Box::new(LayerGroup::new(layers)))
+    }
+}
+
+impl ShapeLayer for CirclesLayer {
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::Circle
+    }
+
+    fn shape_count(&self) -> usize {
+        self.data.len()
+    }
+
+    fn shape(&self, index: usize) -> Shape {
+        Shape::Circle(self.data[index])
+    }
+
+    fn set_shape(&mut self, index: usize, shape: Shape) -> bool {
+        match shape.into_circle() {
+            Some(circle) => {
+                self.data[index] = circle;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert_shape(&mut self, index: usize, shape: Shape, id: u64) -> bool {
+        match shape.into_circle() {
+            Some(circle) => {
+                self.ids.for_edit(self.data.len()).insert(index, id);
+                self.data.insert(index, circle);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_shape(&mut self, index: usize) -> (Shape, u64) {
+        let id = self.ids.for_edit(self.data.len()).remove(index);
+        (Shape::Circle(self.data.remove(index)), id)
+    }
 }
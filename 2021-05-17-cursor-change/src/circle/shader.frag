@@ -2,18 +2,33 @@
 
 layout(location=0) in vec4 v_color;
 layout(location=1) in vec2 v_coord;
+layout(location=2) flat in float v_radius;
+layout(location=3) in vec4 v_stroke_color;
+layout(location=4) flat in float v_stroke_width;
+layout(location=5) in vec2 v_position;
 
 layout(location=0) out vec4 f_color;
 
+#include "../fill.glsl"
+
 void main() {
-    float r = dot(v_coord, v_coord);
+    float r = length(v_coord);
     float delta = fwidth(r);
 
-    float alpha = 1.0 - smoothstep(1.0 - delta*2., 1.0, r);
+    float outer = v_radius + v_stroke_width / 2.;
+    float inner = v_radius - v_stroke_width / 2.;
+
+    float alpha = 1.0 - smoothstep(outer - delta*2., outer, r);
 
     if (alpha < 0.01) {
         discard;
     }
 
-    f_color = vec4(v_color.rgb * alpha, v_color.a * alpha);
+    // Blend premultiplied colors, so that a transparent fill gives a clean ring.
+    vec4 color = fill_color(v_color, v_position);
+    vec4 fill = vec4(color.rgb * color.a, color.a);
+    vec4 stroke = vec4(v_stroke_color.rgb * v_stroke_color.a, v_stroke_color.a);
+    float stroke_amount = v_stroke_width > 0. ? smoothstep(inner - delta*2., inner, r) : 0.;
+
+    f_color = mix(fill, stroke, stroke_amount) * alpha;
 }
@@ -3,37 +3,61 @@
 layout(location=0) in vec2 a_position;
 layout(location=1) in vec4 a_color;
 layout(location=2) in float a_radius;
+layout(location=3) in vec4 a_stroke_color;
+layout(location=4) in float a_stroke_width;
+layout(location=5) in uint a_flags;
 
 layout(location=0) out vec4 v_color;
 layout(location=1) out vec2 v_coord;
+layout(location=2) flat out float v_radius;
+layout(location=3) out vec4 v_stroke_color;
+layout(location=4) flat out float v_stroke_width;
+layout(location=5) out vec2 v_position;
 
 layout(set=0, binding=0)
 uniform Uniforms {
     mat4 u_transform;
+    vec2 u_viewport_size;
 };
 
+#include "../layer.glsl"
+
 void main() {
+    float radius = a_radius;
+    float stroke_width = a_stroke_width;
+    if ((a_flags & SIZE_IN_PIXELS) != 0) {
+        // Scene units per pixel.
+        float px = 2. / (u_transform[0][0] * u_viewport_size.x);
+        radius *= px;
+        stroke_width *= px;
+    }
+
+    // The stroke is centered on the edge, so half of it lies outside the radius.
+    float outer = radius + stroke_width / 2.;
+
     switch (gl_VertexIndex) {
         case 0:
-            gl_Position = vec4(a_position.x - a_radius, a_position.y - a_radius, 0., 1.);
             v_coord = vec2(-1., -1.);
             break;
         case 1:
         case 3:
-            gl_Position = vec4(a_position.x + a_radius, a_position.y - a_radius, 0., 1.);
             v_coord = vec2(1., -1.);
             break;
         case 2:
         case 4:
-            gl_Position = vec4(a_position.x - a_radius, a_position.y + a_radius, 0., 1.);
             v_coord = vec2(-1., 1.);
             break;
         case 5:
-            gl_Position = vec4(a_position.x + a_radius, a_position.y + a_radius, 0., 1.);
             v_coord = vec2(1., 1.);
     }
 
-    gl_Position = u_transform * gl_Position;
+    // v_coord is in scene units relative to the center.
+    v_coord *= outer;
+    v_position = a_position + v_coord;
+    gl_Position = u_transform * vec4(v_position, 0., 1.);
 
     v_color = a_color;
+    v_radius = radius;
+    v_stroke_color = a_stroke_color;
+    v_stroke_width = stroke_width;
 }
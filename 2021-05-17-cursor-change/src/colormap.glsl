@@ -0,0 +1,48 @@
+// Polynomial fits of matplotlib's perceptually uniform colormaps, from
+// https://www.shadertoy.com/view/WlfXRN. Must match the variants of `Colormap`.
+const uint COLORMAP_VIRIDIS = 0;
+const uint COLORMAP_MAGMA = 1;
+const uint COLORMAP_INFERNO = 2;
+const uint COLORMAP_PLASMA = 3;
+
+// Maps t from 0 to 1 onto the colormap, as a linear color.
+vec3 colormap(uint kind, float t) {
+    vec3 c0, c1, c2, c3, c4, c5, c6;
+    if (kind == COLORMAP_MAGMA) {
+        c0 = vec3(-0.002136485053939582, -0.000749655052795221, -0.005386127855323933);
+        c1 = vec3(0.2516605407371642, 0.6775232436837668, 2.494026599312351);
+        c2 = vec3(8.353717279216625, -3.577719514958484, 0.3144679030132573);
+        c3 = vec3(-27.66873308576866, 14.26473078096533, -13.64921318813922);
+        c4 = vec3(52.17613981234068, -27.94360607168351, 12.94416944238394);
+        c5 = vec3(-50.76852536473588, 29.04658282127291, 4.23415299384598);
+        c6 = vec3(18.65570506591883, -11.48977351997711, -5.601961508734096);
+    } else if (kind == COLORMAP_INFERNO) {
+        c0 = vec3(0.0002189403691192265, 0.001651004631001012, -0.01948089843709184);
+        c1 = vec3(0.1065134194856116, 0.5639564367884091, 3.932712388889277);
+        c2 = vec3(11.60249308247187, -3.972853965665698, -15.9423941062914);
+        c3 = vec3(-41.70399613139459, 17.43639888205313, 44.35414519872813);
+        c4 = vec3(77.162935699427, -33.40235894210092, -81.80730925738993);
+        c5 = vec3(-71.31942824499214, 32.62606426397723, 73.20951985803202);
+        c6 = vec3(25.13112622477341, -12.24266895238567, -23.07032500287172);
+    } else if (kind == COLORMAP_PLASMA) {
+        c0 = vec3(0.05873234392399702, 0.02333670892565664, 0.5433401826748754);
+        c1 = vec3(2.176514634195958, 0.2383834171260182, 0.7539604599784036);
+        c2 = vec3(-2.689460476458034, -7.455851135738909, 3.110799939717086);
+        c3 = vec3(6.130348345893603, 42.3461881477227, -28.51885465332158);
+        c4 = vec3(-11.10743619062271, -82.66631109428045, 60.13984767418263);
+        c5 = vec3(10.02306557647065, 71.41361770095349, -54.07218655560067);
+        c6 = vec3(-3.658713842777788, -22.93153465461149, 18.19190778539828);
+    } else {
+        c0 = vec3(0.2777273272234177, 0.005407344544966578, 0.3340998053353061);
+        c1 = vec3(0.1050930431085774, 1.404613529898575, 1.384590162594685);
+        c2 = vec3(-0.3308618287255563, 0.214847559468213, 0.09509516302823659);
+        c3 = vec3(-4.634230498983486, -5.799100973351585, -19.33244095627987);
+        c4 = vec3(6.228269936347081, 14.17993336680509, 56.69055260068105);
+        c5 = vec3(4.776384997670288, -13.74514537774601, -65.35303263337234);
+        c6 = vec3(-5.435455855934631, 4.645852612178535, 26.3124352495832);
+    }
+
+    vec3 srgb = c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+    // The swap chain expects linear colors.
+    return pow(clamp(srgb, 0., 1.), vec3(2.2));
+}
@@ -0,0 +1,9 @@
+/// A perceptually uniform colormap from matplotlib. Must match the constants in
+/// colormap.glsl.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Colormap {
+    Viridis = 0,
+    Magma = 1,
+    Inferno = 2,
+    Plasma = 3,
+}
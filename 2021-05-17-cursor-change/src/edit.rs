@@ -0,0 +1,371 @@
+use crate::circle::{Circle, CirclesLayer};
+use crate::history::Command;
+use crate::layer::{Layer, SIZE_IN_PIXELS};
+use crate::line::{Line, LinesLayer};
+use crate::rectangle::{Rectangle, RectanglesLayer};
+
+// Style of shapes created with the editing tools.
+const NEW_FILL_COLOR: [f32; 4] = [0.2, 0.45, 0.8, 1.];
+const NEW_STROKE_COLOR: [f32; 4] = [0., 0., 0., 1.];
+const NEW_CIRCLE_STROKE_WIDTH: f32 = 2.;
+const NEW_STROKE_WIDTH_IN_PIXELS: f32 = 1.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShapeKind {
+    Circle,
+    Rectangle,
+    Line,
+}
+
+/// An instance of a layer that can be edited.
+#[derive(Copy, Clone, Debug)]
+pub enum Shape {
+    Circle(Circle),
+    Rectangle(Rectangle),
+    Line(Line),
+}
+
+impl Shape {
+    /// A shape of the default style, dragged out from `start` to `end`: a circle centered
+    /// on `start`, or a rectangle or line between both.
+    pub fn new(kind: ShapeKind, start: [f64; 2], end: [f64; 2]) -> Self {
+        match kind {
+            ShapeKind::Circle => Shape::Circle(Circle {
+                position: start,
+                color: NEW_FILL_COLOR,
+                radius: (end[0] - start[0]).hypot(end[1] - start[1]) as f32,
+                stroke_color: NEW_STROKE_COLOR,
+                stroke_width: NEW_CIRCLE_STROKE_WIDTH,
+                flags: 0,
+            }),
+            ShapeKind::Rectangle => Shape::Rectangle(Rectangle {
+                upper_left: start,
+                bottom_right: end,
+                color: NEW_FILL_COLOR,
+                stroke_color: NEW_STROKE_COLOR,
+                stroke_width: NEW_STROKE_WIDTH_IN_PIXELS,
+                corner_radius: 0.,
+                flags: SIZE_IN_PIXELS,
+            }),
+            ShapeKind::Line => Shape::Line(Line {
+                start,
+                end,
+                color: NEW_STROKE_COLOR,
+                width: NEW_STROKE_WIDTH_IN_PIXELS,
+                dash_array: [0.; 4],
+                dash_offset: 0.,
+                flags: SIZE_IN_PIXELS,
+            }),
+        }
+    }
+
+    pub fn kind(&self) -> ShapeKind {
+        match self {
+            Shape::Circle(_) => ShapeKind::Circle,
+            Shape::Rectangle(_) => ShapeKind::Rectangle,
+            Shape::Line(_) => ShapeKind::Line,
+        }
+    }
+
+    /// A new layer with this shape as its only instance, with ID `id`.
+    pub fn into_layer(self, id: u64) -> Box<dyn Layer> {
+        match self {
+            Shape::Circle(circle) => Box::new(CirclesLayer::new(vec![circle]).with_ids(vec![id])),
+            Shape::Rectangle(rect) => Box::new(RectanglesLayer::new(vec![rect]).with_ids(vec![id])),
+            Shape::Line(line) => Box::new(LinesLayer::new(vec![line]).with_ids(vec![id])),
+        }
+    }
+
+    /// The shape moved by `offset`.
+    pub fn translated(&self, [dx, dy]: [f64; 2]) -> Self {
+        let offset = |[x, y]: [f64; 2]| [x + dx, y + dy];
+        match *self {
+            Shape::Circle(circle) => Shape::Circle(Circle {
+                position: offset(circle.position),
+                ..circle
+            }),
+            Shape::Rectangle(rect) => Shape::Rectangle(Rectangle {
+                upper_left: offset(rect.upper_left),
+                bottom_right: offset(rect.bottom_right),
+                ..rect
+            }),
+            Shape::Line(line) => Shape::Line(Line {
+                start: offset(line.start),
+                end: offset(line.end),
+                ..line
+            }),
+        }
+    }
+
+    /// Positions of the handles that reshape the shape: the corners of a rectangle, the end
+    /// points of a line, and a point on the edge of a circle that sets its radius. Circles
+    /// sized in pixels have none.
+    pub fn handles(&self) -> Vec<[f64; 2]> {
+        match self {
+            Shape::Circle(circle) if circle.flags & SIZE_IN_PIXELS != 0 => Vec::new(),
+            Shape::Circle(circle) => vec![[
+                circle.position[0] + circle.radius as f64,
+                circle.position[1],
+            ]],
+            Shape::Rectangle(rect) => {
+                let [x1, y1] = rect.upper_left;
+                let [x2, y2] = rect.bottom_right;
+                vec![[x1, y1], [x2, y1], [x2, y2], [x1, y2]]
+            }
+            Shape::Line(line) => vec![line.start, line.end],
+        }
+    }
+
+    /// The shape with handle `handle`, an index into `handles`, dragged to `position`.
+    pub fn with_handle(&self, handle: usize, [x, y]: [f64; 2]) -> Self {
+        match *self {
+            Shape::Circle(circle) => Shape::Circle(Circle {
+                radius: (x - circle.position[0]).hypot(y - circle.position[1]) as f32,
+                ..circle
+            }),
+            Shape::Rectangle(mut rect) => {
+                match handle {
+                    0 => rect.upper_left = [x, y],
+                    1 => {
+                        rect.bottom_right[0] = x;
+                        rect.upper_left[1] = y;
+                    }
+                    2 => rect.bottom_right = [x, y],
+                    _ => {
+                        rect.upper_left[0] = x;
+                        rect.bottom_right[1] = y;
+                    }
+                }
+                Shape::Rectangle(rect)
+            }
+            Shape::Line(mut line) => {
+                if handle == 0 {
+                    line.start = [x, y];
+                } else {
+                    line.end = [x, y];
+                }
+                Shape::Line(line)
+            }
+        }
+    }
+
+    pub fn into_circle(self) -> Option<Circle> {
+        match self {
+            Shape::Circle(circle) => Some(circle),
+            _ => None,
+        }
+    }
+
+    pub fn into_rectangle(self) -> Option<Rectangle> {
+        match self {
+            Shape::Rectangle(rect) => Some(rect),
+            _ => None,
+        }
+    }
+
+    pub fn into_line(self) -> Option<Line> {
+        match self {
+            Shape::Line(line) => Some(line),
+            _ => None,
+        }
+    }
+}
+
+/// A layer of `Shape`s of a single kind that can be edited in place. Instances keep their
+/// IDs as others are inserted and removed.
+pub trait ShapeLayer {
+    fn kind(&self) -> ShapeKind;
+
+    fn shape_count(&self) -> usize;
+
+    fn shape(&self, index: usize) -> Shape;
+
+    /// Replaces the instance at `index` with `shape`. Returns false, leaving the layer
+    /// unchanged, if `shape` is not of the layer's kind.
+    fn set_shape(&mut self, index: usize, shape: Shape) -> bool;
+
+    /// Inserts `shape` at `index` with ID `id`. Returns false, leaving the layer unchanged,
+    /// if `shape` is not of the layer's kind.
+    fn insert_shape(&mut self, index: usize, shape: Shape, id: u64) -> bool;
+
+    /// Removes the instance at `index`, and returns it with its ID.
+    fn remove_shape(&mut self, index: usize) -> (Shape, u64);
+}
+
+/// A left drag of an editing tool in progress, which edits the layers through commands so
+/// that the whole drag can be undone in one step.
+pub enum EditDrag {
+    /// Moves the shapes at `(layer, index)`, last dragged to `last`.
+    Move {
+        shapes: Vec<(usize, usize)>,
+        last: [f64; 2],
+    },
+    /// Drags handle `handle` of a shape.
+    Handle {
+        layer: usize,
+        index: usize,
+        handle: usize,
+    },
+    /// Drags out a new shape from `start`, already inserted at `index` of `layer`.
+    Create {
+        layer: usize,
+        index: usize,
+        kind: ShapeKind,
+        start: [f64; 2],
+    },
+}
+
+fn shape_at(layers: &[Box<dyn Layer>], layer: usize, index: usize) -> Shape {
+    layers[layer]
+        .as_shapes()
+        .expect("Drags edit layers of shapes")
+        .shape(index)
+}
+
+impl EditDrag {
+    /// The command that follows the drag to scene position `position`.
+    pub fn command_to(&mut self, layers: &[Box<dyn Layer>], position: [f64; 2]) -> Command {
+        match self {
+            EditDrag::Move { shapes, last } => {
+                let offset = [position[0] - last[0], position[1] - last[1]];
+                *last = position;
+                Command::Group(
+                    shapes
+                        .iter()
+                        .map(|&(layer, index)| Command::Move {
+                            layer,
+                            index,
+                            offset,
+                        })
+                        .collect(),
+                )
+            }
+            EditDrag::Handle {
+                layer,
+                index,
+                handle,
+            } => {
+                let before = shape_at(layers, *layer, *index);
+                Command::Change {
+                    layer: *layer,
+                    index: *index,
+                    before,
+                    after: before.with_handle(*handle, position),
+                }
+            }
+            EditDrag::Create {
+                layer,
+                index,
+                kind,
+                start,
+            } => Command::Change {
+                layer: *layer,
+                index: *index,
+                before: shape_at(layers, *layer, *index),
+                after: Shape::new(*kind, *start, position),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle() -> Shape {
+        Shape::new(ShapeKind::Rectangle, [0., 0.], [2., 1.])
+    }
+
+    #[test]
+    fn translated() {
+        let offset = [3., -1.];
+        match Shape::new(ShapeKind::Circle, [1., 2.], [4., 6.]).translated(offset) {
+            Shape::Circle(circle) => {
+                assert_eq!(circle.position, [4., 1.]);
+                assert_eq!(circle.radius, 5.);
+            }
+            shape => panic!("Not a circle: {:?}", shape),
+        }
+        match rectangle().translated(offset) {
+            Shape::Rectangle(rect) => {
+                assert_eq!(rect.upper_left, [3., -1.]);
+                assert_eq!(rect.bottom_right, [5., 0.]);
+            }
+            shape => panic!("Not a rectangle: {:?}", shape),
+        }
+        match Shape::new(ShapeKind::Line, [0., 0.], [1., 1.]).translated(offset) {
+            Shape::Line(line) => {
+                assert_eq!(line.start, [3., -1.]);
+                assert_eq!(line.end, [4., 0.]);
+                assert_eq!(line.width, NEW_STROKE_WIDTH_IN_PIXELS);
+            }
+            shape => panic!("Not a line: {:?}", shape),
+        }
+    }
+
+    #[test]
+    fn handles() {
+        let circle = Shape::new(ShapeKind::Circle, [1., 2.], [4., 6.]);
+        assert_eq!(circle.handles(), vec![[6., 2.]]);
+        let mut pixel_circle = circle;
+        if let Shape::Circle(circle) = &mut pixel_circle {
+            circle.flags |= SIZE_IN_PIXELS;
+        }
+        assert!(pixel_circle.handles().is_empty());
+        assert_eq!(
+            rectangle().handles(),
+            vec![[0., 0.], [2., 0.], [2., 1.], [0., 1.]]
+        );
+        assert_eq!(
+            Shape::new(ShapeKind::Line, [0., 0.], [1., 2.]).handles(),
+            vec![[0., 0.], [1., 2.]]
+        );
+    }
+
+    #[test]
+    fn circle_handle_sets_radius() {
+        let circle = Shape::new(ShapeKind::Circle, [1., 2.], [4., 6.]);
+        match circle.with_handle(0, [1., -1.]) {
+            Shape::Circle(circle) => {
+                assert_eq!(circle.position, [1., 2.]);
+                assert_eq!(circle.radius, 3.);
+            }
+            shape => panic!("Not a circle: {:?}", shape),
+        }
+    }
+
+    #[test]
+    fn line_handles_move_end_points() {
+        let line = Shape::new(ShapeKind::Line, [0., 0.], [1., 2.]);
+        assert_eq!(
+            line.with_handle(0, [5., 5.]).handles(),
+            vec![[5., 5.], [1., 2.]]
+        );
+        assert_eq!(
+            line.with_handle(1, [5., 5.]).handles(),
+            vec![[0., 0.], [5., 5.]]
+        );
+    }
+
+    #[test]
+    fn rectangle_handles_keep_the_opposite_corner() {
+        // Including past the opposite corner, where the stored corners swap roles: the
+        // dragged handle must still be the one under the cursor.
+        for &target in &[[0.5, 0.5], [-1., 3.], [4., -2.]] {
+            let before = rectangle().handles();
+            for handle in 0..4 {
+                let after = rectangle().with_handle(handle, target).handles();
+                assert_eq!(after[handle], target, "handle {}", handle);
+                let opposite = (handle + 2) % 4;
+                assert_eq!(after[opposite], before[opposite], "handle {}", handle);
+            }
+        }
+    }
+
+    #[test]
+    fn conversions_check_the_kind() {
+        assert!(rectangle().into_rectangle().is_some());
+        assert!(rectangle().into_circle().is_none());
+        assert!(rectangle().into_line().is_none());
+    }
+}
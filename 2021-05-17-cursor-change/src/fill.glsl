@@ -0,0 +1,39 @@
+// Per-layer fill shared by the shape fragment shaders. Must match `Fill` in fill.rs.
+
+layout(set=1, binding=0)
+uniform FillUniforms {
+    uint u_fill_kind;
+    // Linear: start and end. Radial: center and radius. Texture: origin and size of a tile.
+    vec4 u_fill_params;
+};
+layout(set=1, binding=1) uniform texture2D u_fill_texture;
+layout(set=1, binding=2) uniform sampler u_fill_sampler;
+
+const uint FILL_SOLID = 0;
+const uint FILL_LINEAR_GRADIENT = 1;
+const uint FILL_RADIAL_GRADIENT = 2;
+const uint FILL_TEXTURE = 3;
+
+// Returns the fill color at a point in scene coordinates relative to the upload origin, like
+// the uniforms. The instance color is used as is for solid fills, otherwise only its alpha
+// is kept.
+vec4 fill_color(vec4 color, vec2 position) {
+    if (u_fill_kind == FILL_SOLID) {
+        return color;
+    }
+
+    vec2 uv;
+    if (u_fill_kind == FILL_LINEAR_GRADIENT) {
+        vec2 axis = u_fill_params.zw - u_fill_params.xy;
+        uv = vec2(dot(position - u_fill_params.xy, axis) / dot(axis, axis), 0.5);
+    } else if (u_fill_kind == FILL_RADIAL_GRADIENT) {
+        uv = vec2(length(position - u_fill_params.xy) / u_fill_params.z, 0.5);
+    } else {
+        vec2 t = (position - u_fill_params.xy) / u_fill_params.zw;
+        // Images are stored top row first, but scene Y goes up.
+        uv = vec2(t.x, 1. - t.y);
+    }
+
+    vec4 sampled = texture(sampler2D(u_fill_texture, u_fill_sampler), uv);
+    return vec4(sampled.rgb, sampled.a * color.a);
+}
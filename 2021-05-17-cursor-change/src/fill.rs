@@ -0,0 +1,388 @@
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+
+use image::{ImageResult, RgbaImage};
+use wgpu::util::DeviceExt;
+use wgpu::{AddressMode, BindGroup, BindGroupLayout, Buffer, Device, FilterMode, Queue};
+
+use crate::layer::relative_to;
+use crate::svg::{linear_to_srgb, svg_paint, SvgIds};
+use crate::texture::upload_rgba;
+
+// Must match the constants in fill.glsl.
+const FILL_SOLID: u32 = 0;
+const FILL_LINEAR_GRADIENT: u32 = 1;
+const FILL_RADIAL_GRADIENT: u32 = 2;
+const FILL_TEXTURE: u32 = 3;
+
+// Gradients are baked into a ramp texture of this many texels.
+const GRADIENT_RESOLUTION: u32 = 256;
+
+#[derive(Copy, Clone, Debug)]
+pub struct GradientStop {
+    /// Position along the gradient, from 0 to 1.
+    pub offset: f32,
+    pub color: [f32; 4],
+}
+
+/// How the shapes of a layer are filled. Gradient and texture coordinates are in scene
+/// units, so the fill stays put while the shapes move through it. They replace the color
+/// of each instance, except for its alpha.
+///
+/// Gradient stops must be sorted by offset; `Fill::linear_gradient` and
+/// `Fill::radial_gradient` sort them.
+pub enum Fill {
+    /// Each instance's own color.
+    Solid,
+    LinearGradient {
+        start: [f64; 2],
+        end: [f64; 2],
+        stops: Vec<GradientStop>,
+    },
+    RadialGradient {
+        center: [f64; 2],
+        radius: f32,
+        stops: Vec<GradientStop>,
+    },
+    /// An image repeated in tiles, with one tile at `origin` (its lower left corner).
+    Texture {
+        path: PathBuf,
+        image: RgbaImage,
+        origin: [f64; 2],
+        size: [f32; 2],
+    },
+}
+
+// The part of a fill that depends on the upload origin.
+#[derive(Copy, Clone, Debug)]
+enum Placement {
+    Solid,
+    LinearGradient { start: [f64; 2], end: [f64; 2] },
+    RadialGradient { center: [f64; 2], radius: f32 },
+    Texture { origin: [f64; 2], size: [f32; 2] },
+}
+
+/// A fill uploaded as bind group 1 of a shape pipeline.
+pub struct FillBinding {
+    pub bind_group: BindGroup,
+    uniform_buffer: Buffer,
+    placement: Placement,
+}
+
+impl FillBinding {
+    /// Moves the fill to a new upload origin.
+    pub fn rebase(&self, queue: &Queue, origin: [f64; 2]) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.placement.uniforms(origin)]),
+        );
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct FillUniforms {
+    kind: u32,
+    _padding: [u32; 3],
+    params: [f32; 4],
+}
+
+impl Fill {
+    pub fn linear_gradient(start: [f64; 2], end: [f64; 2], stops: Vec<GradientStop>) -> Fill {
+        Fill::LinearGradient {
+            start,
+            end,
+            stops: sorted(stops),
+        }
+    }
+
+    pub fn radial_gradient(center: [f64; 2], radius: f32, stops: Vec<GradientStop>) -> Fill {
+        Fill::RadialGradient {
+            center,
+            radius,
+            stops: sorted(stops),
+        }
+    }
+
+    /// Loads a PNG (or any other format `image` understands) as a tiled texture fill.
+    pub fn texture(path: impl AsRef<Path>, origin: [f64; 2], size: [f32; 2]) -> ImageResult<Fill> {
+        let path = path.as_ref().to_path_buf();
+        let image = image::open(&path)?.into_rgba8();
+        Ok(Fill::Texture {
+            path,
+            image,
+            origin,
+            size,
+        })
+    }
+
+    fn placement(&self) -> Placement {
+        match *self {
+            Fill::Solid => Placement::Solid,
+            Fill::LinearGradient { start, end, .. } => Placement::LinearGradient { start, end },
+            Fill::RadialGradient { center, radius, .. } => {
+                Placement::RadialGradient { center, radius }
+            }
+            Fill::Texture { origin, size, .. } => Placement::Texture { origin, size },
+        }
+    }
+
+    /// The texture sampled by the shaders, as RGBA sRGB pixels with its width and height.
+    fn texels(&self) -> (Vec<u8>, u32, u32) {
+        match self {
+            // Never sampled, but the bind group needs a texture.
+            Fill::Solid => (vec![255; 4], 1, 1),
+            Fill::LinearGradient { stops, .. } | Fill::RadialGradient { stops, .. } => {
+                (gradient_ramp(stops), GRADIENT_RESOLUTION, 1)
+            }
+            Fill::Texture { image, .. } => (image.as_raw().clone(), image.width(), image.height()),
+        }
+    }
+
+    pub fn bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Fill bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    /// Uploads the fill's uniforms and texture, for use as bind group 1 of a shape pipeline.
+    pub fn create_bind_group(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        layout: &BindGroupLayout,
+        origin: [f64; 2],
+    ) -> FillBinding {
+        let placement = self.placement();
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Fill uniform buffer"),
+            contents: bytemuck::cast_slice(&[placement.uniforms(origin)]),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let (texels, width, height) = self.texels();
+        let view = upload_rgba(device, queue, "Fill texture", &texels, width, height);
+
+        // Gradients are clamped to their end colors, textures repeat.
+        let address_mode = match self {
+            Fill::Texture { .. } => AddressMode::Repeat,
+            _ => AddressMode::ClampToEdge,
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Fill sampler"),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Fill bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        FillBinding {
+            bind_group,
+            uniform_buffer,
+            placement,
+        }
+    }
+
+    /// Writes the gradient or pattern definition, if any, and returns the SVG paint that
+    /// refers to it.
+    pub fn write_svg_defs(
+        &self,
+        out: &mut dyn Write,
+        ids: &mut SvgIds,
+    ) -> Result<Option<String>, fmt::Error> {
+        let id = match self {
+            Fill::Solid => return Ok(None),
+            _ => ids.unique("fill"),
+        };
+
+        match self {
+            Fill::Solid => {}
+            Fill::LinearGradient { start, end, stops } => {
+                writeln!(
+                    out,
+                    r#"<linearGradient id="{}" gradientUnits="userSpaceOnUse" x1="{}" y1="{}" x2="{}" y2="{}">"#,
+                    id, start[0], start[1], end[0], end[1]
+                )?;
+                write_stops(out, stops)?;
+                writeln!(out, "</linearGradient>")?;
+            }
+            Fill::RadialGradient {
+                center,
+                radius,
+                stops,
+            } => {
+                writeln!(
+                    out,
+                    r#"<radialGradient id="{}" gradientUnits="userSpaceOnUse" cx="{}" cy="{}" r="{}">"#,
+                    id, center[0], center[1], radius
+                )?;
+                write_stops(out, stops)?;
+                writeln!(out, "</radialGradient>")?;
+            }
+            Fill::Texture {
+                path, origin, size, ..
+            } => {
+                // The image is flipped inside the tile, so that it ends up upright once the
+                // scene's Y axis is flipped.
+                writeln!(
+                    out,
+                    r#"<pattern id="{}" patternUnits="userSpaceOnUse" x="{}" y="{}" width="{}" height="{}">"#,
+                    id, origin[0], origin[1], size[0], size[1]
+                )?;
+                writeln!(
+                    out,
+                    r#"<image href="{}" width="{}" height="{}" preserveAspectRatio="none" transform="translate(0 {}) scale(1 -1)"/>"#,
+                    path.display(),
+                    size[0],
+                    size[1],
+                    size[1]
+                )?;
+                writeln!(out, "</pattern>")?;
+            }
+        }
+
+        Ok(Some(format!("url(#{})", id)))
+    }
+}
+
+impl Placement {
+    // Positions are relative to `origin`, like the shapes' positions.
+    fn uniforms(&self, origin: [f64; 2]) -> FillUniforms {
+        let (kind, params) = match *self {
+            Placement::Solid => (FILL_SOLID, [0.; 4]),
+            Placement::LinearGradient { start, end } => {
+                let [x1, y1] = relative_to(start, origin);
+                let [x2, y2] = relative_to(end, origin);
+                (FILL_LINEAR_GRADIENT, [x1, y1, x2, y2])
+            }
+            Placement::RadialGradient { center, radius } => {
+                let [x, y] = relative_to(center, origin);
+                (FILL_RADIAL_GRADIENT, [x, y, radius, 0.])
+            }
+            Placement::Texture {
+                origin: tile_origin,
+                size,
+            } => {
+                // Only the position within a tile matters, so the tile origin is moved next
+                // to the upload origin to keep it small.
+                let offset = |axis: usize| {
+                    let size = size[axis] as f64;
+                    let tiles = ((origin[axis] - tile_origin[axis]) / size).floor();
+                    (tile_origin[axis] + tiles * size - origin[axis]) as f32
+                };
+                (FILL_TEXTURE, [offset(0), offset(1), size[0], size[1]])
+            }
+        };
+
+        FillUniforms {
+            kind,
+            _padding: [0; 3],
+            params,
+        }
+    }
+}
+
+fn sorted(mut stops: Vec<GradientStop>) -> Vec<GradientStop> {
+    stops.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+    stops
+}
+
+fn write_stops(out: &mut dyn Write, stops: &[GradientStop]) -> fmt::Result {
+    for stop in stops {
+        let (color, opacity) = svg_paint(stop.color);
+        writeln!(
+            out,
+            r#"<stop offset="{}" stop-color="{}" stop-opacity="{}"/>"#,
+            stop.offset, color, opacity
+        )?;
+    }
+    Ok(())
+}
+
+/// Samples the gradient into sRGB texels. Colors are interpolated in sRGB, as in SVG.
+fn gradient_ramp(stops: &[GradientStop]) -> Vec<u8> {
+    let srgb = |color: [f32; 4]| {
+        [
+            linear_to_srgb(color[0]) as f32,
+            linear_to_srgb(color[1]) as f32,
+            linear_to_srgb(color[2]) as f32,
+            color[3] * 255.,
+        ]
+    };
+
+    let mut texels = Vec::with_capacity(4 * GRADIENT_RESOLUTION as usize);
+    for i in 0..GRADIENT_RESOLUTION {
+        let t = i as f32 / (GRADIENT_RESOLUTION - 1) as f32;
+
+        let color = match stops.iter().position(|stop| stop.offset > t) {
+            None => stops.last().map(|stop| srgb(stop.color)),
+            Some(0) => Some(srgb(stops[0].color)),
+            Some(next) => {
+                let (a, b) = (&stops[next - 1], &stops[next]);
+                let s = (t - a.offset) / (b.offset - a.offset);
+                let (a, b) = (srgb(a.color), srgb(b.color));
+                let mut color = a;
+                for (c, b) in color.iter_mut().zip(&b) {
+                    *c += (b - *c) * s;
+                }
+                Some(color)
+            }
+        }
+        .unwrap_or([0.; 4]);
+
+        texels.extend(color.iter().map(|c| c.round() as u8));
+    }
+    texels
+}
@@ -0,0 +1,516 @@
+use std::fmt::{self, Write};
+
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, BlendComponent, BlendFactor, BlendOperation, BlendState, Buffer,
+    CommandEncoder, ComputePipeline, Device, Queue, RenderPass, RenderPipeline, Sampler,
+    SwapChainDescriptor, TextureFormat, TextureView,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::colormap::Colormap;
+use crate::layer::{relative_to, Drawable, Layer, SIZE_IN_PIXELS};
+use crate::svg::SvgIds;
+use crate::zoom::ZoomState;
+
+// Half precision floats can be blended on every adapter, unlike full precision ones.
+const DENSITY_FORMAT: TextureFormat = TextureFormat::R16Float;
+// Must match the local size in maximum.comp.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// A sample contributing `weight` to the density around `position`.
+#[derive(Copy, Clone, Debug)]
+pub struct HeatPoint {
+    pub position: [f64; 2],
+    pub weight: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct HeatPointInstance {
+    position: [f32; 2],
+    weight: f32,
+}
+
+impl HeatPoint {
+    fn instance(&self, origin: [f64; 2]) -> HeatPointInstance {
+        HeatPointInstance {
+            position: relative_to(self.position, origin),
+            weight: self.weight,
+        }
+    }
+}
+
+/// The density that is mapped to the top of the colormap.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Normalization {
+    /// The highest density on screen, found on the GPU every frame.
+    Auto,
+    Fixed(f32),
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct SplatUniforms {
+    radius: f32,
+    flags: u32,
+    _padding: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct HeatmapUniforms {
+    colormap: u32,
+    auto_maximum: u32,
+    maximum: f32,
+    _padding: u32,
+}
+
+/// Draws the density of points as a colormapped heatmap. Every point adds a Gaussian kernel
+/// into an offscreen float texture, which a full-screen pass then maps through the colormap.
+pub struct HeatmapLayer {
+    data: Vec<HeatPoint>,
+    radius: f32,
+    flags: u32,
+    colormap: Colormap,
+    normalization: Normalization,
+}
+
+impl HeatmapLayer {
+    pub fn new(data: Vec<HeatPoint>) -> Self {
+        HeatmapLayer {
+            data,
+            radius: 20.,
+            flags: SIZE_IN_PIXELS,
+            colormap: Colormap::Viridis,
+            normalization: Normalization::Auto,
+        }
+    }
+
+    /// Sets the radius the kernels fade out at, in scene units, or in pixels with
+    /// `layer::SIZE_IN_PIXELS`.
+    pub fn with_radius(mut self, radius: f32, flags: u32) -> Self {
+        self.radius = radius;
+        self.flags = flags;
+        self
+    }
+
+    pub fn with_colormap(mut self, colormap: Colormap) -> Self {
+        self.colormap = colormap;
+        self
+    }
+
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+}
+
+struct DensityTexture {
+    view: TextureView,
+    bind_group: BindGroup,
+    size: PhysicalSize<u32>,
+}
+
+impl DensityTexture {
+    fn new(
+        device: &Device,
+        layout: &BindGroupLayout,
+        sampler: &Sampler,
+        uniform_buffer: &Buffer,
+        maximum_buffer: &Buffer,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Heatmap density texture"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DENSITY_FORMAT,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Shared by the maximum and colormap passes.
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Heatmap bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: maximum_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        DensityTexture {
+            view,
+            bind_group,
+            size,
+        }
+    }
+}
+
+pub struct HeatmapLayerDrawable {
+    normalization: Normalization,
+
+    instance_buffer: Buffer,
+    num_points: u32,
+    splat_pipeline: RenderPipeline,
+    splat_bind_group: BindGroup,
+
+    maximum_pipeline: ComputePipeline,
+    maximum_buffer: Buffer,
+
+    shade_pipeline: RenderPipeline,
+    heatmap_layout: BindGroupLayout,
+    uniform_buffer: Buffer,
+    sampler: Sampler,
+    density: DensityTexture,
+}
+
+impl Drawable for HeatmapLayerDrawable {
+    fn update(&self, queue: &Queue, _zoom_state: &ZoomState) {
+        if self.normalization == Normalization::Auto {
+            queue.write_buffer(&self.maximum_buffer, 0, bytemuck::cast_slice(&[0u32]));
+        }
+    }
+
+    fn prepare(&self, encoder: &mut CommandEncoder, transform_bind_group: &BindGroup) {
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Splat pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &self.density.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&self.splat_pipeline);
+            render_pass.set_bind_group(0, transform_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.splat_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+            render_pass.draw(0..6, 0..self.num_points);
+        }
+
+        if self.normalization == Normalization::Auto {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Maximum pass"),
+            });
+            compute_pass.set_pipeline(&self.maximum_pipeline);
+            compute_pass.set_bind_group(0, &self.density.bind_group, &[]);
+            compute_pass.dispatch(
+                (self.density.size.width + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+                (self.density.size.height + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+                1,
+            );
+        }
+    }
+
+    fn resize(&mut self, device: &Device, size: PhysicalSize<u32>) {
+        self.density = DensityTexture::new(
+            device,
+            &self.heatmap_layout,
+            &self.sampler,
+            &self.uniform_buffer,
+            &self.maximum_buffer,
+            size,
+        );
+    }
+
+    fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, _bind_group: &'a BindGroup) {
+        render_pass.set_pipeline(&self.shade_pipeline);
+        render_pass.set_bind_group(0, &self.density.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    bind_group_layouts: &[&BindGroupLayout],
+    vs_module: &wgpu::ShaderModule,
+    fs_module: &wgpu::ShaderModule,
+    buffers: &[wgpu::VertexBufferLayout],
+    target: wgpu::ColorTargetState,
+) -> RenderPipeline {
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: vs_module,
+            entry_point: "main",
+            buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: fs_module,
+            entry_point: "main",
+            targets: &[target],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            clamp_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
+
+impl Layer for HeatmapLayer {
+    fn init_drawable(
+        &self,
+        device: &Device,
+        _queue: &Queue,
+        sc_desc: &SwapChainDescriptor,
+        transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
+    ) -> Box<dyn Drawable> {
+        let instances: Vec<HeatPointInstance> =
+            self.data.iter().map(|d| d.instance(origin)).collect();
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance buffer"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+
+        let splat_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Splat uniform buffer"),
+            contents: bytemuck::cast_slice(&[SplatUniforms {
+                radius: self.radius,
+                flags: self.flags,
+                _padding: [0; 2],
+            }]),
+            usage: wgpu::BufferUsage::UNIFORM,
+        });
+        let splat_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Splat bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let splat_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Splat bind group"),
+            layout: &splat_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: splat_buffer.as_entire_binding(),
+            }],
+        });
+
+        // The kernels of all points add up in the density texture.
+        let splat_pipeline = create_pipeline(
+            device,
+            &[transform_layout, &splat_layout],
+            &device.create_shader_module(&wgpu::include_spirv!("splat.vert.spv")),
+            &device.create_shader_module(&wgpu::include_spirv!("splat.frag.spv")),
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<HeatPointInstance>() as wgpu::BufferAddress,
+                step_mode: wgpu::InputStepMode::Instance,
+                attributes: &[
+                    wgpu::VertexAttribute {
+                        offset: 0,
+                        shader_location: 0,
+                        format: wgpu::VertexFormat::Float32x2,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                        shader_location: 1,
+                        format: wgpu::VertexFormat::Float32,
+                    },
+                ],
+            }],
+            wgpu::ColorTargetState {
+                format: DENSITY_FORMAT,
+                write_mask: wgpu::ColorWrite::ALL,
+                blend: Some(BlendState {
+                    color: BlendComponent {
+                        src_factor: BlendFactor::One,
+                        dst_factor: BlendFactor::One,
+                        operation: BlendOperation::Add,
+                    },
+                    alpha: BlendComponent::REPLACE,
+                }),
+            },
+        );
+
+        let heatmap_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Heatmap bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::COMPUTE | wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::COMPUTE | wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: false,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::COMPUTE | wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        // Finds the highest density for automatic normalization.
+        let maximum_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Maximum Pipeline Layout"),
+            bind_group_layouts: &[&heatmap_layout],
+            push_constant_ranges: &[],
+        });
+        let maximum_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Maximum Pipeline"),
+            layout: Some(&maximum_layout),
+            module: &device.create_shader_module(&wgpu::include_spirv!("maximum.comp.spv")),
+            entry_point: "main",
+        });
+        let maximum_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Maximum buffer"),
+            size: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let (auto_maximum, maximum) = match self.normalization {
+            Normalization::Auto => (1, 0.),
+            Normalization::Fixed(maximum) => (0, maximum),
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Heatmap uniform buffer"),
+            contents: bytemuck::cast_slice(&[HeatmapUniforms {
+                colormap: self.colormap as u32,
+                auto_maximum,
+                maximum,
+                _padding: 0,
+            }]),
+            usage: wgpu::BufferUsage::UNIFORM,
+        });
+
+        // The full-screen pass maps the density through the colormap.
+        let shade_pipeline = create_pipeline(
+            device,
+            &[&heatmap_layout],
+            &device.create_shader_module(&wgpu::include_spirv!("../fullscreen.vert.spv")),
+            &device.create_shader_module(&wgpu::include_spirv!("shade.frag.spv")),
+            &[],
+            wgpu::ColorTargetState {
+                format: sc_desc.format,
+                write_mask: wgpu::ColorWrite::ALL,
+                blend: Some(BlendState {
+                    color: BlendComponent::OVER,
+                    alpha: BlendComponent::REPLACE,
+                }),
+            },
+        );
+
+        // Texels are only fetched, never filtered.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Density sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let density = DensityTexture::new(
+            device,
+            &heatmap_layout,
+            &sampler,
+            &uniform_buffer,
+            &maximum_buffer,
+            PhysicalSize::new(sc_desc.width, sc_desc.height),
+        );
+
+        Box::new(HeatmapLayerDrawable {
+            normalization: self.normalization,
+            instance_buffer,
+            num_points: instances.len() as u32,
+            splat_pipeline,
+            splat_bind_group,
+            maximum_pipeline,
+            maximum_buffer,
+            shade_pipeline,
+            heatmap_layout,
+            uniform_buffer,
+            sampler,
+            density,
+        })
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, _pixel_size: f32, _ids: &mut SvgIds) -> fmt::Result {
+        // SVG has no additive blending to build the density from, so the layer is left out.
+        writeln!(
+            out,
+            "<!-- HeatmapLayer with {} points not exported -->",
+            self.data.len()
+        )
+    }
+}
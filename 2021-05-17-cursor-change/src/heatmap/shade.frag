@@ -0,0 +1,34 @@
+#version 450
+
+#include "../colormap.glsl"
+
+layout(location=0) out vec4 f_color;
+
+layout(set=0, binding=0) uniform texture2D t_density;
+layout(set=0, binding=1) uniform sampler s_density;
+layout(set=0, binding=2)
+uniform HeatmapUniforms {
+    uint u_colormap;
+    uint u_auto_maximum;
+    float u_maximum;
+};
+layout(set=0, binding=3)
+readonly buffer Maximum {
+    uint u_maximum_bits;
+};
+
+void main() {
+    // The density texture has the size of the screen.
+    float density = texelFetch(sampler2D(t_density, s_density), ivec2(gl_FragCoord.xy), 0).r;
+    float maximum = u_auto_maximum != 0 ? uintBitsToFloat(u_maximum_bits) : u_maximum;
+    float t = clamp(density / maximum, 0., 1.);
+
+    // Also discards everything while the view is empty and the maximum is zero.
+    if (!(t > 0.004)) {
+        discard;
+    }
+
+    // The lowest densities fade out, so the heatmap has no hard edge.
+    float alpha = smoothstep(0., 0.2, t);
+    f_color = vec4(colormap(u_colormap, t) * alpha, alpha);
+}
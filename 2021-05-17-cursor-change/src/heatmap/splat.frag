@@ -0,0 +1,16 @@
+#version 450
+
+layout(location=0) in vec2 v_coord;
+layout(location=1) flat in float v_weight;
+
+layout(location=0) out float f_density;
+
+void main() {
+    float d2 = dot(v_coord, v_coord);
+    if (d2 > 1.) {
+        discard;
+    }
+
+    // A Gaussian with the radius at three standard deviations, so the cut is invisible.
+    f_density = v_weight * exp(-4.5 * d2);
+}
@@ -0,0 +1,49 @@
+#version 450
+
+layout(location=0) in vec2 a_position;
+layout(location=1) in float a_weight;
+
+layout(location=0) out vec2 v_coord;
+layout(location=1) flat out float v_weight;
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_transform;
+    vec2 u_viewport_size;
+};
+
+layout(set=1, binding=0)
+uniform SplatUniforms {
+    float u_radius;
+    uint u_flags;
+};
+
+#include "../layer.glsl"
+
+void main() {
+    switch (gl_VertexIndex) {
+        case 0:
+            v_coord = vec2(-1., -1.);
+            break;
+        case 1:
+        case 3:
+            v_coord = vec2(1., -1.);
+            break;
+        case 2:
+        case 4:
+            v_coord = vec2(-1., 1.);
+            break;
+        case 5:
+            v_coord = vec2(1., 1.);
+    }
+
+    // v_coord is relative to the center, in units of the kernel radius.
+    if ((u_flags & SIZE_IN_PIXELS) != 0) {
+        gl_Position = u_transform * vec4(a_position, 0., 1.);
+        gl_Position.xy += 2. * v_coord * u_radius / u_viewport_size;
+    } else {
+        gl_Position = u_transform * vec4(a_position + v_coord * u_radius, 0., 1.);
+    }
+
+    v_weight = a_weight;
+}
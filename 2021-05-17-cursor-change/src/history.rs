@@ -0,0 +1,498 @@
+use crate::edit::{Shape, ShapeLayer};
+use crate::layer::Layer;
+
+// Older steps are forgotten beyond this.
+const MAX_STEPS: usize = 1000;
+
+fn shapes(layers: &mut [Box<dyn Layer>], layer: usize) -> &mut dyn ShapeLayer {
+    layers[layer]
+        .as_shapes_mut()
+        .expect("Commands only edit layers of shapes")
+}
+
+// Shapes of the wrong kind for their layer leave it unedited.
+fn edited_if(edited: bool, layer: usize) -> Vec<usize> {
+    if edited {
+        vec![layer]
+    } else {
+        Vec::new()
+    }
+}
+
+/// A reversible edit of the shapes of a list of layers. Commands only touch the instance
+/// data, never the GPU, so the caller uploads the edited layers again.
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Inserts `shape` with ID `id` at `index` of layer `layer`. Inserting into the layer
+    /// just past the last one adds a new layer. Undoing that leaves the new layer in place,
+    /// empty, so that the indices of any layers added later stay valid, and redoing inserts
+    /// into it again.
+    Insert {
+        layer: usize,
+        index: usize,
+        shape: Shape,
+        id: u64,
+    },
+    /// Removes `shape` with ID `id` from `index` of layer `layer`.
+    Delete {
+        layer: usize,
+        index: usize,
+        shape: Shape,
+        id: u64,
+    },
+    /// Moves a shape by `offset`.
+    Move {
+        layer: usize,
+        index: usize,
+        offset: [f64; 2],
+    },
+    /// Replaces a shape, for any change of its properties.
+    Change {
+        layer: usize,
+        index: usize,
+        before: Shape,
+        after: Shape,
+    },
+    /// Several commands applied in order, as a single step.
+    Group(Vec<Command>),
+}
+
+impl Command {
+    /// Applies the command, and returns the indices of the edited layers.
+    pub fn apply(&self, layers: &mut Vec<Box<dyn Layer>>) -> Vec<usize> {
+        match self {
+            Command::Insert {
+                layer,
+                index,
+                shape,
+                id,
+            } => {
+                let inserted = if *layer == layers.len() {
+                    layers.push(shape.into_layer(*id));
+                    true
+                } else {
+                    shapes(layers, *layer).insert_shape(*index, *shape, *id)
+                };
+                edited_if(inserted, *layer)
+            }
+            Command::Delete { layer, index, .. } => {
+                shapes(layers, *layer).remove_shape(*index);
+                vec![*layer]
+            }
+            Command::Move {
+                layer,
+                index,
+                offset,
+            } => {
+                let layer_shapes = shapes(layers, *layer);
+                let moved = layer_shapes.shape(*index).translated(*offset);
+                edited_if(layer_shapes.set_shape(*index, moved), *layer)
+            }
+            Command::Change {
+                layer,
+                index,
+                after,
+                ..
+            } => {
+                let changed = shapes(layers, *layer).set_shape(*index, *after);
+                edited_if(changed, *layer)
+            }
+            Command::Group(commands) => {
+                let mut edited: Vec<usize> = commands
+                    .iter()
+                    .flat_map(|command| command.apply(layers))
+                    .collect();
+                edited.sort_unstable();
+                edited.dedup();
+                edited
+            }
+        }
+    }
+
+    /// The instances the command changes in place, as `(layer, index)`, or `None` if it
+    /// inserts or removes instances, which moves the others.
+    pub fn changed_in_place(&self) -> Option<Vec<(usize, usize)>> {
+        match self {
+            Command::Insert { .. } | Command::Delete { .. } => None,
+            Command::Move { layer, index, .. } | Command::Change { layer, index, .. } => {
+                Some(vec![(*layer, *index)])
+            }
+            Command::Group(commands) => commands.iter().try_fold(Vec::new(), |mut all, command| {
+                all.extend(command.changed_in_place()?);
+                Some(all)
+            }),
+        }
+    }
+
+    /// The command that undoes this one.
+    pub fn inverse(&self) -> Command {
+        match self.clone() {
+            Command::Insert {
+                layer,
+                index,
+                shape,
+                id,
+            } => Command::Delete {
+                layer,
+                index,
+                shape,
+                id,
+            },
+            Command::Delete {
+                layer,
+                index,
+                shape,
+                id,
+            } => Command::Insert {
+                layer,
+                index,
+                shape,
+                id,
+            },
+            Command::Move {
+                layer,
+                index,
+                offset: [dx, dy],
+            } => Command::Move {
+                layer,
+                index,
+                offset: [-dx, -dy],
+            },
+            Command::Change {
+                layer,
+                index,
+                before,
+                after,
+            } => Command::Change {
+                layer,
+                index,
+                before: after,
+                after: before,
+            },
+            Command::Group(commands) => {
+                Command::Group(commands.iter().rev().map(Command::inverse).collect())
+            }
+        }
+    }
+
+    /// A single command with the effect of this one followed by `next`. Consecutive moves
+    /// and changes of the same shape, and changes of a shape right after inserting it,
+    /// collapse into one command.
+    pub fn merge(self, next: Command) -> Command {
+        match (self, next) {
+            (
+                Command::Move {
+                    layer,
+                    index,
+                    offset: [x1, y1],
+                },
+                Command::Move {
+                    layer: next_layer,
+                    index: next_index,
+                    offset: [x2, y2],
+                },
+            ) if (layer, index) == (next_layer, next_index) => Command::Move {
+                layer,
+                index,
+                offset: [x1 + x2, y1 + y2],
+            },
+            (
+                Command::Change {
+                    layer,
+                    index,
+                    before,
+                    ..
+                },
+                Command::Change {
+                    layer: next_layer,
+                    index: next_index,
+                    after,
+                    ..
+                },
+            ) if (layer, index) == (next_layer, next_index) => Command::Change {
+                layer,
+                index,
+                before,
+                after,
+            },
+            (
+                Command::Insert {
+                    layer, index, id, ..
+                },
+                Command::Change {
+                    layer: next_layer,
+                    index: next_index,
+                    after,
+                    ..
+                },
+            ) if (layer, index) == (next_layer, next_index) => Command::Insert {
+                layer,
+                index,
+                shape: after,
+                id,
+            },
+            (Command::Group(commands), Command::Group(next)) if commands.len() == next.len() => {
+                Command::Group(
+                    commands
+                        .into_iter()
+                        .zip(next)
+                        .map(|(command, next)| command.merge(next))
+                        .collect(),
+                )
+            }
+            (command, next) => Command::Group(vec![command, next]),
+        }
+    }
+}
+
+/// The applied commands, as steps that can be undone and redone. While a drag is in
+/// progress, between `begin_merge` and `end_merge`, its commands merge into a single step.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+    merging: bool,
+    // Whether the step being merged into has been recorded yet.
+    step_started: bool,
+}
+
+impl History {
+    /// Applies `command` as a new step, or as part of the current one while merging, and
+    /// returns the indices of the edited layers. Forgets the steps that were undone.
+    pub fn apply(&mut self, layers: &mut Vec<Box<dyn Layer>>, command: Command) -> Vec<usize> {
+        let edited = command.apply(layers);
+        self.redo.clear();
+
+        let step = match self.undo.pop() {
+            Some(last) if self.merging && self.step_started => last.merge(command),
+            last => {
+                self.undo.extend(last);
+                command
+            }
+        };
+        self.undo.push(step);
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+        self.step_started = true;
+        edited
+    }
+
+    pub fn begin_merge(&mut self) {
+        self.merging = true;
+        self.step_started = false;
+    }
+
+    pub fn end_merge(&mut self) {
+        self.merging = false;
+    }
+
+    /// Reverts the last step, and returns the indices of the edited layers, if there was a
+    /// step to undo.
+    pub fn undo(&mut self, layers: &mut Vec<Box<dyn Layer>>) -> Option<Vec<usize>> {
+        let command = self.undo.pop()?;
+        let edited = command.inverse().apply(layers);
+        self.redo.push(command);
+        self.step_started = false;
+        Some(edited)
+    }
+
+    /// Applies the last undone step again, and returns the indices of the edited layers, if
+    /// there was a step to redo.
+    pub fn redo(&mut self, layers: &mut Vec<Box<dyn Layer>>) -> Option<Vec<usize>> {
+        let command = self.redo.pop()?;
+        let edited = command.apply(layers);
+        self.undo.push(command);
+        self.step_started = false;
+        Some(edited)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circle::CirclesLayer;
+    use crate::edit::ShapeKind;
+    use crate::line::LinesLayer;
+    use crate::rectangle::RectanglesLayer;
+
+    fn circle(x: f64) -> Shape {
+        Shape::new(ShapeKind::Circle, [x, 0.], [x + 1., 0.])
+    }
+
+    fn rectangle(x: f64) -> Shape {
+        Shape::new(ShapeKind::Rectangle, [x, 0.], [x + 1., 1.])
+    }
+
+    fn line(x: f64) -> Shape {
+        Shape::new(ShapeKind::Line, [x, 0.], [x + 1., 1.])
+    }
+
+    // Three circles without IDs, five rectangles with IDs and a line.
+    fn scene() -> Vec<Box<dyn Layer>> {
+        let circles = (0..3).map(|i| circle(i as f64).into_circle().unwrap());
+        let rectangles = (0..5).map(|i| rectangle(i as f64).into_rectangle().unwrap());
+        vec![
+            Box::new(CirclesLayer::new(circles.collect())),
+            Box::new(
+                RectanglesLayer::new(rectangles.collect()).with_ids(vec![100, 101, 102, 103, 104]),
+            ),
+            Box::new(LinesLayer::new(vec![line(0.).into_line().unwrap()])),
+        ]
+    }
+
+    // The IDs and shapes of every layer, with shapes compared through their debug output.
+    fn contents(layers: &[Box<dyn Layer>]) -> Vec<Vec<(u64, String)>> {
+        layers
+            .iter()
+            .map(|layer| {
+                let shapes = layer.as_shapes().expect("Only layers of shapes");
+                (0..shapes.shape_count())
+                    .map(|i| (layer.instance_id(i), format!("{:?}", shapes.shape(i))))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn insert(layer: usize, index: usize, shape: Shape, id: u64) -> Command {
+        Command::Insert {
+            layer,
+            index,
+            shape,
+            id,
+        }
+    }
+
+    fn move_by(layer: usize, index: usize, offset: [f64; 2]) -> Command {
+        Command::Move {
+            layer,
+            index,
+            offset,
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_insert() {
+        let mut layers = scene();
+        let mut history = History::default();
+        let original = contents(&layers);
+
+        history.apply(&mut layers, insert(0, 1, circle(10.), 10));
+        let inserted = contents(&layers);
+        assert_eq!(inserted[0].len(), 4);
+        assert_eq!(inserted[0][1].0, 10);
+
+        assert_eq!(history.undo(&mut layers), Some(vec![0]));
+        assert_eq!(contents(&layers), original);
+        assert_eq!(history.redo(&mut layers), Some(vec![0]));
+        assert_eq!(contents(&layers), inserted);
+    }
+
+    #[test]
+    fn undo_and_redo_insert_into_new_layer() {
+        let mut layers = scene();
+        let mut history = History::default();
+        let original = contents(&layers);
+
+        history.apply(&mut layers, insert(3, 0, line(10.), 10));
+        let inserted = contents(&layers);
+        assert_eq!(inserted.len(), 4);
+        assert_eq!(inserted[3].len(), 1);
+
+        // The new layer stays, empty.
+        assert_eq!(history.undo(&mut layers), Some(vec![3]));
+        let undone = contents(&layers);
+        assert_eq!(undone[..3], original[..]);
+        assert!(undone[3].is_empty());
+
+        assert_eq!(history.redo(&mut layers), Some(vec![3]));
+        assert_eq!(contents(&layers), inserted);
+    }
+
+    #[test]
+    fn undo_group_of_deletes() {
+        let mut layers = scene();
+        let mut history = History::default();
+        let original = contents(&layers);
+
+        // From the end, like deleting a selection.
+        let deletes = [4, 3, 1]
+            .iter()
+            .map(|&index| Command::Delete {
+                layer: 1,
+                index,
+                shape: layers[1].as_shapes().unwrap().shape(index),
+                id: layers[1].instance_id(index),
+            })
+            .collect();
+        history.apply(&mut layers, Command::Group(deletes));
+        let ids: Vec<u64> = contents(&layers)[1].iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, vec![100, 102]);
+
+        assert_eq!(history.undo(&mut layers), Some(vec![1]));
+        assert_eq!(contents(&layers), original);
+    }
+
+    #[test]
+    fn merged_drag_is_one_step() {
+        let mut layers = scene();
+        let mut history = History::default();
+        let original = contents(&layers);
+
+        history.begin_merge();
+        for &offset in &[[0.5, 0.25], [1., -0.5], [0.25, 2.]] {
+            history.apply(
+                &mut layers,
+                Command::Group(vec![move_by(0, 2, offset), move_by(1, 0, offset)]),
+            );
+        }
+        history.end_merge();
+        let moved = layers[1].as_shapes().unwrap().shape(0).handles();
+        assert_eq!(moved[0], [1.75, 1.75]);
+
+        assert_eq!(history.undo(&mut layers), Some(vec![0, 1]));
+        assert_eq!(contents(&layers), original);
+        assert_eq!(history.undo(&mut layers), None);
+    }
+
+    #[test]
+    fn merged_create_drag_inserts_the_final_shape() {
+        let mut layers = scene();
+        let mut history = History::default();
+        let original = contents(&layers);
+
+        // Like the create tools, which insert a shape on press and change it while dragging.
+        let dragged = Shape::new(ShapeKind::Circle, [10., 0.], [13., 0.]);
+        history.begin_merge();
+        history.apply(&mut layers, insert(0, 3, circle(10.), 10));
+        history.apply(
+            &mut layers,
+            Command::Change {
+                layer: 0,
+                index: 3,
+                before: circle(10.),
+                after: dragged,
+            },
+        );
+        history.end_merge();
+        let created = contents(&layers);
+        assert_eq!(created[0][3], (10, format!("{:?}", dragged)));
+
+        assert_eq!(history.undo(&mut layers), Some(vec![0]));
+        assert_eq!(contents(&layers), original);
+        assert_eq!(history.undo(&mut layers), None);
+        assert_eq!(history.redo(&mut layers), Some(vec![0]));
+        assert_eq!(contents(&layers), created);
+    }
+
+    #[test]
+    fn apply_clears_redo() {
+        let mut layers = scene();
+        let mut history = History::default();
+
+        history.apply(&mut layers, move_by(2, 0, [1., 1.]));
+        history.undo(&mut layers);
+        history.apply(&mut layers, move_by(2, 0, [2., 2.]));
+        assert_eq!(history.redo(&mut layers), None);
+    }
+}
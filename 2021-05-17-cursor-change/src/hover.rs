@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use winit::dpi::PhysicalPosition;
+
+use crate::layer::{Layer, HIGHLIGHT_COLOR};
+use crate::overlay::Overlay;
+use crate::text::TextRenderer;
+
+// From the cursor to the upper left corner of the tooltip, in pixels.
+const TOOLTIP_OFFSET: [f32; 2] = [16., 16.];
+const TOOLTIP_SIZE: f32 = 16.;
+const TOOLTIP_COLOR: [f32; 4] = [0.05, 0.05, 0.05, 1.];
+const TOOLTIP_HALO_COLOR: [f32; 4] = [1., 1., 1., 1.];
+
+/// The topmost picked instance at scene position `position`, as the index of its layer and
+/// its index within the layer.
+pub fn pick(
+    layers: &[Box<dyn Layer>],
+    position: [f64; 2],
+    pixel_size: f32,
+) -> Option<(usize, usize)> {
+    layers
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(layer, l)| l.pick(position, pixel_size).map(|index| (layer, index)))
+}
+
+/// The instance under the cursor, drawn with its layer's highlight on top of the scene and
+/// described by a tooltip next to the cursor.
+pub struct Hover {
+    layer: usize,
+    index: usize,
+    id: u64,
+    pub highlight: Option<Overlay>,
+}
+
+impl Hover {
+    pub fn new(layers: &[Box<dyn Layer>], layer: usize, index: usize) -> Self {
+        Hover {
+            layer,
+            index,
+            id: layers[layer].instance_id(index),
+            highlight: layers[layer]
+                .highlight(&[index], HIGHLIGHT_COLOR)
+                .map(Overlay::new),
+        }
+    }
+
+    /// Whether this is the hover of instance `index` of layer `layer`.
+    pub fn is(&self, layer: usize, index: usize) -> bool {
+        self.layer == layer && self.index == index
+    }
+
+    /// Queues a tooltip next to `cursor` with the instance's entry in `metadata`, or its
+    /// ID if it has none.
+    pub fn queue_tooltip(
+        &self,
+        text: &mut TextRenderer,
+        metadata: &HashMap<u64, String>,
+        cursor: PhysicalPosition<f64>,
+    ) {
+        let label = match metadata.get(&self.id) {
+            Some(label) => label.clone(),
+            None => format!("#{}", self.id),
+        };
+        text.queue_with_halo(
+            (
+                cursor.x as f32 + TOOLTIP_OFFSET[0],
+                cursor.y as f32 + TOOLTIP_OFFSET[1],
+            ),
+            &label,
+            TOOLTIP_COLOR,
+            TOOLTIP_HALO_COLOR,
+            TOOLTIP_SIZE,
+        );
+    }
+}
@@ -0,0 +1,2 @@
+// Flag shared by every layer with sizes. Must match `layer::SIZE_IN_PIXELS`.
+const uint SIZE_IN_PIXELS = 1;
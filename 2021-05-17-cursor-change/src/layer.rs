@@ -1,14 +1,278 @@
-use wgpu::{BindGroup, BindGroupLayout, Device, RenderPass, SwapChainDescriptor};
+use std::fmt::{self, Write};
+
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, Device, Queue, RenderPass, SwapChainDescriptor,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::edit::ShapeLayer;
+use crate::line::{Line, LinesLayer};
+use crate::selection::Region;
+use crate::svg::SvgIds;
+use crate::zoom::ZoomState;
+
+/// Flag shared by every layer with sizes: the instance's sizes are in screen pixels instead
+/// of scene units, so it keeps the same size on screen when zooming. Positions are always in
+/// scene units. Flags specific to one kind of shape use the bits above this one. Shaders get
+/// it from `layer.glsl`.
+pub const SIZE_IN_PIXELS: u32 = 1;
+
+/// Color of the outline drawn around hovered instances.
+pub const HIGHLIGHT_COLOR: [f32; 4] = [1., 0.45, 0., 1.];
+/// Color of the outline drawn around selected instances.
+pub const SELECTION_COLOR: [f32; 4] = [0., 0.45, 1., 1.];
+/// Width of highlight outlines, in pixels.
+pub const HIGHLIGHT_WIDTH_IN_PIXELS: f32 = 2.;
+/// How far from a thin line the cursor may be to still pick it, in pixels.
+pub const PICK_TOLERANCE_IN_PIXELS: f32 = 3.;
+
+/// Converts a scene position to the single precision offset from `origin` that is uploaded
+/// to the GPU.
+pub fn relative_to(position: [f64; 2], origin: [f64; 2]) -> [f32; 2] {
+    [
+        (position[0] - origin[0]) as f32,
+        (position[1] - origin[1]) as f32,
+    ]
+}
+
+/// Lines in `color` along the closed polygon through `corners`, which keep their width on
+/// screen when zooming.
+pub fn outline(corners: &[[f64; 2]], color: [f32; 4]) -> impl Iterator<Item = Line> + '_ {
+    corners
+        .iter()
+        .zip(corners.iter().cycle().skip(1))
+        .map(move |(&start, &end)| Line {
+            start,
+            end,
+            color,
+            width: HIGHLIGHT_WIDTH_IN_PIXELS,
+            dash_array: [0.; 4],
+            dash_offset: 0.,
+            flags: SIZE_IN_PIXELS,
+        })
+}
+
+/// The IDs attached to a layer's instances with its `with_ids`, reported by `instance_id` for
+/// picked and selected instances. Without IDs, instances are identified by their index.
+#[derive(Default)]
+pub struct InstanceIds(Option<Vec<u64>>);
+
+impl InstanceIds {
+    /// One ID for every one of a layer's `count` instances, in order.
+    pub fn new(ids: Vec<u64>, count: usize) -> Self {
+        assert_eq!(ids.len(), count, "One ID per instance");
+        InstanceIds(Some(ids))
+    }
+
+    pub fn get(&self, index: usize) -> u64 {
+        self.0.as_ref().map_or(index as u64, |ids| ids[index])
+    }
+
+    /// The IDs of a layer with `count` instances that is being edited. Layers without IDs
+    /// get their indices as IDs before the first edit, so that IDs don't change as instances
+    /// are inserted and removed.
+    pub fn for_edit(&mut self, count: usize) -> &mut Vec<u64> {
+        self.0.get_or_insert_with(|| (0..count as u64).collect())
+    }
+}
 
 pub trait Layer {
+    /// Uploads the layer with positions relative to `origin`, which is also the origin of the
+    /// shared transform. Called again with a new origin when the view moves far away.
     fn init_drawable(
         &self,
         device: &Device,
+        queue: &Queue,
         sc_desc: &SwapChainDescriptor,
         transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
     ) -> Box<dyn Drawable>;
+
+    /// Moves `drawable`, made by this layer's `init_drawable`, to a new upload origin without
+    /// recreating its pipeline. Returns false if the layer can't, in which case the drawable
+    /// has to be recreated with `init_drawable`.
+    fn rebase(&self, _queue: &Queue, _drawable: &dyn Drawable, _origin: [f64; 2]) -> bool {
+        false
+    }
+
+    /// Writes the instances at `indices`, in ascending order, to `drawable`, made by this
+    /// layer's `init_drawable`, after they were changed in place. Returns false if the layer
+    /// can't, in which case the drawable has to be recreated with `init_drawable`.
+    fn update_instances(
+        &self,
+        _queue: &Queue,
+        _drawable: &dyn Drawable,
+        _indices: &[usize],
+        _origin: [f64; 2],
+    ) -> bool {
+        false
+    }
+
+    /// Writes the layer's instances as SVG elements, in scene coordinates. `pixel_size` is
+    /// the size of a screen pixel in scene units, for anything sized in pixels. Definitions
+    /// such as gradients take their IDs from `ids`.
+    fn write_svg(&self, out: &mut dyn Write, pixel_size: f32, ids: &mut SvgIds) -> fmt::Result;
+
+    /// The index of the topmost instance under scene position `position`, for layers that
+    /// support picking. `pixel_size` is the size of a screen pixel in scene units.
+    fn pick(&self, _position: [f64; 2], _pixel_size: f32) -> Option<usize> {
+        None
+    }
+
+    /// The ID of the instance at `index`, from the layer's ID column if it has one.
+    fn instance_id(&self, index: usize) -> u64 {
+        index as u64
+    }
+
+    /// The indices of the instances inside `region`, for layers that support selection.
+    /// Circles and markers are inside when their center is, other shapes when all their
+    /// corners or end points are.
+    fn select(&self, _region: &Region) -> Vec<usize> {
+        Vec::new()
+    }
+
+    /// A layer drawn over the instances at `indices` in `color`, to show that they are
+    /// hovered or selected.
+    fn highlight(&self, _indices: &[usize], _color: [f32; 4]) -> Option<Box<dyn Layer>> {
+        None
+    }
+
+    /// The layer's instances as editable shapes, for layers that support editing.
+    fn as_shapes(&self) -> Option<&dyn ShapeLayer> {
+        None
+    }
+
+    fn as_shapes_mut(&mut self) -> Option<&mut dyn ShapeLayer> {
+        None
+    }
 }
 
 pub trait Drawable {
+    /// Called before every frame, outside of the render pass, for drawables that depend on
+    /// the view beyond the shared transform.
+    fn update(&self, _queue: &Queue, _zoom_state: &ZoomState) {}
+
+    /// Called after `update`, for drawables that render into their own textures before the
+    /// main render pass.
+    fn prepare(&self, _encoder: &mut CommandEncoder, _transform_bind_group: &BindGroup) {}
+
+    /// Called when the window is resized, for drawables with screen-sized textures.
+    fn resize(&mut self, _device: &Device, _size: PhysicalSize<u32>) {}
+
+    /// Overwrites the instances from index `first` on with `data`, which holds instances in
+    /// the drawable's own vertex layout. Used by layers to update drawables in place.
+    fn write_instances(&self, _queue: &Queue, _first: usize, _data: &[u8]) {}
+
+    /// Moves anything besides the instances that depends on the upload origin, such as fill
+    /// uniforms, to `origin`.
+    fn rebase(&self, _queue: &Queue, _origin: [f64; 2]) {}
+
+    /// The drawables this one is made of, for the drawables of layer groups, so that their
+    /// layers can update them in place.
+    fn parts(&self) -> &[Box<dyn Drawable>] {
+        &[]
+    }
+
     fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup);
 }
+
+/// Writes the instances at ascending `indices` to `drawable`, once per run of consecutive
+/// indices. `instance` computes the instance at an index, in the drawable's vertex layout.
+pub fn write_instances_at<T: bytemuck::Pod>(
+    queue: &Queue,
+    drawable: &dyn Drawable,
+    indices: &[usize],
+    instance: impl Fn(usize) -> T,
+) {
+    let mut start = 0;
+    while start < indices.len() {
+        let mut end = start + 1;
+        while end < indices.len() && indices[end] == indices[end - 1] + 1 {
+            end += 1;
+        }
+        let instances: Vec<T> = indices[start..end].iter().map(|&i| instance(i)).collect();
+        drawable.write_instances(queue, indices[start], bytemuck::cast_slice(&instances));
+        start = end;
+    }
+}
+
+/// Several layers drawn one after the other, as a single layer.
+pub struct LayerGroup {
+    layers: Vec<Box<dyn Layer>>,
+}
+
+impl LayerGroup {
+    pub fn new(layers: Vec<Box<dyn Layer>>) -> Self {
+        LayerGroup { layers }
+    }
+}
+
+pub struct LayerGroupDrawable {
+    drawables: Vec<Box<dyn Drawable>>,
+}
+
+impl Drawable for LayerGroupDrawable {
+    fn update(&self, queue: &Queue, zoom_state: &ZoomState) {
+        for drawable in &self.drawables {
+            drawable.update(queue, zoom_state);
+        }
+    }
+
+    fn prepare(&self, encoder: &mut CommandEncoder, transform_bind_group: &BindGroup) {
+        for drawable in &self.drawables {
+            drawable.prepare(encoder, transform_bind_group);
+        }
+    }
+
+    fn resize(&mut self, device: &Device, size: PhysicalSize<u32>) {
+        for drawable in &mut self.drawables {
+            drawable.resize(device, size);
+        }
+    }
+
+    fn parts(&self) -> &[Box<dyn Drawable>] {
+        &self.drawables
+    }
+
+    fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        for drawable in &self.drawables {
+            drawable.draw(render_pass, bind_group);
+        }
+    }
+}
+
+impl Layer for LayerGroup {
+    fn init_drawable(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        sc_desc: &SwapChainDescriptor,
+        transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
+    ) -> Box<dyn Drawable> {
+        Box::new(LayerGroupDrawable {
+            drawables: self
+                .layers
+                .iter()
+                .map(|layer| layer.init_drawable(device, queue, sc_desc, transform_layout, origin))
+                .collect(),
+        })
+    }
+
+    fn rebase(&self, queue: &Queue, drawable: &dyn Drawable, origin: [f64; 2]) -> bool {
+        let parts = drawable.parts();
+        parts.len() == self.layers.len()
+            && self
+                .layers
+                .iter()
+                .zip(parts)
+                .all(|(layer, part)| layer.rebase(queue, part.as_ref(), origin))
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, pixel_size: f32, ids: &mut SvgIds) -> fmt::Result {
+        for layer in &self.layers {
+            layer.write_svg(out, pixel_size, ids)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+// Flags and arrowhead proportions shared by the line shaders. Must match `line/mod.rs`.
+#include "../layer.glsl"
+const uint DASH_IN_PIXELS = 2;
+const uint ARROW_START = 4;
+const uint ARROW_END = 8;
+
+// Arrowhead size relative to the line width.
+const float ARROW_WIDTH = 3.;
+const float ARROW_LENGTH = 6.;
@@ -1,26 +1,114 @@
-use crate::layer::{Drawable, Layer};
+use std::fmt::{self, Write};
+
+use crate::edit::{Shape, ShapeKind, ShapeLayer};
+use crate::layer::{
+    relative_to, write_instances_at, Drawable, InstanceIds, Layer, HIGHLIGHT_WIDTH_IN_PIXELS,
+    PICK_TOLERANCE_IN_PIXELS, SIZE_IN_PIXELS,
+};
+use crate::selection::Region;
+use crate::svg::{svg_paint, SvgIds};
 use wgpu::util::DeviceExt;
 use wgpu::{
-    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, RenderPass,
+    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, Queue, RenderPass,
     RenderPipeline, SwapChainDescriptor,
 };
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+/// `dash_array` and `dash_offset` are in screen pixels instead of scene units, so that
+/// dashes keep the same size on screen when zooming.
+pub const DASH_IN_PIXELS: u32 = 2;
+/// Draw an arrowhead pointing away from the line at `start`.
+pub const ARROW_START: u32 = 4;
+/// Draw an arrowhead pointing away from the line at `end`.
+pub const ARROW_END: u32 = 8;
+
+// Arrowhead size relative to the line width. These and the flags above must match
+// `line.glsl`, which both shaders include.
+const ARROW_WIDTH: f32 = 3.;
+const ARROW_LENGTH: f32 = 6.;
+
+#[derive(Copy, Clone, Debug)]
 pub struct Line {
-    pub start: [f32; 2],
-    pub end: [f32; 2],
+    pub start: [f64; 2],
+    pub end: [f64; 2],
     pub color: [f32; 4],
+    /// Half the line's thickness: the line extends `width` on either side of the segment
+    /// between `start` and `end`, so a width of 2 draws a line 4 units thick.
     pub width: f32,
+    /// Alternating dash and gap lengths. All zeros for a solid line.
+    pub dash_array: [f32; 4],
+    /// Distance into the dash pattern at which the line starts.
+    pub dash_offset: f32,
+    /// Combination of `SIZE_IN_PIXELS` (for `width`), `DASH_IN_PIXELS`, `ARROW_START` and
+    /// `ARROW_END`.
+    pub flags: u32,
+}
+
+/// A `Line` as uploaded, with the end points relative to the upload origin.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct LineInstance {
+    start: [f32; 2],
+    end: [f32; 2],
+    color: [f32; 4],
+    width: f32,
+    dash_array: [f32; 4],
+    dash_offset: f32,
+    flags: u32,
+}
+
+impl Line {
+    fn instance(&self, origin: [f64; 2]) -> LineInstance {
+        LineInstance {
+            start: relative_to(self.start, origin),
+            end: relative_to(self.end, origin),
+            color: self.color,
+            width: self.width,
+            dash_array: self.dash_array,
+            dash_offset: self.dash_offset,
+            flags: self.flags,
+        }
+    }
+
+    // Whether `position` is within the line, or within a few pixels of a thin line.
+    // Arrowheads are ignored.
+    fn contains(&self, position: [f64; 2], pixel_size: f32) -> bool {
+        let width = if self.flags & SIZE_IN_PIXELS != 0 {
+            self.width * pixel_size
+        } else {
+            self.width
+        };
+        let tolerance = width.max(PICK_TOLERANCE_IN_PIXELS * pixel_size) as f64;
+
+        let direction = [self.end[0] - self.start[0], self.end[1] - self.start[1]];
+        let offset = [position[0] - self.start[0], position[1] - self.start[1]];
+        let length_squared = direction[0] * direction[0] + direction[1] * direction[1];
+        let t = if length_squared > 0. {
+            ((offset[0] * direction[0] + offset[1] * direction[1]) / length_squared).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let [dx, dy] = [offset[0] - t * direction[0], offset[1] - t * direction[1]];
+        dx * dx + dy * dy <= tolerance * tolerance
+    }
 }
 
 pub struct LinesLayer {
     data: Vec<Line>,
+    ids: InstanceIds,
 }
 
 impl LinesLayer {
     pub fn new(data: Vec<Line>) -> Self {
-        LinesLayer { data }
+        LinesLayer {
+            data,
+            ids: InstanceIds::default(),
+        }
+    }
+
+    /// Attaches an ID to every instance, see `InstanceIds`.
+    pub fn with_ids(mut self, ids: Vec<u64>) -> Self {
+        self.ids = InstanceIds::new(ids, self.data.len());
+        self
     }
 }
 
@@ -32,22 +120,33 @@ pub struct LinesLayerDrawable {
 
 impl Drawable for LinesLayerDrawable {
     fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        // Layers can be emptied by editing.
+        if self.num_lines == 0 {
+            return;
+        }
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(0, bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
         render_pass.draw(0..6, 0..self.num_lines);
     }
+
+    fn write_instances(&self, queue: &Queue, first: usize, data: &[u8]) {
+        let offset = first * std::mem::size_of::<LineInstance>();
+        queue.write_buffer(&self.instance_buffer, offset as wgpu::BufferAddress, data);
+    }
 }
 
 impl Layer for LinesLayer {
     fn init_drawable(
         &self,
         device: &Device,
+        _queue: &Queue,
         sc_desc: &SwapChainDescriptor,
         transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
     ) -> Box<dyn Drawable> {
         let instance_buffer_desc = wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Line>() as wgpu::BufferAddress,
+            array_stride: std::mem::size_of::<LineInstance>() as wgpu::BufferAddress,
             step_mode: wgpu::InputStepMode::Instance,
             attributes: &[
                 wgpu::VertexAttribute {
@@ -70,13 +169,29 @@ impl Layer for LinesLayer {
                     shader_location: 3,
                     format: wgpu::VertexFormat::Float32,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 13]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Uint32,
+                },
             ],
         };
 
+        let instances: Vec<LineInstance> = self.data.iter().map(|d| d.instance(origin)).collect();
         let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Instance buffer"),
-            contents: bytemuck::cast_slice(&self.data),
-            usage: wgpu::BufferUsage::VERTEX,
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
         });
 
         let render_pipeline_layout =
@@ -134,4 +249,204 @@ impl Layer for LinesLayer {
             num_lines: self.data.len() as u32,
         })
     }
+
+    fn rebase(&self, queue: &Queue, drawable: &dyn Drawable, origin: [f64; 2]) -> bool {
+        let instances: Vec<LineInstance> = self.data.iter().map(|d| d.instance(origin)).collect();
+        drawable.write_instances(queue, 0, bytemuck::cast_slice(&instances));
+        true
+    }
+
+    fn update_instances(
+        &self,
+        queue: &Queue,
+        drawable: &dyn Drawable,
+        indices: &[usize],
+        origin: [f64; 2],
+    ) -> bool {
+        write_instances_at(queue, drawable, indices, |i| self.data[i].instance(origin));
+        true
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, pixel_size: f32, _ids: &mut SvgIds) -> fmt::Result {
+        for line in &self.data {
+            // Zero-length lines have no direction, so the shader draws nothing for them.
+            if line.start == line.end {
+                continue;
+            }
+
+            let (stroke, opacity) = svg_paint(line.color);
+            let width = if line.flags & SIZE_IN_PIXELS != 0 {
+                line.width * pixel_size
+            } else {
+                line.width
+            };
+            let arrow_length = (width * ARROW_LENGTH) as f64;
+            let direction = [line.end[0] - line.start[0], line.end[1] - line.start[1]];
+            let length = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+            let direction = [direction[0] / length, direction[1] / length];
+
+            // The stroke stops at the base of each arrowhead.
+            let mut start = line.start;
+            let mut end = line.end;
+            if line.flags & ARROW_START != 0 {
+                start[0] += direction[0] * arrow_length;
+                start[1] += direction[1] * arrow_length;
+            }
+            if line.flags & ARROW_END != 0 {
+                end[0] -= direction[0] * arrow_length;
+                end[1] -= direction[1] * arrow_length;
+            }
+
+            // The shader extends the line by `width` on either side.
+            write!(
+                out,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-opacity="{}" stroke-width="{}""#,
+                start[0],
+                start[1],
+                end[0],
+                end[1],
+                stroke,
+                opacity,
+                2. * width
+            )?;
+
+            // Without gaps the shader draws a solid line, whatever the dash lengths.
+            let [dash, gap, second_dash, second_gap] = line.dash_array;
+            if gap > 0. || second_gap > 0. {
+                // SVG has no pixel units once the drawing is scaled, so pixel dashes are
+                // converted at the current zoom level.
+                let unit = if line.flags & DASH_IN_PIXELS != 0 {
+                    pixel_size
+                } else {
+                    1.
+                };
+                // Without a second dash, both gaps form a single gap.
+                let pattern = if second_dash > 0. {
+                    vec![dash, gap, second_dash, second_gap]
+                } else {
+                    vec![dash, gap + second_gap]
+                };
+                let dashes: Vec<String> = pattern.iter().map(|d| (d * unit).to_string()).collect();
+                write!(
+                    out,
+                    r#" stroke-dasharray="{}" stroke-dashoffset="{}""#,
+                    dashes.join(" "),
+                    line.dash_offset * unit
+                )?;
+            }
+            writeln!(out, "/>")?;
+
+            let perp = [direction[1], -direction[0]];
+            let half_width = (width * ARROW_WIDTH) as f64;
+            for &(flag, tip, sign) in &[(ARROW_START, line.start, -1.), (ARROW_END, line.end, 1.)] {
+                if line.flags & flag == 0 {
+                    continue;
+                }
+                let base = [
+                    tip[0] - sign * direction[0] * arrow_length,
+                    tip[1] - sign * direction[1] * arrow_length,
+                ];
+                writeln!(
+                    out,
+                    r#"<polygon points="{},{} {},{} {},{}" fill="{}" fill-opacity="{}"/>"#,
+                    tip[0],
+                    tip[1],
+                    base[0] + perp[0] * half_width,
+                    base[1] + perp[1] * half_width,
+                    base[0] - perp[0] * half_width,
+                    base[1] - perp[1] * half_width,
+                    stroke,
+                    opacity
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn pick(&self, position: [f64; 2], pixel_size: f32) -> Option<usize> {
+        self.data
+            .iter()
+            .rposition(|line| line.contains(position, pixel_size))
+    }
+
+    fn instance_id(&self, index: usize) -> u64 {
+        self.ids.get(index)
+    }
+
+    fn as_shapes(&self) -> Option<&dyn ShapeLayer> {
+        Some(self)
+    }
+
+    fn as_shapes_mut(&mut self) -> Option<&mut dyn ShapeLayer> {
+        Some(self)
+    }
+
+    fn select(&self, region: &Region) -> Vec<usize> {
+        (0..self.data.len())
+            .filter(|&i| region.contains_all(&[self.data[i].start, self.data[i].end]))
+            .collect()
+    }
+
+    fn highlight(&self, indices: &[usize], color: [f32; 4]) -> Option<Box<dyn Layer>> {
+        // The same lines in a translucent `color`, wider when sized in pixels, so the lines
+        // still show through.
+        let color = [color[0] * 0.6, color[1] * 0.6, color[2] * 0.6, 0.6];
+        let lines = indices
+            .iter()
+            .map(|&i| {
+                let line = &self.data[i];
+                let width = if line.flags & SIZE_IN_PIXELS != 0 {
+                    line.width + HIGHLIGHT_WIDTH_IN_PIXELS
+                } else {
+                    line.width
+                };
+                Line {
+                    color,
+                    width,
+                    ..*line
+                }
+            })
+            .collect();
+        Some(Box::new(LinesLayer::new(lines)))
+    }
+}
+
+impl ShapeLayer for LinesLayer {
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::Line
+    }
+
+    fn shape_count(&self) -> usize {
+        self.data.len()
+    }
+
+    fn shape(&self, index: usize) -> Shape {
+        Shape::Line(self.data[index])
+    }
+
+    fn set_shape(&mut self, index: usize, shape: Shape) -> bool {
+        match shape.into_line() {
+            Some(line) => {
+                self.data[index] = line;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert_shape(&mut self, index: usize, shape: Shape, id: u64) -> bool {
+        match shape.into_line() {
+            Some(line) => {
+                self.ids.for_edit(self.data.len()).insert(index, id);
+                self.data.insert(index, line);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_shape(&mut self, index: usize) -> (Shape, u64) {
+        let id = self.ids.for_edit(self.data.len()).remove(index);
+        (Shape::Line(self.data.remove(index)), id)
+    }
 }
@@ -1,17 +1,82 @@
 #version 450
 
 layout(location=0) in vec4 v_color;
-layout(location=1) in vec2 v_edge;
+// Position along the line from the start, and across it from the center, in scene units.
+layout(location=1) in vec2 v_local;
+layout(location=2) flat in float v_length;
+layout(location=3) flat in float v_width;
+layout(location=4) flat in vec4 v_dash_array;
+layout(location=5) flat in float v_dash_offset;
+layout(location=6) flat in uint v_flags;
 
 layout(location=0) out vec4 f_color;
 
+#include "line.glsl"
+
+// Signed distance from x to the interval [start, end], negative inside.
+float interval_distance(float x, float start, float end) {
+    return max(start - x, x - end);
+}
+
+// Signed distance, in pattern units, from x to the nearest dash.
+float dash_distance(float x, vec4 dashes) {
+    float period = dashes.x + dashes.y + dashes.z + dashes.w;
+    float m = mod(x, period);
+
+    // Check the neighbouring periods too, so that distances are right near the wrap.
+    float d = interval_distance(m, 0., dashes.x);
+    d = min(d, interval_distance(m, period, period + dashes.x));
+    if (dashes.z > 0.) {
+        float start = dashes.x + dashes.y;
+        d = min(d, interval_distance(m, start, start + dashes.z));
+        d = min(d, interval_distance(m, start - period, start + dashes.z - period));
+    }
+    return d;
+}
+
+// Signed distance to an arrowhead with its tip at `tip` along the line, pointing
+// in `direction` (+1 or -1).
+float arrow_distance(vec2 p, float tip, float direction) {
+    float half_width = v_width * ARROW_WIDTH;
+    float len = v_width * ARROW_LENGTH;
+    float along = (tip - p.x) * direction;
+    float base = along - len;
+    float side = (abs(p.y) * len - half_width * along) / length(vec2(len, half_width));
+    return max(max(base, side), -along);
+}
+
 void main() {
-    float dx = fwidth(v_edge.x);
-    float dy = fwidth(v_edge.y);
+    // Scene units per pixel, used for antialiasing and pixel-sized dashes.
+    float px = length(vec2(dFdx(v_local.x), dFdy(v_local.x)));
+
+    float body_start = 0.;
+    float body_end = v_length;
+    if ((v_flags & ARROW_START) != 0) {
+        body_start += v_width * ARROW_LENGTH;
+    }
+    if ((v_flags & ARROW_END) != 0) {
+        body_end -= v_width * ARROW_LENGTH;
+    }
+
+    float d = max(abs(v_local.y) - v_width, interval_distance(v_local.x, body_start, body_end));
+
+    if (dot(v_dash_array, vec4(1.)) > 0.) {
+        float unit = (v_flags & DASH_IN_PIXELS) != 0 ? px : 1.;
+        d = max(d, dash_distance(v_local.x / unit + v_dash_offset, v_dash_array) * unit);
+    }
+
+    if ((v_flags & ARROW_START) != 0) {
+        d = min(d, arrow_distance(v_local, 0., -1.));
+    }
+    if ((v_flags & ARROW_END) != 0) {
+        d = min(d, arrow_distance(v_local, v_length, 1.));
+    }
+
+    float alpha = clamp(0.5 - d / px, 0., 1.);
 
-    float xcov = min(clamp(0., 1., v_edge.x / dx), clamp(0., 1., (1. - v_edge.x) / dx));
-    float ycov = min(clamp(0., 1., v_edge.y / dy), clamp(0., 1., (1. - v_edge.y) / dy));
-    float alpha = xcov * ycov;
+    if (alpha < 0.01) {
+        discard;
+    }
 
-    f_color = vec4(f_color.rgb * alpha, alpha);
+    f_color = vec4(v_color.rgb * alpha, v_color.a * alpha);
 }
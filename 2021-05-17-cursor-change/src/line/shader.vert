@@ -4,45 +4,74 @@ layout(location=0) in vec2 a_start;
 layout(location=1) in vec2 a_end;
 layout(location=2) in vec4 a_color;
 layout(location=3) in float a_width;
+layout(location=4) in vec4 a_dash_array;
+layout(location=5) in float a_dash_offset;
+layout(location=6) in uint a_flags;
 
 layout(location=0) out vec4 v_color;
-layout(location=1) out vec2 v_edge;
+layout(location=1) out vec2 v_local;
+layout(location=2) flat out float v_length;
+layout(location=3) flat out float v_width;
+layout(location=4) flat out vec4 v_dash_array;
+layout(location=5) flat out float v_dash_offset;
+layout(location=6) flat out uint v_flags;
 
 layout(set=0, binding=0)
 uniform Uniforms {
     mat4 u_transform;
+    vec2 u_viewport_size;
 };
 
+#include "line.glsl"
+
 void main() {
     vec2 line = normalize(a_end - a_start);
     vec2 perp = vec2(line.y, -line.x);
+    float len = length(a_end - a_start);
+
+    float width = a_width;
+    if ((a_flags & SIZE_IN_PIXELS) != 0) {
+        // Scene units per pixel.
+        width *= 2. / (u_transform[0][0] * u_viewport_size.x);
+    }
+
+    // Arrowheads are wider than the line, so the quad has to cover them.
+    float extent = width;
+    if ((a_flags & (ARROW_START | ARROW_END)) != 0) {
+        extent *= ARROW_WIDTH;
+    }
 
-    vec2 c1 = a_start - perp * a_width;
-    vec2 c2 = a_start + perp * a_width;
-    vec2 c3 = a_end - perp * a_width;
-    vec2 c4 = a_end + perp * a_width;
+    vec2 c1 = a_start - perp * extent;
+    vec2 c2 = a_start + perp * extent;
+    vec2 c3 = a_end - perp * extent;
+    vec2 c4 = a_end + perp * extent;
 
     switch (gl_VertexIndex) {
         case 0:
         gl_Position = vec4(c1, 0., 1.);
-        v_edge = vec2(0., 0.);
+        v_local = vec2(0., -extent);
         break;
         case 1:
         case 3:
         gl_Position = vec4(c2, 0., 1.);
-        v_edge = vec2(0., 1.);
+        v_local = vec2(0., extent);
         break;
         case 2:
         case 4:
         gl_Position = vec4(c3, 0., 1.);
-        v_edge = vec2(1., 0.);
+        v_local = vec2(len, -extent);
         break;
         case 5:
         gl_Position = vec4(c4, 0., 1.);
-        v_edge = vec2(0., 0.);
+        v_local = vec2(len, extent);
     }
 
     gl_Position = u_transform * gl_Position;
 
     v_color = a_color;
+    v_length = len;
+    v_width = width;
+    v_dash_array = a_dash_array;
+    v_dash_offset = a_dash_offset;
+    v_flags = a_flags;
 }
@@ -0,0 +1,7 @@
+#version 450
+
+layout(location=0) out float f_count;
+
+void main() {
+    f_count = 1.;
+}
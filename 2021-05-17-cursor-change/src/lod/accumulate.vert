@@ -0,0 +1,16 @@
+#version 450
+
+layout(location=0) in vec2 a_position;
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_transform;
+    vec2 u_viewport_size;
+};
+
+void main() {
+    // The density texture covers the same view as the screen, so the point lands in the
+    // texel of its bin.
+    gl_Position = u_transform * vec4(a_position, 0., 1.);
+    gl_PointSize = 1.;
+}
@@ -0,0 +1,27 @@
+#version 450
+
+#include "../colormap.glsl"
+
+layout(location=0) in vec2 v_tex_coord;
+
+layout(location=0) out vec4 f_color;
+
+layout(set=0, binding=0) uniform texture2D t_density;
+layout(set=0, binding=1) uniform sampler s_density;
+layout(set=0, binding=2)
+uniform ColormapUniforms {
+    float u_expected_count;
+};
+
+void main() {
+    float count = texture(sampler2D(t_density, s_density), v_tex_coord).r;
+
+    // Empty bins let the layers below show through.
+    if (count < 0.5) {
+        discard;
+    }
+
+    // Reaches about two thirds of the colormap at the count of evenly spread instances.
+    float t = 1. - exp(-count / u_expected_count);
+    f_color = vec4(colormap(COLORMAP_VIRIDIS, t), 1.);
+}
@@ -0,0 +1,448 @@
+use std::cell::Cell;
+use std::fmt::{self, Write};
+
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, BlendComponent, BlendFactor, BlendOperation, BlendState, Buffer,
+    CommandEncoder, Device, Queue, RenderPass, RenderPipeline, Sampler, SwapChainDescriptor,
+    TextureFormat, TextureView,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::circle::{Circle, CirclesLayer};
+use crate::layer::{relative_to, Drawable, Layer};
+use crate::point::{Marker, Point, PointsLayer};
+use crate::selection::Region;
+use crate::svg::SvgIds;
+use crate::zoom::ZoomState;
+
+// Instances are aggregated once they are on average closer together than this.
+const LOD_SPACING_IN_PIXELS: f32 = 2.;
+// Side of the square screen bins that instances are counted in.
+const BIN_SIZE_IN_PIXELS: u32 = 4;
+// Half precision floats can be blended on every adapter, unlike full precision ones. They
+// only count exactly up to 2048: past that, adding one rounds back down and bins stop
+// growing. Scaling contributions down would not help, as the limit is on the ratio of the
+// count to each contribution.
+const DENSITY_FORMAT: TextureFormat = TextureFormat::R16Float;
+const MAX_DENSITY_COUNT: f64 = 2048.;
+
+/// Wraps a dense layer, and draws it as a binned density map instead of individual instances
+/// while zoomed out far enough for them to blur together. The counting happens on the GPU,
+/// by drawing every instance as a single additive pixel into a texture with one texel per bin.
+pub struct LodLayer {
+    detail: Box<dyn Layer>,
+    positions: Vec<[f64; 2]>,
+    threshold: f32,
+}
+
+// Area of the bounding box of the positions.
+fn area(positions: &[[f64; 2]]) -> f64 {
+    let mut min = [f64::INFINITY; 2];
+    let mut max = [f64::NEG_INFINITY; 2];
+    for &[x, y] in positions {
+        min = [min[0].min(x), min[1].min(y)];
+        max = [max[0].max(x), max[1].max(y)];
+    }
+    if positions.is_empty() {
+        0.
+    } else {
+        (max[0] - min[0]) * (max[1] - min[1])
+    }
+}
+
+impl LodLayer {
+    /// `positions` are the positions of the instances of `detail`, which are counted when
+    /// zoomed out.
+    pub fn new(detail: Box<dyn Layer>, positions: Vec<[f64; 2]>) -> Self {
+        // Assuming the instances are spread evenly, they are `spacing` apart, and blur
+        // together once a pixel is larger than the threshold.
+        let area = area(&positions);
+        let threshold = if area > 0. {
+            let spacing = (area / positions.len() as f64).sqrt() as f32;
+            spacing / LOD_SPACING_IN_PIXELS
+        } else {
+            f32::INFINITY
+        };
+
+        LodLayer {
+            detail,
+            positions,
+            threshold,
+        }
+    }
+
+    pub fn circles(data: Vec<Circle>) -> Self {
+        let positions = data.iter().map(|circle| circle.position).collect();
+        LodLayer::new(Box::new(CirclesLayer::new(data)), positions)
+    }
+
+    pub fn points(data: Vec<Point>, marker: Marker) -> Self {
+        let positions = data.iter().map(|point| point.position).collect();
+        LodLayer::new(
+            Box::new(PointsLayer::new(data).with_marker(marker)),
+            positions,
+        )
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct ColormapUniforms {
+    expected_count: f32,
+    _padding: [f32; 3],
+}
+
+struct DensityTexture {
+    view: TextureView,
+    bind_group: BindGroup,
+}
+
+impl DensityTexture {
+    fn new(
+        device: &Device,
+        layout: &BindGroupLayout,
+        sampler: &Sampler,
+        uniform_buffer: &Buffer,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Density texture"),
+            size: wgpu::Extent3d {
+                width: (size.width + BIN_SIZE_IN_PIXELS - 1) / BIN_SIZE_IN_PIXELS,
+                height: (size.height + BIN_SIZE_IN_PIXELS - 1) / BIN_SIZE_IN_PIXELS,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DENSITY_FORMAT,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Density bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        DensityTexture { view, bind_group }
+    }
+}
+
+pub struct LodLayerDrawable {
+    detail: Box<dyn Drawable>,
+    threshold: f32,
+    area: f64,
+    aggregated: Cell<bool>,
+
+    position_buffer: Buffer,
+    num_positions: u32,
+    accumulate_pipeline: RenderPipeline,
+
+    colormap_pipeline: RenderPipeline,
+    colormap_layout: BindGroupLayout,
+    uniform_buffer: Buffer,
+    sampler: Sampler,
+    density: DensityTexture,
+}
+
+impl Drawable for LodLayerDrawable {
+    fn update(&self, queue: &Queue, zoom_state: &ZoomState) {
+        let aggregated = zoom_state.pixel_size() > self.threshold;
+        self.aggregated.set(aggregated);
+
+        if aggregated {
+            // The colormap saturates relative to the count a bin would have if the instances
+            // were spread evenly. It is capped so that bins that stop counting at
+            // `MAX_DENSITY_COUNT` are already at the top of the colormap.
+            let bin_size = (BIN_SIZE_IN_PIXELS as f32 * zoom_state.pixel_size()) as f64;
+            let expected_count = (self.num_positions as f64 * bin_size * bin_size / self.area)
+                .min(MAX_DENSITY_COUNT / 4.);
+            queue.write_buffer(
+                &self.uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[ColormapUniforms {
+                    expected_count: expected_count.max(1.) as f32,
+                    _padding: [0.; 3],
+                }]),
+            );
+        } else {
+            self.detail.update(queue, zoom_state);
+        }
+    }
+
+    fn prepare(&self, encoder: &mut CommandEncoder, transform_bind_group: &BindGroup) {
+        if !self.aggregated.get() {
+            self.detail.prepare(encoder, transform_bind_group);
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Density pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &self.density.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.accumulate_pipeline);
+        render_pass.set_bind_group(0, transform_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.position_buffer.slice(..));
+        render_pass.draw(0..self.num_positions, 0..1);
+    }
+
+    fn resize(&mut self, device: &Device, size: PhysicalSize<u32>) {
+        self.density = DensityTexture::new(
+            device,
+            &self.colormap_layout,
+            &self.sampler,
+            &self.uniform_buffer,
+            size,
+        );
+        self.detail.resize(device, size);
+    }
+
+    fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        if self.aggregated.get() {
+            render_pass.set_pipeline(&self.colormap_pipeline);
+            render_pass.set_bind_group(0, &self.density.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        } else {
+            self.detail.draw(render_pass, bind_group);
+        }
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    bind_group_layouts: &[&BindGroupLayout],
+    vs_module: &wgpu::ShaderModule,
+    fs_module: &wgpu::ShaderModule,
+    buffers: &[wgpu::VertexBufferLayout],
+    topology: wgpu::PrimitiveTopology,
+    target: wgpu::ColorTargetState,
+) -> RenderPipeline {
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: vs_module,
+            entry_point: "main",
+            buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: fs_module,
+            entry_point: "main",
+            targets: &[target],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            clamp_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
+
+impl Layer for LodLayer {
+    fn init_drawable(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        sc_desc: &SwapChainDescriptor,
+        transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
+    ) -> Box<dyn Drawable> {
+        let detail = self
+            .detail
+            .init_drawable(device, queue, sc_desc, transform_layout, origin);
+
+        let positions: Vec<[f32; 2]> = self
+            .positions
+            .iter()
+            .map(|&position| relative_to(position, origin))
+            .collect();
+        let position_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Position buffer"),
+            contents: bytemuck::cast_slice(&positions),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+
+        // Every instance is drawn as a single point adding one to the count of its bin.
+        let accumulate_pipeline = create_pipeline(
+            device,
+            &[transform_layout],
+            &device.create_shader_module(&wgpu::include_spirv!("accumulate.vert.spv")),
+            &device.create_shader_module(&wgpu::include_spirv!("accumulate.frag.spv")),
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &[wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                }],
+            }],
+            wgpu::PrimitiveTopology::PointList,
+            wgpu::ColorTargetState {
+                format: DENSITY_FORMAT,
+                write_mask: wgpu::ColorWrite::ALL,
+                blend: Some(BlendState {
+                    color: BlendComponent {
+                        src_factor: BlendFactor::One,
+                        dst_factor: BlendFactor::One,
+                        operation: BlendOperation::Add,
+                    },
+                    alpha: BlendComponent::REPLACE,
+                }),
+            },
+        );
+
+        let colormap_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Density bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: false,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        // The full-screen pass colors each bin by its count.
+        let colormap_pipeline = create_pipeline(
+            device,
+            &[&colormap_layout],
+            &device.create_shader_module(&wgpu::include_spirv!("../fullscreen.vert.spv")),
+            &device.create_shader_module(&wgpu::include_spirv!("colormap.frag.spv")),
+            &[],
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::ColorTargetState {
+                format: sc_desc.format,
+                write_mask: wgpu::ColorWrite::ALL,
+                blend: Some(BlendState {
+                    color: BlendComponent::OVER,
+                    alpha: BlendComponent::REPLACE,
+                }),
+            },
+        );
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Colormap uniform buffer"),
+            size: std::mem::size_of::<ColormapUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Bins are drawn as blocks rather than blurred together.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Density sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let density = DensityTexture::new(
+            device,
+            &colormap_layout,
+            &sampler,
+            &uniform_buffer,
+            PhysicalSize::new(sc_desc.width, sc_desc.height),
+        );
+
+        Box::new(LodLayerDrawable {
+            detail,
+            threshold: self.threshold,
+            area: area(&self.positions),
+            aggregated: Cell::new(false),
+            position_buffer,
+            num_positions: positions.len() as u32,
+            accumulate_pipeline,
+            colormap_pipeline,
+            colormap_layout,
+            uniform_buffer,
+            sampler,
+            density,
+        })
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, pixel_size: f32, ids: &mut SvgIds) -> fmt::Result {
+        // Exports keep every instance, whatever the zoom level.
+        self.detail.write_svg(out, pixel_size, ids)
+    }
+
+    fn pick(&self, position: [f64; 2], pixel_size: f32) -> Option<usize> {
+        self.detail.pick(position, pixel_size)
+    }
+
+    fn instance_id(&self, index: usize) -> u64 {
+        self.detail.instance_id(index)
+    }
+
+    fn select(&self, region: &Region) -> Vec<usize> {
+        self.detail.select(region)
+    }
+
+    fn highlight(&self, indices: &[usize], color: [f32; 4]) -> Option<Box<dyn Layer>> {
+        self.detail.highlight(indices, color)
+    }
+}
@@ -1,30 +1,316 @@
+use std::collections::{BTreeSet, HashMap};
 use std::iter;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::{
-    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
     BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferUsage, ShaderStage,
 };
 use winit::dpi::PhysicalSize;
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
+    window::{CursorIcon, Window, WindowBuilder},
 };
 
+use axes::Axes;
+use benchmark::Benchmark;
+use chart::{Chart, Series};
 use circle::{Circle, CirclesLayer};
-use layer::{Drawable, Layer};
+use colormap::Colormap;
+use edit::{EditDrag, Shape, ShapeKind};
+use fill::{Fill, GradientStop};
+use history::{Command, History};
+use hover::Hover;
+use layer::{Drawable, Layer, SIZE_IN_PIXELS};
+use overlay::Overlay;
+use post::{PostChain, PostEffect};
+use selection::{Selection, HANDLE_RADIUS_IN_PIXELS};
+use text::TextRenderer;
+use tool::{SelectionDrag, Tool};
 use zoom::ZoomState;
 
-use crate::line::{Line, LinesLayer};
+use crate::heatmap::{HeatPoint, HeatmapLayer, Normalization};
+use crate::line::{Line, LinesLayer, ARROW_END, DASH_IN_PIXELS};
 use crate::rectangle::{Rectangle, RectanglesLayer};
+use crate::sprite::{Atlas, ImagesLayer, Sprite};
+use crate::stream::{StreamLayer, StreamWriter};
 
+mod axes;
+mod benchmark;
+mod chart;
 mod circle;
+mod colormap;
+mod edit;
+mod fill;
+mod heatmap;
+mod history;
+mod hover;
 mod layer;
 mod line;
+mod lod;
+mod overlay;
+mod point;
+mod post;
 mod rectangle;
+mod selection;
+mod sprite;
+mod stream;
+mod svg;
+mod text;
+mod texture;
+mod tool;
+mod triangle;
 mod zoom;
 
+const USAGE: &str = "Usage: [file.svg | --chart | --stream | --bench <kind> <count>]
+Benchmark kinds: circles, points, squares, diamonds, cloud, lod, lod-points,
+heatmap, heatmap-magma, heatmap-inferno, heatmap-plasma";
+const SVG_EXPORT_PATH: &str = "scene.svg";
+// Cycled through with P.
+const POST_PRESETS: &[&[PostEffect]] = &[
+    &[],
+    &[PostEffect::Fxaa],
+    &[
+        PostEffect::Bloom {
+            threshold: 0.8,
+            intensity: 0.5,
+            sigma: 8.,
+        },
+        PostEffect::ToneMapping { exposure: 1. },
+        PostEffect::Fxaa,
+    ],
+    &[PostEffect::ColorGrading {
+        brightness: 1.,
+        contrast: 1.3,
+        saturation: 0.3,
+    }],
+    &[PostEffect::Blur { sigma: 4. }],
+];
+// The status line lists the selected IDs up to this many, and only counts larger selections.
+const STATUS_MAX_IDS: usize = 8;
+const STATUS_POSITION: (f32, f32) = (8., 8.);
+const STATUS_SIZE: f32 = 16.;
+const STATUS_COLOR: [f32; 4] = [0.05, 0.05, 0.05, 1.];
+const STATUS_HALO_COLOR: [f32; 4] = [1., 1., 1., 1.];
+// Drawn as sprites in the demo scene, if present in the working directory.
+const DEMO_ATLAS_PATH: &str = "atlas.png";
+
+fn demo_layers() -> Vec<Box<dyn Layer>> {
+    let mut layers: Vec<Box<dyn Layer>> = vec![
+        // A density glow under the shapes, with kernels that grow with the scene.
+        Box::new(
+            HeatmapLayer::new(
+                [[-220., -220.], [-350., -350.], [-300., -250.], [300., 300.]]
+                    .iter()
+                    .map(|&position| HeatPoint {
+                        position,
+                        weight: 1.,
+                    })
+                    .collect(),
+            )
+            .with_radius(200., 0)
+            .with_colormap(Colormap::Magma)
+            .with_normalization(Normalization::Fixed(1.5)),
+        ),
+        Box::new(
+            CirclesLayer::new(vec![
+                Circle {
+                    position: [-220., -220.],
+                    radius: 15.,
+                    color: [0.1, 1.0, 0.5, 1.],
+                    stroke_color: [0., 0., 0., 0.],
+                    stroke_width: 0.,
+                    flags: 0,
+                },
+                Circle {
+                    position: [300., 300.],
+                    radius: 50.,
+                    color: [0., 0., 0., 0.],
+                    stroke_color: [0.6, 0.6, 0., 1.],
+                    stroke_width: 10.,
+                    flags: 0,
+                },
+                Circle {
+                    position: [-350., -350.],
+                    radius: 70.,
+                    color: [0.7, 0., 0.4, 1.],
+                    stroke_color: [0., 0., 0., 1.],
+                    stroke_width: 4.,
+                    flags: 0,
+                },
+            ])
+            .with_ids(vec![1, 2, 3])
+            .with_fill(Fill::linear_gradient(
+                [-420., -420.],
+                [350., 350.],
+                vec![
+                    GradientStop {
+                        offset: 0.,
+                        color: [0.7, 0., 0.4, 1.],
+                    },
+                    GradientStop {
+                        offset: 1.,
+                        color: [0.1, 0.4, 1., 1.],
+                    },
+                ],
+            )),
+        ),
+        // Stays the same size on screen regardless of zoom.
+        Box::new(
+            CirclesLayer::new(vec![Circle {
+                position: [500., -300.],
+                radius: 8.,
+                color: [0.3, 0.6, 0.9, 1.],
+                stroke_color: [0., 0., 0., 1.],
+                stroke_width: 1.,
+                flags: SIZE_IN_PIXELS,
+            }])
+            .with_ids(vec![4]),
+        ),
+        Box::new(
+            RectanglesLayer::new(vec![
+                Rectangle {
+                    upper_left: [-400., 400.],
+                    bottom_right: [-450., 500.],
+                    color: [0.3, 0.6, 0.4, 1.],
+                    stroke_color: [0., 0., 0., 0.],
+                    stroke_width: 0.,
+                    corner_radius: 10.,
+                    flags: 0,
+                },
+                Rectangle {
+                    upper_left: [10., 250.],
+                    bottom_right: [50., 300.],
+                    color: [0.7, 0., 0.4, 1.],
+                    stroke_color: [0., 0., 0., 1.],
+                    stroke_width: 1.,
+                    corner_radius: 0.,
+                    flags: SIZE_IN_PIXELS,
+                },
+            ])
+            .with_ids(vec![10, 11]),
+        ),
+        Box::new(
+            LinesLayer::new(vec![
+                Line {
+                    start: [450., -450.],
+                    end: [200., -100.],
+                    width: 2.,
+                    color: [0.0, 0.0, 0.0, 1.0],
+                    dash_array: [0.; 4],
+                    dash_offset: 0.,
+                    flags: ARROW_END | SIZE_IN_PIXELS,
+                },
+                Line {
+                    start: [-450., -450.],
+                    end: [200., -100.],
+                    width: 30.,
+                    color: [0.0, 0.0, 0.0, 1.0],
+                    dash_array: [20., 10., 4., 10.],
+                    dash_offset: 0.,
+                    flags: DASH_IN_PIXELS,
+                },
+            ])
+            .with_ids(vec![20, 21]),
+        ),
+    ];
+
+    if let Ok(atlas) = Atlas::load(DEMO_ATLAS_PATH) {
+        let source = atlas.full_source();
+        let sprite = |position, rotation, flags| Sprite {
+            position,
+            size: [source[2], source[3]],
+            rotation,
+            tint: [1., 1., 1., 1.],
+            source,
+            flags,
+        };
+        layers.push(Box::new(ImagesLayer::new(
+            atlas,
+            vec![
+                sprite([-100., 100.], 0., 0),
+                sprite([100., 100.], std::f32::consts::FRAC_PI_4, 0),
+                sprite([0., -200.], 0., SIZE_IN_PIXELS),
+            ],
+        )));
+    }
+
+    layers
+}
+
+/// Tooltips for the IDs of the demo scene's instances.
+fn demo_metadata() -> HashMap<u64, String> {
+    [
+        (1, "Small circle\n(-220, -220)"),
+        (2, "Ring\n(300, 300)"),
+        (3, "Large circle\n(-350, -350)"),
+        (4, "Marker\nsized in pixels"),
+        (10, "Rounded rectangle"),
+        (11, "Rectangle\nstroke in pixels"),
+        (20, "Arrow"),
+        (21, "Dashed line"),
+    ]
+    .iter()
+    .map(|&(id, label)| (id, label.to_string()))
+    .collect()
+}
+
+// Samples per second of the simulated telemetry in `--stream`, and how many are shown.
+const TELEMETRY_RATE: f64 = 1000.;
+const STREAM_WINDOW: usize = 10_000;
+
+/// Appends a made-up signal to the stream from a background thread, in real time.
+fn spawn_telemetry(writer: StreamWriter) {
+    std::thread::spawn(move || {
+        let start = Instant::now();
+        let mut sent = 0;
+        loop {
+            let due = (start.elapsed().as_secs_f64() * TELEMETRY_RATE) as u64;
+            writer.extend((sent..due).map(|i| {
+                let t = i as f64 / TELEMETRY_RATE;
+                let phase = 2. * std::f64::consts::PI * t;
+                [t, 2. * (0.5 * phase).sin() + 0.5 * (3.7 * phase).sin()]
+            }));
+            sent = due;
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    });
+}
+
+fn demo_chart() -> Chart {
+    let months: Vec<f64> = (1..=12).map(|month| month as f64).collect();
+    let sales = [12., 15., 9., 18., 22., 25., 21., 27., 30., 26., 32., 35.];
+    Chart::new(vec![
+        Series::area(
+            "Total",
+            months
+                .iter()
+                .zip(&sales)
+                .scan(0., |total, (&month, &value)| {
+                    *total += value / 4.;
+                    Some([month, *total])
+                })
+                .collect(),
+        ),
+        Series::bar(
+            "Sales",
+            months.iter().zip(&sales).map(|(&m, &v)| [m, v]).collect(),
+        ),
+        Series::line("Trend", months.iter().map(|&m| [m, 10. + 2. * m]).collect())
+            .with_color([0., 0., 0., 1.]),
+        Series::scatter(
+            "Returns",
+            months
+                .iter()
+                .map(|&m| [m, 5. + 4. * (m * 1.3).sin()])
+                .collect(),
+        ),
+    ])
+}
+
 struct State {
     surface: wgpu::Surface,
     device: wgpu::Device,
@@ -33,14 +319,35 @@ struct State {
     swap_chain: wgpu::SwapChain,
     size: winit::dpi::PhysicalSize<u32>,
     transform_buffer: Buffer,
+    transform_layout: BindGroupLayout,
     transform_bind_group: BindGroup,
 
+    layers: Vec<Box<dyn Layer>>,
     drawables: Vec<Box<dyn Drawable>>,
     zoom_state: ZoomState,
+    post: PostChain,
+    post_preset: usize,
+    axes: Axes,
+    show_axes: bool,
+    text: TextRenderer,
+    hover: Option<Hover>,
+    // Tooltips of hovered instances, by instance ID.
+    metadata: HashMap<u64, String>,
+    tool: Tool,
+    modifiers: ModifiersState,
+    selection_drag: Option<SelectionDrag>,
+    selection: Option<Selection>,
+    edit_drag: Option<EditDrag>,
+    history: History,
+    // Followed by the view while `follow_stream` is set.
+    stream: Option<StreamWriter>,
+    follow_stream: bool,
+    // Scene position that uploaded instance positions are relative to.
+    origin: [f64; 2],
 }
 
 impl State {
-    async fn new(window: &Window) -> Self {
+    async fn new(window: &Window, layers: Vec<Box<dyn Layer>>) -> Self {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
@@ -74,63 +381,13 @@ impl State {
         };
         let swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
-        let layers: Vec<Box<dyn Layer>> = vec![
-            Box::new(CirclesLayer::new(vec![
-                Circle {
-                    position: [-220., -220.],
-                    radius: 15.,
-                    color: [0.1, 1.0, 0.5, 1.],
-                },
-                Circle {
-                    position: [300., 300.],
-                    radius: 50.,
-                    color: [0.6, 0.6, 0., 1.],
-                },
-                Circle {
-                    position: [-350., -350.],
-                    radius: 70.,
-                    color: [0.7, 0., 0.4, 1.],
-                },
-            ])),
-            Box::new(CirclesLayer::new(vec![Circle {
-                position: [500., -300.],
-                radius: 40.,
-                color: [0.3, 0.6, 0.9, 1.],
-            }])),
-            Box::new(RectanglesLayer::new(vec![
-                Rectangle {
-                    upper_left: [-400., 400.],
-                    bottom_right: [-450., 500.],
-                    color: [0.3, 0.6, 0.4, 1.],
-                },
-                Rectangle {
-                    upper_left: [10., 250.],
-                    bottom_right: [50., 300.],
-                    color: [0.7, 0., 0.4, 1.],
-                },
-            ])),
-            Box::new(LinesLayer::new(vec![
-                Line {
-                    start: [450., -450.],
-                    end: [200., -100.],
-                    width: 3.,
-                    color: [0.0, 0.0, 0.0, 1.0],
-                },
-                Line {
-                    start: [-450., -450.],
-                    end: [200., -100.],
-                    width: 30.,
-                    color: [0.0, 0.0, 0.0, 1.0],
-                },
-            ])),
-        ];
-
         let zoom_state = ZoomState::new(size);
-        let transform = zoom_state.matrix();
+        let origin = zoom_state.center();
+        let uniforms = zoom_state.uniforms(origin);
 
         let transform_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Transformation buffer"),
-            contents: bytemuck::cast_slice(&[transform]),
+            contents: bytemuck::cast_slice(&[uniforms]),
             usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
         });
 
@@ -157,10 +414,15 @@ impl State {
             }],
         });
 
+        // Layers draw into the scene texture of the post-processing chain.
+        let scene_desc = post::scene_desc(&sc_desc);
         let drawables = layers
-            .into_iter()
-            .map(|d| d.init_drawable(&device, &sc_desc, &transform_layout))
+            .iter()
+            .map(|d| d.init_drawable(&device, &queue, &scene_desc, &transform_layout, origin))
             .collect();
+        let post = PostChain::new(&device, &sc_desc, POST_PRESETS[0]);
+        let axes = Axes::new(&device, &scene_desc, &transform_layout);
+        let text = TextRenderer::new(&device, sc_desc.format);
 
         Self {
             surface,
@@ -169,24 +431,518 @@ impl State {
             size,
             sc_desc,
             swap_chain,
+            layers,
             drawables,
             transform_buffer,
+            transform_layout,
             transform_bind_group,
             zoom_state,
+            post,
+            post_preset: 0,
+            axes,
+            show_axes: true,
+            text,
+            hover: None,
+            metadata: HashMap::new(),
+            tool: Tool::Pan,
+            modifiers: ModifiersState::empty(),
+            selection_drag: None,
+            selection: None,
+            edit_drag: None,
+            history: History::default(),
+            stream: None,
+            follow_stream: false,
+            origin,
+        }
+    }
+
+    /// Uploads all layers again relative to the view center, once the view has moved far
+    /// enough from the current origin for single precision positions to jitter.
+    fn rebase_if_needed(&mut self) {
+        if !self.zoom_state.needs_rebase(self.origin) {
+            return;
+        }
+
+        self.origin = self.zoom_state.center();
+        let scene_desc = post::scene_desc(&self.sc_desc);
+        for (layer, drawable) in self.layers.iter().zip(self.drawables.iter_mut()) {
+            if !layer.rebase(&self.queue, drawable.as_ref(), self.origin) {
+                *drawable = layer.init_drawable(
+                    &self.device,
+                    &self.queue,
+                    &scene_desc,
+                    &self.transform_layout,
+                    self.origin,
+                );
+            }
+        }
+        let overlays = self
+            .hover
+            .iter_mut()
+            .filter_map(|hover| hover.highlight.as_mut())
+            .chain(self.selection.iter_mut().map(|s| &mut s.overlay))
+            .chain(self.selection_drag.iter_mut().map(|drag| &mut drag.overlay));
+        for overlay in overlays {
+            overlay.init_drawable(
+                &self.device,
+                &self.queue,
+                &scene_desc,
+                &self.transform_layout,
+                self.origin,
+            );
         }
     }
 
+    /// Uploads an overlay relative to the current origin.
+    fn init_overlay(&self, overlay: &mut Overlay) {
+        overlay.init_drawable(
+            &self.device,
+            &self.queue,
+            &post::scene_desc(&self.sc_desc),
+            &self.transform_layout,
+            self.origin,
+        );
+    }
+
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        // Minimized windows report a zero size, which the swap chain and textures can't have.
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
         self.size = new_size;
         self.sc_desc.width = new_size.width;
         self.sc_desc.height = new_size.height;
         self.zoom_state.set_size(new_size);
+        for drawable in &mut self.drawables {
+            drawable.resize(&self.device, new_size);
+        }
+        self.post.resize(&self.device, new_size);
 
         self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
     }
 
+    fn export_svg(&self, path: &Path) {
+        match svg::export_svg(path, &self.layers, &self.zoom_state) {
+            Ok(()) => println!("Exported scene to {}", path.display()),
+            Err(e) => eprintln!("Could not export scene: {}", e),
+        }
+    }
+
+    fn next_post_preset(&mut self) {
+        self.post_preset = (self.post_preset + 1) % POST_PRESETS.len();
+        let effects = POST_PRESETS[self.post_preset];
+        self.post = PostChain::new(&self.device, &self.sc_desc, effects);
+    }
+
+    /// Labels the axes with the chart's data values, and zooms to show all of it.
+    fn show_chart(&mut self, chart: &Chart) {
+        let (x_scale, y_scale) = chart.axis_scales();
+        self.axes.x_scale = x_scale;
+        self.axes.y_scale = y_scale;
+        self.axes.legend = chart.legend();
+
+        let (min, max) = chart.scene_bounds();
+        self.zoom_state.fit(min, max);
+    }
+
+    /// Shows the last few seconds of the stream, and keeps its latest sample in view.
+    fn follow(&mut self, stream: StreamWriter) {
+        let window = STREAM_WINDOW as f64 / TELEMETRY_RATE;
+        self.zoom_state
+            .fit([0., -window / 4.], [window, window / 4.]);
+        self.stream = Some(stream);
+        self.follow_stream = true;
+    }
+
+    fn toggle_follow(&mut self) {
+        self.follow_stream = !self.follow_stream;
+    }
+
+    fn toggle_axes(&mut self) {
+        self.show_axes = !self.show_axes;
+    }
+
+    fn set_metadata(&mut self, metadata: HashMap<u64, String>) {
+        self.metadata = metadata;
+    }
+
+    /// Picks the instance under the cursor, and uploads its highlight if it changed.
+    fn update_hover(&mut self) {
+        let position = self.cursor_scene_position();
+        let picked = hover::pick(&self.layers, position, self.zoom_state.pixel_size());
+        match picked {
+            Some((layer, index)) if self.hover.as_ref().map_or(false, |h| h.is(layer, index)) => {}
+            Some((layer, index)) => {
+                let mut hover = Hover::new(&self.layers, layer, index);
+                if let Some(highlight) = &mut hover.highlight {
+                    self.init_overlay(highlight);
+                }
+                self.hover = Some(hover);
+            }
+            None => self.hover = None,
+        }
+    }
+
+    fn set_tool(&mut self, tool: Tool) {
+        self.tool = tool;
+    }
+
+    /// The selected instances as `(layer, id)`, like `Selection::ids`.
+    fn selected_ids(&self) -> BTreeSet<(usize, u64)> {
+        self.selection
+            .as_ref()
+            .map_or_else(BTreeSet::new, |selection| selection.ids(&self.layers))
+    }
+
+    /// The current tool and selection, shown in the upper left corner.
+    fn status(&self) -> String {
+        let mut status = format!("{:?}", self.tool);
+        match self.selection.as_ref().map_or(0, Selection::count) {
+            0 => {}
+            count if count <= STATUS_MAX_IDS => {
+                let ids: Vec<String> = self
+                    .selected_ids()
+                    .iter()
+                    .map(|(layer, id)| format!("{}:#{}", layer, id))
+                    .collect();
+                status += &format!(", selected {}", ids.join(" "));
+            }
+            count => status += &format!(", {} selected", count),
+        }
+        status
+    }
+
+    fn cursor_scene_position(&self) -> [f64; 2] {
+        self.zoom_state.to_scene(self.zoom_state.cursor_position())
+    }
+
+    fn set_selection(&mut self, mut selection: Selection) {
+        self.init_overlay(&mut selection.overlay);
+        self.selection = Some(selection);
+    }
+
+    /// Selects the instances inside the dragged region, replacing the previous selection.
+    fn finish_selection(&mut self, drag: SelectionDrag) {
+        let selection = Selection::in_region(&self.layers, drag.region());
+        self.set_selection(selection);
+    }
+
+    /// Uploads edited layers again, along with the selection and hover that depend on them.
+    /// Layers added at the end get new drawables.
+    fn layers_edited(&mut self, edited: &[usize]) {
+        let scene_desc = post::scene_desc(&self.sc_desc);
+        for &layer in edited {
+            let drawable = self.layers[layer].init_drawable(
+                &self.device,
+                &self.queue,
+                &scene_desc,
+                &self.transform_layout,
+                self.origin,
+            );
+            if layer < self.drawables.len() {
+                self.drawables[layer] = drawable;
+            } else {
+                self.drawables.push(drawable);
+            }
+        }
+
+        if let Some(selection) = self.selection.take() {
+            self.set_selection(Selection::new(&self.layers, selection.indices().to_vec()));
+        }
+        self.hover = None;
+        self.update_hover();
+    }
+
+    /// Writes instances changed in place, as `(layer, index)`, to the uploaded layers, along
+    /// with the selection and hover that depend on them. Falls back to `layers_edited` for
+    /// layers that can't be updated in place.
+    fn instances_edited(&mut self, instances: &[(usize, usize)]) {
+        let mut indices = vec![Vec::new(); self.layers.len()];
+        for &(layer, index) in instances {
+            indices[layer].push(index);
+        }
+        let mut recreate = Vec::new();
+        for (layer, mut indices) in indices.into_iter().enumerate() {
+            if indices.is_empty() {
+                continue;
+            }
+            indices.sort_unstable();
+            indices.dedup();
+            let drawable = self.drawables[layer].as_ref();
+            if !self.layers[layer].update_instances(&self.queue, drawable, &indices, self.origin) {
+                recreate.push(layer);
+            }
+        }
+        if !recreate.is_empty() {
+            self.layers_edited(&recreate);
+            return;
+        }
+
+        if let Some(mut selection) = self.selection.take() {
+            if !selection.update_overlay(&self.layers, &self.queue, self.origin) {
+                self.init_overlay(&mut selection.overlay);
+            }
+            self.selection = Some(selection);
+        }
+        // The hovered instance may have changed shape.
+        if self.hover.is_some() {
+            self.hover = None;
+            self.update_hover();
+        }
+    }
+
+    /// Applies and records an edit. Edits that only change instances, such as every step of
+    /// a drag, update the uploaded layers in place.
+    fn execute(&mut self, command: Command) {
+        let in_place = command.changed_in_place();
+        let edited = self.history.apply(&mut self.layers, command);
+        match in_place {
+            Some(instances) => self.instances_edited(&instances),
+            None => self.layers_edited(&edited),
+        }
+    }
+
+    fn undo(&mut self) {
+        self.cancel_edit();
+        if let Some(edited) = self.history.undo(&mut self.layers) {
+            self.layers_edited(&edited);
+        }
+    }
+
+    fn redo(&mut self) {
+        self.cancel_edit();
+        if let Some(edited) = self.history.redo(&mut self.layers) {
+            self.layers_edited(&edited);
+        }
+    }
+
+    // Stops dragging, and forgets the selection, whose indices may not survive undoing.
+    fn cancel_edit(&mut self) {
+        self.edit_drag = None;
+        self.history.end_merge();
+        self.selection = None;
+    }
+
+    /// One more than the largest ID in the editable layers.
+    fn next_id(&self) -> u64 {
+        self.layers
+            .iter()
+            .flat_map(|layer| {
+                let count = layer.as_shapes().map_or(0, |shapes| shapes.shape_count());
+                (0..count).map(move |i| layer.instance_id(i))
+            })
+            .max()
+            .map_or(0, |id| id + 1)
+    }
+
+    /// Adds `shape` on top of the topmost layer of its kind, or in a new layer on top of
+    /// the scene if there is none. Returns its layer and index.
+    fn insert_shape(&mut self, shape: Shape) -> (usize, usize) {
+        let id = self.next_id();
+        let existing = self.layers.iter().rposition(|layer| {
+            layer
+                .as_shapes()
+                .map_or(false, |shapes| shapes.kind() == shape.kind())
+        });
+        let (layer, index) = match existing {
+            Some(layer) => {
+                let shapes = self.layers[layer].as_shapes().expect("Layer of shapes");
+                (layer, shapes.shape_count())
+            }
+            None => (self.layers.len(), 0),
+        };
+        self.execute(Command::Insert {
+            layer,
+            index,
+            shape,
+            id,
+        });
+        (layer, index)
+    }
+
+    fn delete_selection(&mut self) {
+        // A drag would go on editing the deleted indices, and merge the deletion into its step.
+        if self.edit_drag.take().is_some() {
+            self.history.end_merge();
+        }
+        let selection = match self.selection.take() {
+            Some(selection) => selection,
+            None => return,
+        };
+        let mut deletions = Vec::new();
+        for (layer, indices) in selection.indices().iter().enumerate() {
+            if let Some(shapes) = self.layers[layer].as_shapes() {
+                // From the end, so that the other indices stay valid.
+                for &index in indices.iter().rev() {
+                    deletions.push(Command::Delete {
+                        layer,
+                        index,
+                        shape: shapes.shape(index),
+                        id: self.layers[layer].instance_id(index),
+                    });
+                }
+            }
+        }
+        if !deletions.is_empty() {
+            self.execute(Command::Group(deletions));
+        }
+    }
+
+    /// Starts a left drag with an editing tool. Returns false if the drag should pan the
+    /// view instead, for `Tool::Edit` outside of any shape.
+    fn start_edit(&mut self, tool: Tool) -> bool {
+        let position = self.cursor_scene_position();
+        let pixel_size = self.zoom_state.pixel_size();
+
+        // Everything until the button is released is a single step of the history.
+        self.history.begin_merge();
+        let drag = if let Tool::Create(kind) = tool {
+            let (layer, index) = self.insert_shape(Shape::new(kind, position, position));
+            self.set_selection(Selection::single(&self.layers, layer, index));
+            EditDrag::Create {
+                layer,
+                index,
+                kind,
+                start: position,
+            }
+        } else if let Some((layer, index, handle)) = self.pick_handle(position, pixel_size) {
+            EditDrag::Handle {
+                layer,
+                index,
+                handle,
+            }
+        } else {
+            let picked = hover::pick(&self.layers, position, pixel_size)
+                .filter(|&(layer, _)| self.layers[layer].as_shapes().is_some());
+            let (layer, index) = match picked {
+                Some(picked) => picked,
+                None => {
+                    self.history.end_merge();
+                    self.selection = None;
+                    return false;
+                }
+            };
+            // Dragging a selected shape moves the whole selection.
+            if !self
+                .selection
+                .as_ref()
+                .map_or(false, |selection| selection.contains(layer, index))
+            {
+                self.set_selection(Selection::single(&self.layers, layer, index));
+            }
+            let shapes = self
+                .selection
+                .as_ref()
+                .map_or(Vec::new(), |selection| selection.shapes(&self.layers));
+            EditDrag::Move {
+                shapes: shapes
+                    .into_iter()
+                    .map(|(layer, index, _)| (layer, index))
+                    .collect(),
+                last: position,
+            }
+        };
+        self.edit_drag = Some(drag);
+        true
+    }
+
+    /// The handle of a selected shape at scene position `position`, as `(layer, index,
+    /// handle)`.
+    fn pick_handle(&self, [x, y]: [f64; 2], pixel_size: f32) -> Option<(usize, usize, usize)> {
+        let radius = (HANDLE_RADIUS_IN_PIXELS * pixel_size) as f64;
+        self.selection
+            .as_ref()?
+            .handles(&self.layers)
+            .into_iter()
+            .find(|&(_, _, _, [hx, hy])| (hx - x).hypot(hy - y) <= radius)
+            .map(|(layer, index, handle, _)| (layer, index, handle))
+    }
+
     fn input(&mut self, event: &WindowEvent, window: &Window) -> bool {
-        self.zoom_state.handle_event(event, window)
+        // Selection tools take over left drags from `ZoomState`.
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                return true;
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                let tool = self.tool.with_modifiers(self.modifiers);
+                match tool {
+                    Tool::Pan => {}
+                    Tool::BoxSelect | Tool::Lasso => {
+                        let mut drag = SelectionDrag::new(tool, self.cursor_scene_position());
+                        self.init_overlay(&mut drag.overlay);
+                        self.selection_drag = Some(drag);
+                        window.set_cursor_icon(CursorIcon::Crosshair);
+                        return true;
+                    }
+                    Tool::Edit | Tool::Create(_) => {
+                        window.request_redraw();
+                        if self.start_edit(tool) {
+                            return true;
+                        }
+                    }
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } => {
+                if let Some(drag) = self.selection_drag.take() {
+                    self.finish_selection(drag);
+                    self.update_hover();
+                    window.set_cursor_icon(CursorIcon::Arrow);
+                    window.request_redraw();
+                    return true;
+                }
+                if self.edit_drag.take().is_some() {
+                    self.history.end_merge();
+                    self.update_hover();
+                    window.request_redraw();
+                    return true;
+                }
+            }
+            _ => {}
+        }
+
+        let hovered = self.hover.is_some();
+        let handled = self.zoom_state.handle_event(event, window);
+        // Either the cursor or the scene under it moved.
+        if let WindowEvent::CursorMoved { .. } | WindowEvent::MouseWheel { .. } = event {
+            if let Some(mut drag) = self.selection_drag.take() {
+                if !drag.extend(self.cursor_scene_position(), &self.queue, self.origin) {
+                    self.init_overlay(&mut drag.overlay);
+                }
+                self.selection_drag = Some(drag);
+                window.request_redraw();
+            }
+            if let Some(mut drag) = self.edit_drag.take() {
+                let command = drag.command_to(&self.layers, self.cursor_scene_position());
+                self.execute(command);
+                self.edit_drag = Some(drag);
+                window.request_redraw();
+            }
+            // Picking every instance on each move would slow drags down, so nothing is
+            // hovered until the drag ends.
+            if self.selection_drag.is_some()
+                || self.edit_drag.is_some()
+                || self.zoom_state.dragging()
+            {
+                self.hover = None;
+            } else {
+                self.update_hover();
+            }
+            // Tooltips follow the cursor.
+            if hovered || self.hover.is_some() {
+                window.request_redraw();
+            }
+        }
+        handled
     }
 
     fn render(&mut self) -> Result<(), wgpu::SwapChainError> {
@@ -198,11 +954,47 @@ impl State {
                 label: Some("Render Encoder"),
             });
 
+        if self.follow_stream {
+            if let Some([x, _]) = self.stream.as_ref().and_then(|stream| stream.latest()) {
+                self.zoom_state.follow(x);
+            }
+        }
+        self.rebase_if_needed();
+        let uniforms = self.zoom_state.uniforms(self.origin);
+        self.queue
+            .write_buffer(&self.transform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+
+        for drawable in &self.drawables {
+            drawable.update(&self.queue, &self.zoom_state);
+        }
+        for drawable in &self.drawables {
+            drawable.prepare(&mut encoder, &self.transform_bind_group);
+        }
+        if self.show_axes {
+            self.axes
+                .update(&self.queue, &self.zoom_state, &mut self.text);
+        }
+        if let Some(hover) = &self.hover {
+            hover.queue_tooltip(
+                &mut self.text,
+                &self.metadata,
+                self.zoom_state.cursor_position(),
+            );
+        }
+        let status = self.status();
+        self.text.queue_with_halo(
+            STATUS_POSITION,
+            &status,
+            STATUS_COLOR,
+            STATUS_HALO_COLOR,
+            STATUS_SIZE,
+        );
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &frame.view,
+                    view: self.post.scene_view(),
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -217,16 +1009,34 @@ impl State {
                 depth_stencil_attachment: None,
             });
 
-            let transform = self.zoom_state.matrix();
-            self.queue
-                .write_buffer(&self.transform_buffer, 0, &bytemuck::cast_slice(&transform));
-
+            if self.show_axes {
+                self.axes
+                    .draw_grid(&mut render_pass, &self.transform_bind_group);
+            }
             for drawable in &self.drawables {
                 drawable.draw(&mut render_pass, &self.transform_bind_group);
             }
+            let overlays = self
+                .selection
+                .iter()
+                .map(|selection| &selection.overlay)
+                .chain(
+                    self.hover
+                        .iter()
+                        .filter_map(|hover| hover.highlight.as_ref()),
+                )
+                .chain(self.selection_drag.iter().map(|drag| &drag.overlay));
+            for overlay in overlays {
+                overlay.draw(&mut render_pass, &self.transform_bind_group);
+            }
         }
+        self.post.run(&mut encoder, &frame.view);
+        // Labels and tooltips go on top of the post-processed frame, so they stay crisp.
+        self.text
+            .draw(&self.device, &mut encoder, &frame.view, self.size);
 
         self.queue.submit(iter::once(encoder.finish()));
+        self.text.recall();
 
         Ok(())
     }
@@ -234,6 +1044,61 @@ impl State {
 
 fn main() {
     env_logger::init();
+
+    // An SVG file can be given on the command line to view it instead of the demo scene,
+    // `--chart` to show a demo chart, or `--bench` to measure the frame rate with many markers.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut benchmark = None;
+    let mut chart = None;
+    let mut stream = None;
+    let mut metadata = HashMap::new();
+    let layers = match args.as_slice() {
+        [] => {
+            metadata = demo_metadata();
+            demo_layers()
+        }
+        [flag] if flag == "--stream" => {
+            let layer = StreamLayer::new(STREAM_WINDOW, [0.8, 0.1, 0.1, 1.], 1.5);
+            let writer = layer.writer();
+            spawn_telemetry(writer.clone());
+            stream = Some(writer);
+            vec![Box::new(layer) as Box<dyn Layer>]
+        }
+        [flag] if flag == "--chart" => {
+            let c = demo_chart();
+            let layers = c.layers();
+            chart = Some(c);
+            layers
+        }
+        [flag, kind, count] if flag == "--bench" => match Benchmark::from_args(kind, count) {
+            Some(b) => {
+                let layers = b.layers();
+                benchmark = Some(b);
+                layers
+            }
+            None => {
+                eprintln!("{}", USAGE);
+                return;
+            }
+        },
+        [path] => match svg::load_svg(Path::new(path)) {
+            Ok(imported) => {
+                for warning in &imported.warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+                imported.layers
+            }
+            Err(e) => {
+                eprintln!("Could not load {}: {}", path, e);
+                return;
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            return;
+        }
+    };
+
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_title("Shape Drawing Demo")
@@ -246,7 +1111,16 @@ fn main() {
 
     use futures::executor::block_on;
 
-    let mut state = block_on(State::new(&window));
+    let mut state = block_on(State::new(&window, layers));
+    state.set_metadata(metadata);
+    if let Some(chart) = &chart {
+        state.show_chart(chart);
+    }
+    // Streams keep changing, so they are redrawn continuously like benchmarks.
+    let animate = benchmark.is_some() || stream.is_some();
+    if let Some(stream) = stream {
+        state.follow(stream);
+    }
 
     event_loop.run(move |event, _, control_flow| {
         match event {
@@ -266,6 +1140,184 @@ fn main() {
                                 },
                             ..
                         } => *control_flow = ControlFlow::Exit,
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::S),
+                                    ..
+                                },
+                            ..
+                        } => state.export_svg(Path::new(SVG_EXPORT_PATH)),
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::P),
+                                    ..
+                                },
+                            ..
+                        } => {
+                            state.next_post_preset();
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::G),
+                                    ..
+                                },
+                            ..
+                        } => {
+                            state.toggle_axes();
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F),
+                                    ..
+                                },
+                            ..
+                        } => state.toggle_follow(),
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::H),
+                                    ..
+                                },
+                            ..
+                        } if !state.modifiers.ctrl() => {
+                            state.set_tool(Tool::Pan);
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::B),
+                                    ..
+                                },
+                            ..
+                        } if !state.modifiers.ctrl() => {
+                            state.set_tool(Tool::BoxSelect);
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::L),
+                                    ..
+                                },
+                            ..
+                        } if !state.modifiers.ctrl() => {
+                            state.set_tool(Tool::Lasso);
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::E),
+                                    ..
+                                },
+                            ..
+                        } if !state.modifiers.ctrl() => {
+                            state.set_tool(Tool::Edit);
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::C),
+                                    ..
+                                },
+                            ..
+                        } if !state.modifiers.ctrl() => {
+                            state.set_tool(Tool::Create(ShapeKind::Circle));
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::R),
+                                    ..
+                                },
+                            ..
+                        } if !state.modifiers.ctrl() => {
+                            state.set_tool(Tool::Create(ShapeKind::Rectangle));
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::N),
+                                    ..
+                                },
+                            ..
+                        } if !state.modifiers.ctrl() => {
+                            state.set_tool(Tool::Create(ShapeKind::Line));
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::Z),
+                                    ..
+                                },
+                            ..
+                        } if state.modifiers.ctrl() => {
+                            if state.modifiers.shift() {
+                                state.redo();
+                            } else {
+                                state.undo();
+                            }
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::Y),
+                                    ..
+                                },
+                            ..
+                        } if state.modifiers.ctrl() => {
+                            state.redo();
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::Delete),
+                                    ..
+                                },
+                            ..
+                        } => {
+                            state.delete_selection();
+                            window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::Back),
+                                    ..
+                                },
+                            ..
+                        } => {
+                            state.delete_selection();
+                            window.request_redraw();
+                        }
                         WindowEvent::Resized(physical_size) => {
                             state.resize(*physical_size);
                             window.request_redraw();
@@ -280,7 +1332,11 @@ fn main() {
             }
             Event::RedrawRequested(_) => {
                 match state.render() {
-                    Ok(_) => {}
+                    Ok(_) => {
+                        if let Some(benchmark) = &mut benchmark {
+                            benchmark.frame_rendered();
+                        }
+                    }
                     // Recreate the swap_chain if lost
                     Err(wgpu::SwapChainError::Lost) => state.resize(state.size),
                     // The system is out of memory, we should probably quit
@@ -289,6 +1345,8 @@ fn main() {
                     Err(e) => eprintln!("{:?}", e),
                 }
             }
+            // Benchmarks and streams redraw continuously, otherwise only when something changed.
+            Event::MainEventsCleared if animate => window.request_redraw(),
             _ => *control_flow = ControlFlow::Wait,
         }
     });
@@ -0,0 +1,58 @@
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue, RenderPass, SwapChainDescriptor};
+
+use crate::layer::{Drawable, Layer};
+
+/// A layer drawn on top of the scene for user interaction, like highlights or a rubber
+/// band. Unlike the scene's layers, it is replaced as the user interacts.
+pub struct Overlay {
+    layer: Box<dyn Layer>,
+    drawable: Option<Box<dyn Drawable>>,
+}
+
+impl Overlay {
+    /// The overlay is drawn once `init_drawable` has been called.
+    pub fn new(layer: Box<dyn Layer>) -> Self {
+        Overlay {
+            layer,
+            drawable: None,
+        }
+    }
+
+    /// Uploads the layer relative to `origin`, like `Layer::init_drawable`.
+    pub fn init_drawable(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        sc_desc: &SwapChainDescriptor,
+        transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
+    ) {
+        self.drawable =
+            Some(
+                self.layer
+                    .init_drawable(device, queue, sc_desc, transform_layout, origin),
+            );
+    }
+
+    /// Replaces the layer with one of the same size, writing its instances over the uploaded
+    /// ones like `Layer::rebase`. Returns false if the overlay has to be uploaded again with
+    /// `init_drawable`.
+    pub fn replace_layer(
+        &mut self,
+        layer: Box<dyn Layer>,
+        queue: &Queue,
+        origin: [f64; 2],
+    ) -> bool {
+        self.layer = layer;
+        match &self.drawable {
+            Some(drawable) => self.layer.rebase(queue, drawable.as_ref(), origin),
+            None => false,
+        }
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        if let Some(drawable) = &self.drawable {
+            drawable.draw(render_pass, bind_group);
+        }
+    }
+}
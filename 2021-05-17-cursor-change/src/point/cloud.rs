@@ -0,0 +1,248 @@
+use std::fmt::{self, Write};
+
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPass, RenderPipeline,
+    SwapChainDescriptor,
+};
+
+use super::{
+    create_marker_bind_group, create_pipeline, write_marker, Marker, Point, PointInstance,
+};
+use crate::layer::{Drawable, Layer};
+use crate::svg::SvgIds;
+use crate::zoom::ZoomState;
+
+// Keeps each instance buffer well below the buffer size limits of common adapters.
+const MAX_POINTS_PER_CHUNK: usize = 1 << 20;
+// Bits per axis of the grid used to sort points into spatially coherent chunks.
+const SORT_BITS: u32 = 16;
+
+/// Points stored as f32 offsets from an f64 origin. The origin is only brought into f32
+/// relative to the view center, so nearby points stay distinct however far they are from
+/// the scene origin.
+struct Chunk {
+    origin: [f64; 2],
+    points: Vec<PointInstance>,
+}
+
+/// Like `PointsLayer`, but for tens of millions of points: they are split into chunks
+/// with their own buffer, draw call and origin.
+pub struct PointCloudLayer {
+    chunks: Vec<Chunk>,
+    marker: Marker,
+}
+
+// Interleaves the bits of the two coordinates, so that sorting by the result keeps nearby
+// points together.
+fn morton_code(x: u32, y: u32) -> u32 {
+    let spread = |mut v: u32| {
+        v = (v | (v << 8)) & 0x00ff_00ff;
+        v = (v | (v << 4)) & 0x0f0f_0f0f;
+        v = (v | (v << 2)) & 0x3333_3333;
+        (v | (v << 1)) & 0x5555_5555
+    };
+    spread(x) | (spread(y) << 1)
+}
+
+fn bounds<'a>(positions: impl Iterator<Item = &'a [f64; 2]>) -> ([f64; 2], [f64; 2]) {
+    let mut min = [f64::INFINITY; 2];
+    let mut max = [f64::NEG_INFINITY; 2];
+    for &[x, y] in positions {
+        min = [min[0].min(x), min[1].min(y)];
+        max = [max[0].max(x), max[1].max(y)];
+    }
+    (min, max)
+}
+
+impl PointCloudLayer {
+    pub fn new(points: &[Point]) -> Self {
+        let (min, max) = bounds(points.iter().map(|p| &p.position));
+        let cells = ((1 << SORT_BITS) - 1) as f64;
+        let quantize = |v: f64, axis: usize| {
+            let extent = max[axis] - min[axis];
+            if extent > 0. {
+                ((v - min[axis]) / extent * cells) as u32
+            } else {
+                0
+            }
+        };
+
+        let mut order: Vec<(u32, usize)> = points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let [x, y] = p.position;
+                (morton_code(quantize(x, 0), quantize(y, 1)), i)
+            })
+            .collect();
+        order.sort_unstable_by_key(|&(code, _)| code);
+
+        let chunks = order
+            .chunks(MAX_POINTS_PER_CHUNK)
+            .map(|chunk| {
+                let (min, max) = bounds(chunk.iter().map(|&(_, i)| &points[i].position));
+                let origin = [(min[0] + max[0]) / 2., (min[1] + max[1]) / 2.];
+                let points = chunk
+                    .iter()
+                    .map(|&(_, i)| points[i].instance(origin))
+                    .collect();
+                Chunk { origin, points }
+            })
+            .collect();
+
+        PointCloudLayer {
+            chunks,
+            marker: Marker::Round,
+        }
+    }
+
+    pub fn with_marker(mut self, marker: Marker) -> Self {
+        self.marker = marker;
+        self
+    }
+}
+
+struct ChunkDrawable {
+    origin: [f64; 2],
+    instance_buffer: Buffer,
+    offset_buffer: Buffer,
+    offset_bind_group: BindGroup,
+    num_points: u32,
+}
+
+pub struct PointCloudLayerDrawable {
+    render_pipeline: RenderPipeline,
+    marker_bind_group: BindGroup,
+    chunks: Vec<ChunkDrawable>,
+}
+
+impl Drawable for PointCloudLayerDrawable {
+    fn update(&self, queue: &Queue, zoom_state: &ZoomState) {
+        let [center_x, center_y] = zoom_state.center();
+        for chunk in &self.chunks {
+            let offset: [f32; 4] = [
+                (chunk.origin[0] - center_x) as f32,
+                (chunk.origin[1] - center_y) as f32,
+                0.,
+                0.,
+            ];
+            queue.write_buffer(&chunk.offset_buffer, 0, bytemuck::cast_slice(&offset));
+        }
+    }
+
+    fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, &self.marker_bind_group, &[]);
+        for chunk in &self.chunks {
+            render_pass.set_bind_group(2, &chunk.offset_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, chunk.instance_buffer.slice(..));
+            render_pass.draw(0..6, 0..chunk.num_points);
+        }
+    }
+}
+
+impl Layer for PointCloudLayer {
+    fn init_drawable(
+        &self,
+        device: &Device,
+        _queue: &Queue,
+        sc_desc: &SwapChainDescriptor,
+        transform_layout: &BindGroupLayout,
+        // Chunks have their own origins, which `update` makes relative to the view center.
+        _origin: [f64; 2],
+    ) -> Box<dyn Drawable> {
+        let (marker_layout, marker_bind_group) = create_marker_bind_group(device, self.marker);
+
+        let offset_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Chunk offset bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let chunks = self
+            .chunks
+            .iter()
+            .map(|chunk| {
+                let instance_buffer =
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Instance buffer"),
+                        contents: bytemuck::cast_slice(&chunk.points),
+                        usage: wgpu::BufferUsage::VERTEX,
+                    });
+
+                // Written by `update` before every frame.
+                let offset_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Chunk offset buffer"),
+                    size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                    mapped_at_creation: false,
+                });
+
+                let offset_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Chunk offset bind group"),
+                    layout: &offset_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: offset_buffer.as_entire_binding(),
+                    }],
+                });
+
+                ChunkDrawable {
+                    origin: chunk.origin,
+                    instance_buffer,
+                    offset_buffer,
+                    offset_bind_group,
+                    num_points: chunk.points.len() as u32,
+                }
+            })
+            .collect();
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("cloud.vert.spv"));
+        let render_pipeline = create_pipeline(
+            device,
+            sc_desc,
+            &[transform_layout, &marker_layout, &offset_layout],
+            &vs_module,
+        );
+
+        Box::new(PointCloudLayerDrawable {
+            render_pipeline,
+            marker_bind_group,
+            chunks,
+        })
+    }
+
+    fn rebase(&self, _queue: &Queue, _drawable: &dyn Drawable, _origin: [f64; 2]) -> bool {
+        // Chunks have their own origins, so there is nothing to move.
+        true
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, pixel_size: f32, _ids: &mut SvgIds) -> fmt::Result {
+        for chunk in &self.chunks {
+            for point in &chunk.points {
+                let position = [
+                    chunk.origin[0] + point.position[0] as f64,
+                    chunk.origin[1] + point.position[1] as f64,
+                ];
+                write_marker(
+                    out,
+                    self.marker,
+                    position,
+                    point.radius * pixel_size,
+                    point.color,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+#version 450
+
+layout(location=0) in vec2 a_position;
+layout(location=1) in vec4 a_color;
+layout(location=2) in float a_radius;
+
+layout(location=0) out vec4 v_color;
+layout(location=1) out vec2 v_coord;
+layout(location=2) flat out float v_radius;
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_transform;
+    vec2 u_viewport_size;
+};
+
+// The chunk's origin relative to the view center, computed in double precision on the CPU.
+layout(set=2, binding=0)
+uniform ChunkUniforms {
+    vec2 u_chunk_offset;
+};
+
+void main() {
+    switch (gl_VertexIndex) {
+        case 0:
+            v_coord = vec2(-1., -1.);
+            break;
+        case 1:
+        case 3:
+            v_coord = vec2(1., -1.);
+            break;
+        case 2:
+        case 4:
+            v_coord = vec2(-1., 1.);
+            break;
+        case 5:
+            v_coord = vec2(1., 1.);
+    }
+
+    // v_coord is in pixels relative to the center, with a pixel of margin for antialiasing.
+    v_coord *= a_radius + 1.;
+
+    // Only the scale of the transform is used, since positions are already relative to the
+    // view center.
+    vec2 scale = vec2(u_transform[0][0], u_transform[1][1]);
+    gl_Position = vec4((u_chunk_offset + a_position) * scale, 0., 1.);
+    gl_Position.xy += 2. * v_coord / u_viewport_size;
+
+    v_color = a_color;
+    v_radius = a_radius;
+}
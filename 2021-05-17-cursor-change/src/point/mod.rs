@@ -0,0 +1,369 @@
+use std::fmt::{self, Write};
+
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, Queue, RenderPass,
+    RenderPipeline, SwapChainDescriptor,
+};
+
+use crate::circle::{Circle, CirclesLayer};
+use crate::layer::{
+    relative_to, Drawable, InstanceIds, Layer, HIGHLIGHT_WIDTH_IN_PIXELS, SIZE_IN_PIXELS,
+};
+use crate::selection::Region;
+use crate::svg::{svg_paint, SvgIds};
+
+pub use cloud::PointCloudLayer;
+
+mod cloud;
+
+/// A marker of fixed size on screen, like a point sprite.
+#[derive(Copy, Clone, Debug)]
+pub struct Point {
+    pub position: [f64; 2],
+    pub color: [f32; 4],
+    /// In screen pixels. For square and diamond markers, the distance from the center to
+    /// the middle of a side or to a corner, respectively.
+    pub radius: f32,
+}
+
+/// A `Point` as uploaded, with the position relative to the upload origin, or to the
+/// origin of its chunk in a `PointCloudLayer`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct PointInstance {
+    position: [f32; 2],
+    color: [f32; 4],
+    radius: f32,
+}
+
+impl Point {
+    fn instance(&self, origin: [f64; 2]) -> PointInstance {
+        PointInstance {
+            position: relative_to(self.position, origin),
+            color: self.color,
+            radius: self.radius,
+        }
+    }
+
+    fn contains(&self, marker: Marker, position: [f64; 2], pixel_size: f32) -> bool {
+        let radius = (self.radius * pixel_size) as f64;
+        let [dx, dy] = [
+            (position[0] - self.position[0]).abs(),
+            (position[1] - self.position[1]).abs(),
+        ];
+        match marker {
+            Marker::Round => dx * dx + dy * dy <= radius * radius,
+            Marker::Square => dx.max(dy) <= radius,
+            Marker::Diamond => dx + dy <= radius,
+        }
+    }
+}
+
+/// The shape drawn for every point of a layer. Must match the constants in shader.frag.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Marker {
+    Round = 0,
+    Square = 1,
+    Diamond = 2,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct PointsUniforms {
+    marker: u32,
+    _padding: [u32; 3],
+}
+
+/// Lots of small markers, cheaper than `CirclesLayer` since there are no strokes and sizes
+/// are always in pixels.
+pub struct PointsLayer {
+    data: Vec<Point>,
+    marker: Marker,
+    ids: InstanceIds,
+}
+
+impl PointsLayer {
+    pub fn new(data: Vec<Point>) -> Self {
+        PointsLayer {
+            data,
+            marker: Marker::Round,
+            ids: InstanceIds::default(),
+        }
+    }
+
+    pub fn with_marker(mut self, marker: Marker) -> Self {
+        self.marker = marker;
+        self
+    }
+
+    /// Attaches an ID to every instance, see `InstanceIds`.
+    pub fn with_ids(mut self, ids: Vec<u64>) -> Self {
+        self.ids = InstanceIds::new(ids, self.data.len());
+        self
+    }
+}
+
+pub struct PointsLayerDrawable {
+    render_pipeline: RenderPipeline,
+    instance_buffer: Buffer,
+    marker_bind_group: BindGroup,
+    num_points: u32,
+}
+
+impl Drawable for PointsLayerDrawable {
+    fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, &self.marker_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.num_points);
+    }
+
+    fn write_instances(&self, queue: &Queue, first: usize, data: &[u8]) {
+        let offset = first * std::mem::size_of::<PointInstance>();
+        queue.write_buffer(&self.instance_buffer, offset as wgpu::BufferAddress, data);
+    }
+}
+
+// Shared with `PointCloudLayer`, which draws the same instances chunk by chunk.
+fn instance_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<PointInstance>() as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Instance,
+        attributes: &[
+            wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float32x2,
+            },
+            wgpu::VertexAttribute {
+                offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                shader_location: 1,
+                format: wgpu::VertexFormat::Float32x4,
+            },
+            wgpu::VertexAttribute {
+                offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                shader_location: 2,
+                format: wgpu::VertexFormat::Float32,
+            },
+        ],
+    }
+}
+
+fn create_marker_bind_group(device: &Device, marker: Marker) -> (BindGroupLayout, BindGroup) {
+    let marker_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Marker buffer"),
+        contents: bytemuck::cast_slice(&[PointsUniforms {
+            marker: marker as u32,
+            _padding: [0; 3],
+        }]),
+        usage: wgpu::BufferUsage::UNIFORM,
+    });
+
+    let marker_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Marker bind group layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    });
+
+    let marker_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Marker bind group"),
+        layout: &marker_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: marker_buffer.as_entire_binding(),
+        }],
+    });
+
+    (marker_layout, marker_bind_group)
+}
+
+fn create_pipeline(
+    device: &Device,
+    sc_desc: &SwapChainDescriptor,
+    bind_group_layouts: &[&BindGroupLayout],
+    vs_module: &wgpu::ShaderModule,
+) -> RenderPipeline {
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+
+    let fs_module = device.create_shader_module(&wgpu::include_spirv!("shader.frag.spv"));
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: vs_module,
+            entry_point: "main",
+            buffers: &[instance_buffer_layout()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &fs_module,
+            entry_point: "main",
+            targets: &[wgpu::ColorTargetState {
+                format: sc_desc.format,
+                write_mask: wgpu::ColorWrite::ALL,
+                blend: Some(BlendState {
+                    color: BlendComponent::OVER,
+                    alpha: BlendComponent::REPLACE,
+                }),
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            clamp_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
+
+fn write_marker(
+    out: &mut dyn Write,
+    marker: Marker,
+    [x, y]: [f64; 2],
+    radius: f32,
+    color: [f32; 4],
+) -> fmt::Result {
+    let r = radius as f64;
+    match marker {
+        Marker::Round => write!(out, r#"<circle cx="{}" cy="{}" r="{}""#, x, y, r)?,
+        Marker::Square => write!(
+            out,
+            r#"<rect x="{}" y="{}" width="{}" height="{}""#,
+            x - r,
+            y - r,
+            2. * r,
+            2. * r
+        )?,
+        Marker::Diamond => write!(
+            out,
+            r#"<polygon points="{},{} {},{} {},{} {},{}""#,
+            x + r,
+            y,
+            x,
+            y + r,
+            x - r,
+            y,
+            x,
+            y - r
+        )?,
+    }
+    let (fill, opacity) = svg_paint(color);
+    writeln!(out, r#" fill="{}" fill-opacity="{}"/>"#, fill, opacity)
+}
+
+impl Layer for PointsLayer {
+    fn init_drawable(
+        &self,
+        device: &Device,
+        _queue: &Queue,
+        sc_desc: &SwapChainDescriptor,
+        transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
+    ) -> Box<dyn Drawable> {
+        let instances: Vec<PointInstance> = self.data.iter().map(|d| d.instance(origin)).collect();
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance buffer"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let (marker_layout, marker_bind_group) = create_marker_bind_group(device, self.marker);
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("shader.vert.spv"));
+        let render_pipeline = create_pipeline(
+            device,
+            sc_desc,
+            &[transform_layout, &marker_layout],
+            &vs_module,
+        );
+
+        Box::new(PointsLayerDrawable {
+            render_pipeline,
+            instance_buffer,
+            marker_bind_group,
+            num_points: self.data.len() as u32,
+        })
+    }
+
+    fn rebase(&self, queue: &Queue, drawable: &dyn Drawable, origin: [f64; 2]) -> bool {
+        let instances: Vec<PointInstance> = self.data.iter().map(|d| d.instance(origin)).collect();
+        drawable.write_instances(queue, 0, bytemuck::cast_slice(&instances));
+        true
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, pixel_size: f32, _ids: &mut SvgIds) -> fmt::Result {
+        for point in &self.data {
+            write_marker(
+                out,
+                self.marker,
+                point.position,
+                point.radius * pixel_size,
+                point.color,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn pick(&self, position: [f64; 2], pixel_size: f32) -> Option<usize> {
+        self.data
+            .iter()
+            .rposition(|point| point.contains(self.marker, position, pixel_size))
+    }
+
+    fn instance_id(&self, index: usize) -> u64 {
+        self.ids.get(index)
+    }
+
+    fn select(&self, region: &Region) -> Vec<usize> {
+        (0..self.data.len())
+            .filter(|&i| region.contains(self.data[i].position))
+            .collect()
+    }
+
+    fn highlight(&self, indices: &[usize], color: [f32; 4]) -> Option<Box<dyn Layer>> {
+        // Rings just outside the markers, whatever their shape.
+        let rings = indices
+            .iter()
+            .map(|&i| {
+                let point = &self.data[i];
+                let radius = match self.marker {
+                    Marker::Round | Marker::Diamond => point.radius,
+                    Marker::Square => point.radius * std::f32::consts::SQRT_2,
+                };
+                Circle {
+                    position: point.position,
+                    color: [0.; 4],
+                    radius: radius + HIGHLIGHT_WIDTH_IN_PIXELS,
+                    stroke_color: color,
+                    stroke_width: HIGHLIGHT_WIDTH_IN_PIXELS,
+                    flags: SIZE_IN_PIXELS,
+                }
+            })
+            .collect();
+        Some(Box::new(CirclesLayer::new(rings)))
+    }
+}
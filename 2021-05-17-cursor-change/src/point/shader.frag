@@ -0,0 +1,37 @@
+#version 450
+
+layout(location=0) in vec4 v_color;
+layout(location=1) in vec2 v_coord;
+layout(location=2) flat in float v_radius;
+
+layout(location=0) out vec4 f_color;
+
+layout(set=1, binding=0)
+uniform PointsUniforms {
+    uint u_marker;
+};
+
+const uint MARKER_ROUND = 0;
+const uint MARKER_SQUARE = 1;
+const uint MARKER_DIAMOND = 2;
+
+void main() {
+    // Signed distance to the edge of the marker in pixels, negative inside.
+    float d;
+    if (u_marker == MARKER_SQUARE) {
+        vec2 q = abs(v_coord) - v_radius;
+        d = length(max(q, 0.)) + min(max(q.x, q.y), 0.);
+    } else if (u_marker == MARKER_DIAMOND) {
+        d = (abs(v_coord.x) + abs(v_coord.y) - v_radius) / sqrt(2.);
+    } else {
+        d = length(v_coord) - v_radius;
+    }
+
+    float alpha = clamp(0.5 - d, 0., 1.) * v_color.a;
+
+    if (alpha < 0.01) {
+        discard;
+    }
+
+    f_color = vec4(v_color.rgb * alpha, alpha);
+}
@@ -0,0 +1,42 @@
+#version 450
+
+layout(location=0) in vec2 a_position;
+layout(location=1) in vec4 a_color;
+layout(location=2) in float a_radius;
+
+layout(location=0) out vec4 v_color;
+layout(location=1) out vec2 v_coord;
+layout(location=2) flat out float v_radius;
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_transform;
+    vec2 u_viewport_size;
+};
+
+void main() {
+    switch (gl_VertexIndex) {
+        case 0:
+            v_coord = vec2(-1., -1.);
+            break;
+        case 1:
+        case 3:
+            v_coord = vec2(1., -1.);
+            break;
+        case 2:
+        case 4:
+            v_coord = vec2(-1., 1.);
+            break;
+        case 5:
+            v_coord = vec2(1., 1.);
+    }
+
+    // v_coord is in pixels relative to the center, with a pixel of margin for antialiasing.
+    // The offset is applied in clip space, so the marker keeps its size when zooming.
+    v_coord *= a_radius + 1.;
+    gl_Position = u_transform * vec4(a_position, 0., 1.);
+    gl_Position.xy += 2. * v_coord / u_viewport_size;
+
+    v_color = a_color;
+    v_radius = a_radius;
+}
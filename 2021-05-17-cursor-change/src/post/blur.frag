@@ -0,0 +1,23 @@
+#version 450
+
+#include "post.glsl"
+
+// One direction of a separable Gaussian blur. u_params.xy is the direction, u_params.z the
+// standard deviation in pixels.
+const int MAX_TAPS = 32;
+
+void main() {
+    vec2 offset = u_params.xy * texel_size();
+    float sigma = max(u_params.z, 0.01);
+    int taps = min(int(ceil(3. * sigma)), MAX_TAPS);
+
+    vec4 sum = sample_input(v_tex_coord);
+    float total = 1.;
+    for (int i = 1; i <= taps; i++) {
+        float weight = exp(-float(i * i) / (2. * sigma * sigma));
+        sum += weight * (sample_input(v_tex_coord + float(i) * offset)
+            + sample_input(v_tex_coord - float(i) * offset));
+        total += 2. * weight;
+    }
+    f_color = sum / total;
+}
@@ -0,0 +1,11 @@
+#version 450
+
+#include "post.glsl"
+
+// Keeps what is brighter than the threshold in u_params.x, the source of bloom.
+void main() {
+    vec4 color = sample_input(v_tex_coord);
+    float brightness = max(color.r, max(color.g, color.b));
+    float contribution = max(brightness - u_params.x, 0.) / max(brightness, 0.0001);
+    f_color = color * contribution;
+}
@@ -0,0 +1,16 @@
+#version 450
+
+#include "post.glsl"
+
+// u_params holds brightness (a factor), contrast (an exponent around middle gray) and
+// saturation (0 is grayscale).
+const float MIDDLE_GRAY = 0.18;
+
+void main() {
+    vec4 color = sample_input(v_tex_coord);
+    vec3 rgb = color.rgb * u_params.x;
+    rgb = MIDDLE_GRAY * pow(max(rgb, 0.) / MIDDLE_GRAY, vec3(u_params.y));
+    float luma = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+    rgb = mix(vec3(luma), rgb, u_params.z);
+    f_color = vec4(rgb, color.a);
+}
@@ -0,0 +1,9 @@
+#version 450
+
+#include "post.glsl"
+
+// Adds the second texture, scaled by u_params.x, to the input.
+void main() {
+    vec4 bloom = texture(sampler2D(t_second, s_input), v_tex_coord);
+    f_color = sample_input(v_tex_coord) + u_params.x * bloom;
+}
@@ -0,0 +1,49 @@
+#version 450
+
+#include "post.glsl"
+
+// The compact console version of Timothy Lottes' FXAA: blurs along the direction of the
+// local luma gradient, unless that overshoots the neighborhood.
+const float FXAA_REDUCE_MIN = 1. / 128.;
+const float FXAA_REDUCE_MUL = 1. / 8.;
+const float FXAA_SPAN_MAX = 8.;
+
+// Colors are linear, the square root is close enough to perceived lightness.
+float luma(vec4 color) {
+    return sqrt(dot(color.rgb, vec3(0.299, 0.587, 0.114)));
+}
+
+void main() {
+    vec2 texel = texel_size();
+    vec4 center = sample_input(v_tex_coord);
+    float luma_nw = luma(sample_input(v_tex_coord + vec2(-1., -1.) * texel));
+    float luma_ne = luma(sample_input(v_tex_coord + vec2(1., -1.) * texel));
+    float luma_sw = luma(sample_input(v_tex_coord + vec2(-1., 1.) * texel));
+    float luma_se = luma(sample_input(v_tex_coord + vec2(1., 1.) * texel));
+    float luma_m = luma(center);
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    vec2 dir = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    float dir_reduce = max(
+        (luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL,
+        FXAA_REDUCE_MIN
+    );
+    float rcp_dir_min = 1. / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, -FXAA_SPAN_MAX, FXAA_SPAN_MAX) * texel;
+
+    vec4 color_a = 0.5 * (
+        sample_input(v_tex_coord + dir * (1. / 3. - 0.5))
+        + sample_input(v_tex_coord + dir * (2. / 3. - 0.5))
+    );
+    vec4 color_b = 0.5 * color_a + 0.25 * (
+        sample_input(v_tex_coord - dir * 0.5) + sample_input(v_tex_coord + dir * 0.5)
+    );
+
+    float luma_b = luma(color_b);
+    f_color = (luma_b < luma_min || luma_b > luma_max) ? color_a : color_b;
+}
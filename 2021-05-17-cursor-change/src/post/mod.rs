@@ -0,0 +1,412 @@
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, RenderPipeline, Sampler,
+    SwapChainDescriptor, TextureFormat, TextureView,
+};
+use winit::dpi::PhysicalSize;
+
+/// What layers render into. Floating point, so bloom and tone mapping get to see colors
+/// brighter than white.
+pub const SCENE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+// The scene and two textures to ping-pong between, which is enough for every effect.
+const NUM_TARGETS: usize = 3;
+
+/// A full-screen effect applied to the rendered scene.
+#[derive(Copy, Clone, Debug)]
+pub enum PostEffect {
+    /// Gaussian blur, with the standard deviation in pixels.
+    Blur { sigma: f32 },
+    /// Adds a blurred copy of everything brighter than `threshold`.
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        sigma: f32,
+    },
+    /// Maps colors brighter than white back into range with a filmic curve.
+    ToneMapping { exposure: f32 },
+    ColorGrading {
+        brightness: f32,
+        contrast: f32,
+        saturation: f32,
+    },
+    /// Fast approximate antialiasing.
+    Fxaa,
+}
+
+/// The shaders of the passes effects are made of. Indexes `PostChain::pipelines`.
+#[derive(Copy, Clone, Debug)]
+enum Shader {
+    Blit = 0,
+    Blur = 1,
+    Bright = 2,
+    Composite = 3,
+    ToneMapping = 4,
+    ColorGrading = 5,
+    Fxaa = 6,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Target {
+    Texture(usize),
+    Output,
+}
+
+struct Pass {
+    shader: Shader,
+    uniform_buffer: Buffer,
+    input: usize,
+    // Sampled by the composite pass only.
+    second: usize,
+    output: Target,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct PostUniforms {
+    params: [f32; 4],
+}
+
+/// Renders the layers into an offscreen texture, and runs a chain of post-processing passes
+/// on it before presenting. The intermediate textures follow the window size.
+pub struct PostChain {
+    pipelines: Vec<RenderPipeline>,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    passes: Vec<Pass>,
+    targets: Vec<TextureView>,
+    bind_groups: Vec<BindGroup>,
+}
+
+/// The descriptor layers create their pipelines with: the size of the window, but the
+/// format of the scene texture.
+pub fn scene_desc(sc_desc: &SwapChainDescriptor) -> SwapChainDescriptor {
+    SwapChainDescriptor {
+        format: SCENE_FORMAT,
+        ..sc_desc.clone()
+    }
+}
+
+// The two targets other than `current`.
+fn others(current: usize) -> (usize, usize) {
+    ((current + 1) % NUM_TARGETS, (current + 2) % NUM_TARGETS)
+}
+
+impl PostChain {
+    pub fn new(device: &Device, sc_desc: &SwapChainDescriptor, effects: &[PostEffect]) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post-processing bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let vs_module =
+            device.create_shader_module(&wgpu::include_spirv!("../fullscreen.vert.spv"));
+        let fs_modules = [
+            wgpu::include_spirv!("blit.frag.spv"),
+            wgpu::include_spirv!("blur.frag.spv"),
+            wgpu::include_spirv!("bright.frag.spv"),
+            wgpu::include_spirv!("composite.frag.spv"),
+            wgpu::include_spirv!("tone_mapping.frag.spv"),
+            wgpu::include_spirv!("color_grading.frag.spv"),
+            wgpu::include_spirv!("fxaa.frag.spv"),
+        ];
+        let pipelines = fs_modules
+            .iter()
+            .enumerate()
+            .map(|(i, source)| {
+                // Only the final copy writes to the swap chain.
+                let format = if i == Shader::Blit as usize {
+                    sc_desc.format
+                } else {
+                    SCENE_FORMAT
+                };
+                create_pipeline(
+                    device,
+                    &layout,
+                    &vs_module,
+                    &device.create_shader_module(source),
+                    format,
+                )
+            })
+            .collect();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post-processing sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        // Lay out the passes over the targets, starting from the scene in target 0.
+        let mut passes = Vec::new();
+        let mut add_pass =
+            |shader: Shader, params: [f32; 4], input: usize, second: usize, output: Target| {
+                let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Post-processing uniform buffer"),
+                    contents: bytemuck::cast_slice(&[PostUniforms { params }]),
+                    usage: wgpu::BufferUsage::UNIFORM,
+                });
+                passes.push(Pass {
+                    shader,
+                    uniform_buffer,
+                    input,
+                    second,
+                    output,
+                });
+            };
+        let mut current = 0;
+        for effect in effects {
+            let (a, b) = others(current);
+            current = match *effect {
+                PostEffect::Blur { sigma } => {
+                    add_pass(
+                        Shader::Blur,
+                        [1., 0., sigma, 0.],
+                        current,
+                        current,
+                        Target::Texture(a),
+                    );
+                    add_pass(Shader::Blur, [0., 1., sigma, 0.], a, a, Target::Texture(b));
+                    b
+                }
+                PostEffect::Bloom {
+                    threshold,
+                    intensity,
+                    sigma,
+                } => {
+                    add_pass(
+                        Shader::Bright,
+                        [threshold, 0., 0., 0.],
+                        current,
+                        current,
+                        Target::Texture(a),
+                    );
+                    add_pass(Shader::Blur, [1., 0., sigma, 0.], a, a, Target::Texture(b));
+                    add_pass(Shader::Blur, [0., 1., sigma, 0.], b, b, Target::Texture(a));
+                    add_pass(
+                        Shader::Composite,
+                        [intensity, 0., 0., 0.],
+                        current,
+                        a,
+                        Target::Texture(b),
+                    );
+                    b
+                }
+                PostEffect::ToneMapping { exposure } => {
+                    add_pass(
+                        Shader::ToneMapping,
+                        [exposure, 0., 0., 0.],
+                        current,
+                        current,
+                        Target::Texture(a),
+                    );
+                    a
+                }
+                PostEffect::ColorGrading {
+                    brightness,
+                    contrast,
+                    saturation,
+                } => {
+                    add_pass(
+                        Shader::ColorGrading,
+                        [brightness, contrast, saturation, 0.],
+                        current,
+                        current,
+                        Target::Texture(a),
+                    );
+                    a
+                }
+                PostEffect::Fxaa => {
+                    add_pass(Shader::Fxaa, [0.; 4], current, current, Target::Texture(a));
+                    a
+                }
+            };
+        }
+        add_pass(Shader::Blit, [0.; 4], current, current, Target::Output);
+
+        let mut chain = PostChain {
+            pipelines,
+            layout,
+            sampler,
+            passes,
+            targets: Vec::new(),
+            bind_groups: Vec::new(),
+        };
+        chain.resize(device, PhysicalSize::new(sc_desc.width, sc_desc.height));
+        chain
+    }
+
+    /// The texture layers draw into, in `SCENE_FORMAT`.
+    pub fn scene_view(&self) -> &TextureView {
+        &self.targets[0]
+    }
+
+    /// Recreates the intermediate textures at the new window size.
+    pub fn resize(&mut self, device: &Device, size: PhysicalSize<u32>) {
+        self.targets = (0..NUM_TARGETS)
+            .map(|_| {
+                let texture = device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Post-processing target"),
+                    size: wgpu::Extent3d {
+                        width: size.width,
+                        height: size.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: SCENE_FORMAT,
+                    usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+                });
+                texture.create_view(&wgpu::TextureViewDescriptor::default())
+            })
+            .collect();
+
+        let targets = &self.targets;
+        let layout = &self.layout;
+        let sampler = &self.sampler;
+        self.bind_groups = self
+            .passes
+            .iter()
+            .map(|pass| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Post-processing bind group"),
+                    layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&targets[pass.input]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: pass.uniform_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: wgpu::BindingResource::TextureView(&targets[pass.second]),
+                        },
+                    ],
+                })
+            })
+            .collect();
+    }
+
+    /// Runs the passes on the scene texture, ending with a copy to `output`.
+    pub fn run(&self, encoder: &mut CommandEncoder, output: &TextureView) {
+        for (pass, bind_group) in self.passes.iter().zip(&self.bind_groups) {
+            let view = match pass.output {
+                Target::Texture(i) => &self.targets[i],
+                Target::Output => output,
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Post-processing pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&self.pipelines[pass.shader as usize]);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    layout: &BindGroupLayout,
+    vs_module: &wgpu::ShaderModule,
+    fs_module: &wgpu::ShaderModule,
+    format: TextureFormat,
+) -> RenderPipeline {
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: vs_module,
+            entry_point: "main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: fs_module,
+            entry_point: "main",
+            // Every pass replaces the whole target.
+            targets: &[wgpu::ColorTargetState {
+                format,
+                write_mask: wgpu::ColorWrite::ALL,
+                blend: None,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            clamp_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
@@ -0,0 +1,21 @@
+// Bindings shared by all post-processing passes, which run on a full-screen triangle.
+layout(location=0) in vec2 v_tex_coord;
+
+layout(location=0) out vec4 f_color;
+
+layout(set=0, binding=0) uniform texture2D t_input;
+layout(set=0, binding=1) uniform sampler s_input;
+layout(set=0, binding=2)
+uniform PostUniforms {
+    vec4 u_params;
+};
+// Only used by passes that combine two textures.
+layout(set=0, binding=3) uniform texture2D t_second;
+
+vec4 sample_input(vec2 tex_coord) {
+    return texture(sampler2D(t_input, s_input), tex_coord);
+}
+
+vec2 texel_size() {
+    return 1. / vec2(textureSize(sampler2D(t_input, s_input), 0));
+}
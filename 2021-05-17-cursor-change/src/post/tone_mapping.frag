@@ -0,0 +1,14 @@
+#version 450
+
+#include "post.glsl"
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve, after scaling by the exposure in
+// u_params.x.
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0., 1.);
+}
+
+void main() {
+    vec4 color = sample_input(v_tex_coord);
+    f_color = vec4(aces(color.rgb * u_params.x), color.a);
+}
@@ -1,52 +1,158 @@
-use crate::layer::{Drawable, Layer};
+use std::fmt::{self, Write};
+
+use crate::edit::{Shape, ShapeKind, ShapeLayer};
+use crate::fill::{Fill, FillBinding};
+use crate::layer::{
+    outline, relative_to, write_instances_at, Drawable, InstanceIds, Layer, SIZE_IN_PIXELS,
+};
+use crate::line::LinesLayer;
+use crate::selection::Region;
+use crate::svg::{svg_paint, write_stroke, SvgIds};
 use wgpu::util::DeviceExt;
 use wgpu::{
-    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, RenderPass,
+    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, Queue, RenderPass,
     RenderPipeline, SwapChainDescriptor,
 };
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+/// An axis-aligned rectangle. `upper_left` and `bottom_right` are opposite corners,
+/// and may be given in either order.
+#[derive(Copy, Clone, Debug)]
 pub struct Rectangle {
-    pub upper_left: [f32; 2],
-    pub bottom_right: [f32; 2],
+    pub upper_left: [f64; 2],
+    pub bottom_right: [f64; 2],
     pub color: [f32; 4],
+    /// Color of the outline, centered on the edge of the rectangle.
+    pub stroke_color: [f32; 4],
+    /// Width of the outline in scene units. Zero for no outline.
+    pub stroke_width: f32,
+    /// Radius of the rounded corners in scene units. Zero for square corners.
+    pub corner_radius: f32,
+    /// Either zero or `SIZE_IN_PIXELS`, which puts `stroke_width` and `corner_radius` in pixels.
+    pub flags: u32,
+}
+
+/// A `Rectangle` as uploaded, with the corners relative to the upload origin.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct RectangleInstance {
+    upper_left: [f32; 2],
+    bottom_right: [f32; 2],
+    color: [f32; 4],
+    stroke_color: [f32; 4],
+    stroke_width: f32,
+    corner_radius: f32,
+    flags: u32,
+}
+
+impl Rectangle {
+    fn instance(&self, origin: [f64; 2]) -> RectangleInstance {
+        RectangleInstance {
+            upper_left: relative_to(self.upper_left, origin),
+            bottom_right: relative_to(self.bottom_right, origin),
+            color: self.color,
+            stroke_color: self.stroke_color,
+            stroke_width: self.stroke_width,
+            corner_radius: self.corner_radius,
+            flags: self.flags,
+        }
+    }
+
+    // The bounds including the outline, as `[min, max]`.
+    fn bounds(&self, pixel_size: f32) -> [[f64; 2]; 2] {
+        let unit = if self.flags & SIZE_IN_PIXELS != 0 {
+            pixel_size
+        } else {
+            1.
+        };
+        let half_stroke = (self.stroke_width * unit / 2.) as f64;
+        let [x1, y1] = self.upper_left;
+        let [x2, y2] = self.bottom_right;
+        [
+            [x1.min(x2) - half_stroke, y1.min(y2) - half_stroke],
+            [x1.max(x2) + half_stroke, y1.max(y2) + half_stroke],
+        ]
+    }
+
+    fn corners(&self) -> [[f64; 2]; 4] {
+        let [x1, y1] = self.upper_left;
+        let [x2, y2] = self.bottom_right;
+        [[x1, y1], [x2, y1], [x2, y2], [x1, y2]]
+    }
+
+    fn contains(&self, [x, y]: [f64; 2], pixel_size: f32) -> bool {
+        let [min, max] = self.bounds(pixel_size);
+        (min[0]..=max[0]).contains(&x) && (min[1]..=max[1]).contains(&y)
+    }
 }
 
 pub struct RectanglesLayer {
     data: Vec<Rectangle>,
+    fill: Fill,
+    ids: InstanceIds,
 }
 
 impl RectanglesLayer {
     pub fn new(data: Vec<Rectangle>) -> Self {
-        RectanglesLayer { data }
+        RectanglesLayer {
+            data,
+            fill: Fill::Solid,
+            ids: InstanceIds::default(),
+        }
+    }
+
+    pub fn with_fill(mut self, fill: Fill) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    /// Attaches an ID to every instance, see `InstanceIds`.
+    pub fn with_ids(mut self, ids: Vec<u64>) -> Self {
+        self.ids = InstanceIds::new(ids, self.data.len());
+        self
     }
 }
 
 pub struct RectanglesLayerDrawable {
     render_pipeline: RenderPipeline,
     instance_buffer: Buffer,
+    fill: FillBinding,
     num_rects: u32,
 }
 
 impl Drawable for RectanglesLayerDrawable {
     fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        // Layers can be emptied by editing.
+        if self.num_rects == 0 {
+            return;
+        }
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, &self.fill.bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
         render_pass.draw(0..6, 0..self.num_rects);
     }
+
+    fn write_instances(&self, queue: &Queue, first: usize, data: &[u8]) {
+        let offset = first * std::mem::size_of::<RectangleInstance>();
+        queue.write_buffer(&self.instance_buffer, offset as wgpu::BufferAddress, data);
+    }
+
+    fn rebase(&self, queue: &Queue, origin: [f64; 2]) {
+        self.fill.rebase(queue, origin);
+    }
 }
 
 impl Layer for RectanglesLayer {
     fn init_drawable(
         &self,
         device: &Device,
+        queue: &Queue,
         sc_desc: &SwapChainDescriptor,
         transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
     ) -> Box<dyn Drawable> {
         let instance_buffer_desc = wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Rectangle>() as wgpu::BufferAddress,
+            array_stride: std::mem::size_of::<RectangleInstance>() as wgpu::BufferAddress,
             step_mode: wgpu::InputStepMode::Instance,
             attributes: &[
                 wgpu::VertexAttribute {
@@ -64,19 +170,46 @@ impl Layer for RectanglesLayer {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32x4,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 13]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Uint32,
+                },
             ],
         };
 
+        let instances: Vec<RectangleInstance> =
+            self.data.iter().map(|d| d.instance(origin)).collect();
         let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Instance buffer"),
-            contents: bytemuck::cast_slice(&self.data),
-            usage: wgpu::BufferUsage::VERTEX,
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
         });
 
+        let fill_layout = Fill::bind_group_layout(device);
+        let fill = self
+            .fill
+            .create_bind_group(device, queue, &fill_layout, origin);
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[transform_layout],
+                bind_group_layouts: &[transform_layout, &fill_layout],
                 push_constant_ranges: &[],
             });
 
@@ -125,7 +258,136 @@ impl Layer for RectanglesLayer {
         Box::new(RectanglesLayerDrawable {
             render_pipeline,
             instance_buffer,
+            fill,
             num_rects: self.data.len() as u32,
         })
     }
+
+    fn rebase(&self, queue: &Queue, drawable: &dyn Drawable, origin: [f64; 2]) -> bool {
+        let instances: Vec<RectangleInstance> =
+            self.data.iter().map(|d| d.instance(origin)).collect();
+        drawable.write_instances(queue, 0, bytemuck::cast_slice(&instances));
+        drawable.rebase(queue, origin);
+        true
+    }
+
+    fn update_instances(
+        &self,
+        queue: &Queue,
+        drawable: &dyn Drawable,
+        indices: &[usize],
+        origin: [f64; 2],
+    ) -> bool {
+        write_instances_at(queue, drawable, indices, |i| self.data[i].instance(origin));
+        true
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, pixel_size: f32, ids: &mut SvgIds) -> fmt::Result {
+        let fill_paint = self.fill.write_svg_defs(out, ids)?;
+        for rect in &self.data {
+            let unit = if rect.flags & SIZE_IN_PIXELS != 0 {
+                pixel_size
+            } else {
+                1.
+            };
+            let (mut fill, opacity) = svg_paint(rect.color);
+            if let Some(paint) = &fill_paint {
+                fill = paint.clone();
+            }
+            let [x1, y1] = rect.upper_left;
+            let [x2, y2] = rect.bottom_right;
+            write!(
+                out,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" fill-opacity="{}""#,
+                x1.min(x2),
+                y1.min(y2),
+                (x2 - x1).abs(),
+                (y2 - y1).abs(),
+                fill,
+                opacity
+            )?;
+            if rect.corner_radius > 0. {
+                write!(out, r#" rx="{}""#, rect.corner_radius * unit)?;
+            }
+            write_stroke(out, rect.stroke_color, rect.stroke_width * unit)?;
+            writeln!(out, "/>")?;
+        }
+        Ok(())
+    }
+
+    fn pick(&self, position: [f64; 2], pixel_size: f32) -> Option<usize> {
+        self.data
+            .iter()
+            .rposition(|rect| rect.contains(position, pixel_size))
+    }
+
+    fn instance_id(&self, index: usize) -> u64 {
+        self.ids.get(index)
+    }
+
+    fn as_shapes(&self) -> Option<&dyn ShapeLayer> {
+        Some(self)
+    }
+
+    fn as_shapes_mut(&mut self) -> Option<&mut dyn ShapeLayer> {
+        Some(self)
+    }
+
+    fn select(&self, region: &Region) -> Vec<usize> {
+        (0..self.data.len())
+            .filter(|&i| region.contains_all(&self.data[i].corners()))
+            .collect()
+    }
+
+    fn highlight(&self, indices: &[usize], color: [f32; 4]) -> Option<Box<dyn Layer>> {
+        // Strokes sized in pixels are left out, since the outlines are in scene units.
+        let lines = indices
+            .iter()
+            .flat_map(|&i| {
+                let [min, max] = self.data[i].bounds(0.);
+                outline(&[min, [max[0], min[1]], max, [min[0], max[1]]], color).collect::<Vec<_>>()
+            })
+            .collect();
+        Some(Box::new(LinesLayer::new(lines)))
+    }
+}
+
+impl ShapeLayer for RectanglesLayer {
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::Rectangle
+    }
+
+    fn shape_count(&self) -> usize {
+        self.data.len()
+    }
+
+    fn shape(&self, index: usize) -> Shape {
+        Shape::Rectangle(self.data[index])
+    }
+
+    fn set_shape(&mut self, index: usize, shape: Shape) -> bool {
+        match shape.into_rectangle() {
+            Some(rect) => {
+                self.data[index] = rect;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert_shape(&mut self, index: usize, shape: Shape, id: u64) -> bool {
+        match shape.into_rectangle() {
+            Some(rect) => {
+                self.ids.for_edit(self.data.len()).insert(index, id);
+                self.data.insert(index, rect);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_shape(&mut self, index: usize) -> (Shape, u64) {
+        let id = self.ids.for_edit(self.data.len()).remove(index);
+        (Shape::Rectangle(self.data.remove(index)), id)
+    }
 }
@@ -1,8 +1,37 @@
 #version 450
 
 layout(location=0) in vec4 v_color;
+layout(location=1) in vec2 v_coord;
+layout(location=2) flat in vec2 v_half_size;
+layout(location=3) in vec4 v_stroke_color;
+layout(location=4) flat in float v_stroke_width;
+layout(location=5) flat in float v_corner_radius;
+layout(location=6) in vec2 v_position;
+
 layout(location=0) out vec4 f_color;
 
+#include "../fill.glsl"
+
 void main() {
-    f_color = v_color;
+    // Signed distance to the edge of the rounded rectangle, negative inside.
+    vec2 q = abs(v_coord) - v_half_size + v_corner_radius;
+    float d = length(max(q, 0.)) + min(max(q.x, q.y), 0.) - v_corner_radius;
+    float delta = fwidth(d);
+
+    float outer = v_stroke_width / 2.;
+    float inner = -v_stroke_width / 2.;
+
+    float alpha = 1.0 - smoothstep(outer - delta*2., outer, d);
+
+    if (alpha < 0.01) {
+        discard;
+    }
+
+    // Blend premultiplied colors, so that a transparent fill gives a clean outline.
+    vec4 color = fill_color(v_color, v_position);
+    vec4 fill = vec4(color.rgb * color.a, color.a);
+    vec4 stroke = vec4(v_stroke_color.rgb * v_stroke_color.a, v_stroke_color.a);
+    float stroke_amount = v_stroke_width > 0. ? smoothstep(inner - delta*2., inner, d) : 0.;
+
+    f_color = mix(fill, stroke, stroke_amount) * alpha;
 }
@@ -3,32 +3,67 @@
 layout(location=0) in vec2 a_upper_left;
 layout(location=1) in vec2 a_lower_right;
 layout(location=2) in vec4 a_color;
+layout(location=3) in vec4 a_stroke_color;
+layout(location=4) in float a_stroke_width;
+layout(location=5) in float a_corner_radius;
+layout(location=6) in uint a_flags;
 
 layout(location=0) out vec4 v_color;
+layout(location=1) out vec2 v_coord;
+layout(location=2) flat out vec2 v_half_size;
+layout(location=3) out vec4 v_stroke_color;
+layout(location=4) flat out float v_stroke_width;
+layout(location=5) flat out float v_corner_radius;
+layout(location=6) out vec2 v_position;
 
 layout(set=0, binding=0)
 uniform Uniforms {
     mat4 u_transform;
+    vec2 u_viewport_size;
 };
 
+#include "../layer.glsl"
+
 void main() {
+    float stroke_width = a_stroke_width;
+    float corner_radius = a_corner_radius;
+    if ((a_flags & SIZE_IN_PIXELS) != 0) {
+        // Scene units per pixel.
+        float px = 2. / (u_transform[0][0] * u_viewport_size.x);
+        stroke_width *= px;
+        corner_radius *= px;
+    }
+
+    // The corners may be given in either order.
+    vec2 center = (a_upper_left + a_lower_right) / 2.;
+    vec2 half_size = abs(a_lower_right - a_upper_left) / 2.;
+    // The stroke is centered on the edge, so half of it lies outside the rectangle.
+    vec2 outer = half_size + stroke_width / 2.;
+
     switch (gl_VertexIndex) {
         case 0:
-            gl_Position = vec4(a_upper_left, 0., 1.);
+            v_coord = vec2(-1., -1.);
             break;
         case 1:
         case 3:
-            gl_Position = vec4(a_upper_left.x, a_lower_right.y, 0., 1.);
+            v_coord = vec2(1., -1.);
             break;
         case 2:
         case 4:
-            gl_Position = vec4(a_lower_right.x, a_upper_left.y, 0., 1.);
+            v_coord = vec2(-1., 1.);
             break;
         case 5:
-            gl_Position = vec4(a_lower_right, 0., 1.);
+            v_coord = vec2(1., 1.);
     }
 
-    gl_Position = u_transform * gl_Position;
+    // v_coord is in scene units relative to the center.
+    v_coord *= outer;
+    v_position = center + v_coord;
+    gl_Position = u_transform * vec4(v_position, 0., 1.);
 
     v_color = a_color;
+    v_half_size = half_size;
+    v_stroke_color = a_stroke_color;
+    v_stroke_width = stroke_width;
+    v_corner_radius = min(corner_radius, min(half_size.x, half_size.y));
 }
@@ -0,0 +1,278 @@
+use std::collections::BTreeSet;
+
+use wgpu::Queue;
+
+use crate::circle::{Circle, CirclesLayer};
+use crate::edit::Shape;
+use crate::layer::{Layer, LayerGroup, SELECTION_COLOR, SIZE_IN_PIXELS};
+use crate::overlay::Overlay;
+
+/// Radius of the handles drawn on selected shapes, in pixels.
+pub const HANDLE_RADIUS_IN_PIXELS: f32 = 4.;
+const HANDLE_STROKE_WIDTH_IN_PIXELS: f32 = 1.5;
+const HANDLE_COLOR: [f32; 4] = [1., 1., 1., 1.];
+
+/// An area of the scene that instances are selected in.
+#[derive(Clone, Debug)]
+pub enum Region {
+    /// An axis-aligned box between two opposite corners, in either order.
+    Box { corners: [[f64; 2]; 2] },
+    /// A closed polygon through the given points, which may cross itself. Points inside
+    /// follow the even-odd rule.
+    Lasso { points: Vec<[f64; 2]> },
+}
+
+impl Region {
+    pub fn contains(&self, [x, y]: [f64; 2]) -> bool {
+        match self {
+            Region::Box {
+                corners: [[x1, y1], [x2, y2]],
+            } => {
+                (x1.min(*x2)..=x1.max(*x2)).contains(&x) && (y1.min(*y2)..=y1.max(*y2)).contains(&y)
+            }
+            Region::Lasso { points } => {
+                // Counts the edges crossed by a ray going right from the point.
+                let mut inside = false;
+                for (&[x1, y1], &[x2, y2]) in points.iter().zip(points.iter().cycle().skip(1)) {
+                    if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+        }
+    }
+
+    /// Whether all of `points` are inside the region, which is how shapes are tested.
+    pub fn contains_all(&self, points: &[[f64; 2]]) -> bool {
+        points.iter().all(|&point| self.contains(point))
+    }
+
+    /// The corners of the region's outline, in order.
+    pub fn outline(&self) -> Vec<[f64; 2]> {
+        match self {
+            Region::Box {
+                corners: [[x1, y1], [x2, y2]],
+            } => vec![[*x1, *y1], [*x2, *y1], [*x2, *y2], [*x1, *y2]],
+            Region::Lasso { points } => points.clone(),
+        }
+    }
+}
+
+/// Selected instances of every layer, drawn outlined in `SELECTION_COLOR`, with handles on
+/// the selected shapes.
+pub struct Selection {
+    // The selected indices of every layer, in layer order and ascending.
+    indices: Vec<Vec<usize>>,
+    pub overlay: Overlay,
+}
+
+impl Selection {
+    /// Selects the instances at `indices` of each of `layers`.
+    pub fn new(layers: &[Box<dyn Layer>], mut indices: Vec<Vec<usize>>) -> Self {
+        for layer_indices in &mut indices {
+            layer_indices.sort_unstable();
+            layer_indices.dedup();
+        }
+
+        Selection {
+            overlay: Overlay::new(overlay_layer(layers, &indices)),
+            indices,
+        }
+    }
+
+    /// Follows changes of the selected instances that kept the number of instances of every
+    /// layer, writing the outlines and handles over the uploaded ones relative to `origin`.
+    /// Returns false if the overlay has to be uploaded again.
+    pub fn update_overlay(
+        &mut self,
+        layers: &[Box<dyn Layer>],
+        queue: &Queue,
+        origin: [f64; 2],
+    ) -> bool {
+        self.overlay
+            .replace_layer(overlay_layer(layers, &self.indices), queue, origin)
+    }
+
+    /// Selects only instance `index` of layer `layer`.
+    pub fn single(layers: &[Box<dyn Layer>], layer: usize, index: usize) -> Self {
+        let mut indices = vec![Vec::new(); layer + 1];
+        indices[layer].push(index);
+        Selection::new(layers, indices)
+    }
+
+    /// Selects the instances inside `region` across all `layers`.
+    pub fn in_region(layers: &[Box<dyn Layer>], region: &Region) -> Self {
+        Selection::new(
+            layers,
+            layers.iter().map(|layer| layer.select(region)).collect(),
+        )
+    }
+
+    pub fn count(&self) -> usize {
+        self.indices.iter().map(Vec::len).sum()
+    }
+
+    /// The selected indices of every layer, in ascending order.
+    pub fn indices(&self) -> &[Vec<usize>] {
+        &self.indices
+    }
+
+    pub fn contains(&self, layer: usize, index: usize) -> bool {
+        self.indices
+            .get(layer)
+            .map_or(false, |indices| indices.binary_search(&index).is_ok())
+    }
+
+    /// The selected instances as `(layer, id)`, with IDs given by `Layer::instance_id`. Layers
+    /// without an ID column number their instances from zero, so IDs are only unique within
+    /// a layer.
+    pub fn ids(&self, layers: &[Box<dyn Layer>]) -> BTreeSet<(usize, u64)> {
+        layers
+            .iter()
+            .zip(&self.indices)
+            .enumerate()
+            .flat_map(|(layer_index, (layer, indices))| {
+                indices
+                    .iter()
+                    .map(move |&i| (layer_index, layer.instance_id(i)))
+            })
+            .collect()
+    }
+
+    /// The selected instances of editable layers, as `(layer, index, shape)`.
+    pub fn shapes(&self, layers: &[Box<dyn Layer>]) -> Vec<(usize, usize, Shape)> {
+        shapes(layers, &self.indices)
+    }
+
+    /// The handles of the selected shapes, as `(layer, index, handle, position)`.
+    pub fn handles(&self, layers: &[Box<dyn Layer>]) -> Vec<(usize, usize, usize, [f64; 2])> {
+        handles(layers, &self.indices)
+    }
+}
+
+// The selection outlines of the instances at `indices` of each of `layers`, and the handles
+// of the selected shapes.
+fn overlay_layer(layers: &[Box<dyn Layer>], indices: &[Vec<usize>]) -> Box<dyn Layer> {
+    let mut overlays: Vec<Box<dyn Layer>> = layers
+        .iter()
+        .zip(indices)
+        .filter(|(_, indices)| !indices.is_empty())
+        .filter_map(|(layer, indices)| layer.highlight(indices, SELECTION_COLOR))
+        .collect();
+    let handles: Vec<Circle> = handles(layers, indices)
+        .into_iter()
+        .map(|(_, _, _, position)| Circle {
+            position,
+            color: HANDLE_COLOR,
+            radius: HANDLE_RADIUS_IN_PIXELS,
+            stroke_color: SELECTION_COLOR,
+            stroke_width: HANDLE_STROKE_WIDTH_IN_PIXELS,
+            flags: SIZE_IN_PIXELS,
+        })
+        .collect();
+    if !handles.is_empty() {
+        overlays.push(Box::new(CirclesLayer::new(handles)));
+    }
+
+    Box::new(LayerGroup::new(overlays))
+}
+
+fn shapes(layers: &[Box<dyn Layer>], indices: &[Vec<usize>]) -> Vec<(usize, usize, Shape)> {
+    let mut shapes = Vec::new();
+    for (layer, indices) in indices.iter().enumerate() {
+        if let Some(layer_shapes) = layers[layer].as_shapes() {
+            shapes.extend(indices.iter().map(|&i| (layer, i, layer_shapes.shape(i))));
+        }
+    }
+    shapes
+}
+
+fn handles(
+    layers: &[Box<dyn Layer>],
+    indices: &[Vec<usize>],
+) -> Vec<(usize, usize, usize, [f64; 2])> {
+    shapes(layers, indices)
+        .into_iter()
+        .flat_map(|(layer, index, shape)| {
+            shape
+                .handles()
+                .into_iter()
+                .enumerate()
+                .map(move |(handle, position)| (layer, index, handle, position))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lasso(points: &[[f64; 2]]) -> Region {
+        Region::Lasso {
+            points: points.to_vec(),
+        }
+    }
+
+    #[test]
+    fn box_in_either_corner_order() {
+        for &corners in &[
+            [[0., 0.], [2., 1.]],
+            [[2., 1.], [0., 0.]],
+            [[0., 1.], [2., 0.]],
+        ] {
+            let region = Region::Box { corners };
+            assert!(region.contains([1., 0.5]));
+            // Edges are inside.
+            assert!(region.contains([0., 0.]));
+            assert!(region.contains([2., 1.]));
+            assert!(!region.contains([2.5, 0.5]));
+            assert!(!region.contains([1., -0.5]));
+        }
+    }
+
+    #[test]
+    fn lasso_square() {
+        let region = lasso(&[[0., 0.], [2., 0.], [2., 2.], [0., 2.]]);
+        assert!(region.contains([1., 1.]));
+        assert!(!region.contains([3., 1.]));
+        assert!(!region.contains([-1., 1.]));
+        assert!(!region.contains([1., 3.]));
+        assert!(region.contains_all(&[[0.5, 0.5], [1.5, 1.5]]));
+        assert!(!region.contains_all(&[[0.5, 0.5], [2.5, 1.5]]));
+    }
+
+    #[test]
+    fn lasso_ray_through_vertex() {
+        // The ray going right from points at the height of the side corners passes through
+        // a corner, which must count as one crossing.
+        let region = lasso(&[[1., 0.], [2., 1.], [1., 2.], [0., 1.]]);
+        assert!(region.contains([0.5, 1.]));
+        assert!(region.contains([1.5, 1.]));
+        assert!(!region.contains([-0.5, 1.]));
+        assert!(!region.contains([2.5, 1.]));
+    }
+
+    #[test]
+    fn lasso_crossing_itself_is_even_odd() {
+        // A pentagram, drawn through every second corner of a regular pentagon.
+        let corner = |i: usize| {
+            let angle = std::f64::consts::FRAC_PI_2 + i as f64 * 2. * std::f64::consts::PI / 5.;
+            [angle.cos(), angle.sin()]
+        };
+        let region = lasso(&[corner(0), corner(2), corner(4), corner(1), corner(3)]);
+        // The pentagon in the middle is enclosed twice, so it is outside.
+        assert!(!region.contains([0., 0.]));
+        // The tips are enclosed once.
+        assert!(region.contains([0., 0.8]));
+        assert!(region.contains([0.6, 0.2]));
+        assert!(!region.contains([0., 1.5]));
+    }
+
+    #[test]
+    fn degenerate_lassos_contain_nothing() {
+        assert!(!lasso(&[]).contains([0., 0.]));
+        assert!(!lasso(&[[0., 0.]]).contains([0., 0.]));
+        assert!(!lasso(&[[0., 0.], [1., 1.]]).contains([0.5, 0.5]));
+    }
+}
@@ -0,0 +1,321 @@
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+
+use image::{ImageResult, RgbaImage};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, Queue, RenderPass,
+    RenderPipeline, SwapChainDescriptor,
+};
+
+use crate::layer::{relative_to, Drawable, Layer, SIZE_IN_PIXELS};
+use crate::svg::SvgIds;
+use crate::texture::upload_rgba;
+
+/// A textured quad, cut out of the layer's atlas.
+#[derive(Copy, Clone, Debug)]
+pub struct Sprite {
+    /// Center of the quad.
+    pub position: [f64; 2],
+    /// Width and height of the quad in scene units.
+    pub size: [f32; 2],
+    /// Counter-clockwise rotation around the center, in radians.
+    pub rotation: f32,
+    /// Multiplied with the texture color. White draws the image as is.
+    pub tint: [f32; 4],
+    /// Region of the atlas to draw, as `[x, y, width, height]` in atlas pixels from the
+    /// upper left corner.
+    pub source: [f32; 4],
+    /// Either zero or `SIZE_IN_PIXELS`, which puts `size` in screen pixels so the sprite
+    /// keeps the same size on screen when zooming.
+    pub flags: u32,
+}
+
+/// A `Sprite` as uploaded, with the position relative to the upload origin.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct SpriteInstance {
+    position: [f32; 2],
+    size: [f32; 2],
+    rotation: f32,
+    tint: [f32; 4],
+    source: [f32; 4],
+    flags: u32,
+}
+
+impl Sprite {
+    fn instance(&self, origin: [f64; 2]) -> SpriteInstance {
+        SpriteInstance {
+            position: relative_to(self.position, origin),
+            size: self.size,
+            rotation: self.rotation,
+            tint: self.tint,
+            source: self.source,
+            flags: self.flags,
+        }
+    }
+}
+
+/// A single image holding all the bitmaps drawn by a layer.
+pub struct Atlas {
+    path: PathBuf,
+    image: RgbaImage,
+}
+
+impl Atlas {
+    pub fn load(path: impl AsRef<Path>) -> ImageResult<Atlas> {
+        let path = path.as_ref().to_path_buf();
+        let image = image::open(&path)?.into_rgba8();
+        Ok(Atlas { path, image })
+    }
+
+    /// The source rectangle covering the whole atlas.
+    pub fn full_source(&self) -> [f32; 4] {
+        [
+            0.,
+            0.,
+            self.image.width() as f32,
+            self.image.height() as f32,
+        ]
+    }
+}
+
+pub struct ImagesLayer {
+    atlas: Atlas,
+    data: Vec<Sprite>,
+}
+
+impl ImagesLayer {
+    pub fn new(atlas: Atlas, data: Vec<Sprite>) -> Self {
+        ImagesLayer { atlas, data }
+    }
+}
+
+pub struct ImagesLayerDrawable {
+    render_pipeline: RenderPipeline,
+    instance_buffer: Buffer,
+    atlas_bind_group: BindGroup,
+    num_sprites: u32,
+}
+
+impl Drawable for ImagesLayerDrawable {
+    fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, &self.atlas_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.num_sprites);
+    }
+}
+
+impl Layer for ImagesLayer {
+    fn init_drawable(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        sc_desc: &SwapChainDescriptor,
+        transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
+    ) -> Box<dyn Drawable> {
+        let instance_buffer_desc = wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 13]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
+        };
+
+        let instances: Vec<SpriteInstance> = self.data.iter().map(|d| d.instance(origin)).collect();
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance buffer"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+
+        let atlas_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Atlas bind group layout"),
+            entries: &[
+                // The vertex shader needs the atlas size to turn pixels into texture coordinates.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let image = &self.atlas.image;
+        let view = upload_rgba(
+            device,
+            queue,
+            "Atlas texture",
+            image.as_raw(),
+            image.width(),
+            image.height(),
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Atlas sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let atlas_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Atlas bind group"),
+            layout: &atlas_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[transform_layout, &atlas_layout],
+                push_constant_ranges: &[],
+            });
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("shader.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("shader.frag.spv"));
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &vs_module,
+                entry_point: "main",
+                buffers: &[instance_buffer_desc],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fs_module,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format: sc_desc.format,
+                    write_mask: wgpu::ColorWrite::ALL,
+                    blend: Some(BlendState {
+                        color: BlendComponent::OVER,
+                        alpha: BlendComponent::REPLACE,
+                    }),
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                clamp_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        });
+
+        Box::new(ImagesLayerDrawable {
+            render_pipeline,
+            instance_buffer,
+            atlas_bind_group,
+            num_sprites: self.data.len() as u32,
+        })
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, pixel_size: f32, _ids: &mut SvgIds) -> fmt::Result {
+        // Tints other than opacity have no simple SVG equivalent and are left out.
+        for sprite in &self.data {
+            let unit = if sprite.flags & SIZE_IN_PIXELS != 0 {
+                pixel_size
+            } else {
+                1.
+            };
+            let [width, height] = [sprite.size[0] * unit, sprite.size[1] * unit];
+            let [sx, sy, sw, sh] = sprite.source;
+
+            // The image is flipped back upright, since the scene's Y axis is flipped.
+            writeln!(
+                out,
+                r#"<g transform="translate({} {}) rotate({}) scale(1 -1)" opacity="{}">"#,
+                sprite.position[0],
+                sprite.position[1],
+                sprite.rotation.to_degrees(),
+                sprite.tint[3]
+            )?;
+            writeln!(
+                out,
+                r#"<svg x="{}" y="{}" width="{}" height="{}" viewBox="{} {} {} {}" preserveAspectRatio="none">"#,
+                -width / 2.,
+                -height / 2.,
+                width,
+                height,
+                sx,
+                sy,
+                sw,
+                sh
+            )?;
+            writeln!(
+                out,
+                r#"<image href="{}" width="{}" height="{}"/>"#,
+                self.atlas.path.display(),
+                self.atlas.image.width(),
+                self.atlas.image.height()
+            )?;
+            writeln!(out, "</svg>")?;
+            writeln!(out, "</g>")?;
+        }
+        Ok(())
+    }
+}
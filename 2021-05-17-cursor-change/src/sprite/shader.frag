@@ -0,0 +1,19 @@
+#version 450
+
+layout(location=0) in vec2 v_tex_coord;
+layout(location=1) in vec4 v_tint;
+
+layout(location=0) out vec4 f_color;
+
+layout(set=1, binding=0) uniform texture2D t_atlas;
+layout(set=1, binding=1) uniform sampler s_atlas;
+
+void main() {
+    vec4 color = texture(sampler2D(t_atlas, s_atlas), v_tex_coord) * v_tint;
+
+    if (color.a < 0.01) {
+        discard;
+    }
+
+    f_color = vec4(color.rgb * color.a, color.a);
+}
@@ -0,0 +1,59 @@
+#version 450
+
+layout(location=0) in vec2 a_position;
+layout(location=1) in vec2 a_size;
+layout(location=2) in float a_rotation;
+layout(location=3) in vec4 a_tint;
+layout(location=4) in vec4 a_source;
+layout(location=5) in uint a_flags;
+
+layout(location=0) out vec2 v_tex_coord;
+layout(location=1) out vec4 v_tint;
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_transform;
+    vec2 u_viewport_size;
+};
+
+layout(set=1, binding=0) uniform texture2D t_atlas;
+layout(set=1, binding=1) uniform sampler s_atlas;
+
+#include "../layer.glsl"
+
+void main() {
+    vec2 corner;
+    switch (gl_VertexIndex) {
+        case 0:
+            corner = vec2(-0.5, -0.5);
+            break;
+        case 1:
+        case 3:
+            corner = vec2(0.5, -0.5);
+            break;
+        case 2:
+        case 4:
+            corner = vec2(-0.5, 0.5);
+            break;
+        case 5:
+            corner = vec2(0.5, 0.5);
+    }
+
+    vec2 size = a_size;
+    if ((a_flags & SIZE_IN_PIXELS) != 0) {
+        // Scene units per pixel.
+        size *= 2. / (u_transform[0][0] * u_viewport_size.x);
+    }
+
+    vec2 local = corner * size;
+    float c = cos(a_rotation);
+    float s = sin(a_rotation);
+    vec2 rotated = vec2(c * local.x - s * local.y, s * local.x + c * local.y);
+    gl_Position = u_transform * vec4(a_position + rotated, 0., 1.);
+
+    // The source rectangle is measured from the top of the atlas, but scene Y goes up.
+    vec2 atlas_size = vec2(textureSize(sampler2D(t_atlas, s_atlas), 0));
+    vec2 texel = a_source.xy + vec2(corner.x + 0.5, 0.5 - corner.y) * a_source.zw;
+    v_tex_coord = texel / atlas_size;
+    v_tint = a_tint;
+}
@@ -0,0 +1,313 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use wgpu::{
+    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, Queue, RenderPass,
+    RenderPipeline, SwapChainDescriptor,
+};
+
+use crate::layer::{relative_to, Drawable, Layer};
+use crate::svg::{svg_paint, SvgIds};
+use crate::zoom::ZoomState;
+
+/// The latest samples of a stream, shared between its writers and drawables.
+struct Window {
+    samples: VecDeque<[f64; 2]>,
+    capacity: usize,
+    /// Number of samples ever appended, which identifies the newest ones.
+    appended: u64,
+}
+
+/// Appends samples to a `StreamLayer`, possibly from another thread.
+#[derive(Clone)]
+pub struct StreamWriter {
+    window: Arc<Mutex<Window>>,
+}
+
+impl StreamWriter {
+    /// Appends `[x, y]` samples, dropping the oldest ones once the window is full.
+    pub fn extend(&self, samples: impl IntoIterator<Item = [f64; 2]>) {
+        let mut window = self.window.lock().unwrap();
+        for sample in samples {
+            if window.samples.len() == window.capacity {
+                window.samples.pop_front();
+            }
+            window.samples.push_back(sample);
+            window.appended += 1;
+        }
+    }
+
+    pub fn latest(&self) -> Option<[f64; 2]> {
+        self.window.lock().unwrap().samples.back().copied()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct StreamUniforms {
+    color: [f32; 4],
+    start: u32,
+    capacity: u32,
+    width: f32,
+    _padding: u32,
+}
+
+/// A line through the latest `capacity` samples of a stream, like live telemetry. Samples
+/// go into a ring buffer on the GPU, and each frame only the ones appended since the last
+/// frame are uploaded.
+pub struct StreamLayer {
+    window: Arc<Mutex<Window>>,
+    color: [f32; 4],
+    /// In pixels.
+    width: f32,
+}
+
+impl StreamLayer {
+    pub fn new(capacity: usize, color: [f32; 4], width: f32) -> Self {
+        assert!(capacity >= 2, "A stream needs room for a segment");
+        StreamLayer {
+            window: Arc::new(Mutex::new(Window {
+                samples: VecDeque::with_capacity(capacity),
+                capacity,
+                appended: 0,
+            })),
+            color,
+            width,
+        }
+    }
+
+    pub fn writer(&self) -> StreamWriter {
+        StreamWriter {
+            window: self.window.clone(),
+        }
+    }
+}
+
+pub struct StreamLayerDrawable {
+    render_pipeline: RenderPipeline,
+    sample_buffer: Buffer,
+    uniform_buffer: Buffer,
+    bind_group: BindGroup,
+
+    window: Arc<Mutex<Window>>,
+    origin: [f64; 2],
+    color: [f32; 4],
+    width: f32,
+    // `Window::appended` as of the last upload.
+    uploaded: Cell<u64>,
+    num_samples: Cell<u32>,
+}
+
+impl Drawable for StreamLayerDrawable {
+    fn update(&self, queue: &Queue, _zoom_state: &ZoomState) {
+        let window = self.window.lock().unwrap();
+        let len = window.samples.len();
+        let new = (window.appended - self.uploaded.get()).min(len as u64) as usize;
+
+        // The sample appended as number `n` lives in slot `n % capacity`, so the new ones are
+        // written in at most two runs, split where the buffer wraps around.
+        let sample_size = std::mem::size_of::<[f32; 2]>();
+        let capacity = window.capacity;
+        let samples: Vec<[f32; 2]> = window
+            .samples
+            .iter()
+            .skip(len - new)
+            .map(|&sample| relative_to(sample, self.origin))
+            .collect();
+        let slot = ((window.appended - new as u64) % capacity as u64) as usize;
+        let run = new.min(capacity - slot);
+        if run > 0 {
+            queue.write_buffer(
+                &self.sample_buffer,
+                (slot * sample_size) as wgpu::BufferAddress,
+                bytemuck::cast_slice(&samples[..run]),
+            );
+        }
+        if run < new {
+            queue.write_buffer(
+                &self.sample_buffer,
+                0,
+                bytemuck::cast_slice(&samples[run..]),
+            );
+        }
+        self.uploaded.set(window.appended);
+
+        let start = ((window.appended - len as u64) % capacity as u64) as u32;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[StreamUniforms {
+                color: self.color,
+                start,
+                capacity: capacity as u32,
+                width: self.width,
+                _padding: 0,
+            }]),
+        );
+        self.num_samples.set(len as u32);
+    }
+
+    fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        let num_samples = self.num_samples.get();
+        if num_samples < 2 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..num_samples - 1);
+    }
+}
+
+impl Layer for StreamLayer {
+    fn init_drawable(
+        &self,
+        device: &Device,
+        _queue: &Queue,
+        sc_desc: &SwapChainDescriptor,
+        transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
+    ) -> Box<dyn Drawable> {
+        let window = self.window.lock().unwrap();
+
+        let sample_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sample ring buffer"),
+            size: (window.capacity * std::mem::size_of::<[f32; 2]>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Stream uniform buffer"),
+            size: std::mem::size_of::<StreamUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let stream_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Stream bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Stream bind group"),
+            layout: &stream_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sample_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[transform_layout, &stream_layout],
+                push_constant_ranges: &[],
+            });
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("shader.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("shader.frag.spv"));
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &vs_module,
+                entry_point: "main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fs_module,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format: sc_desc.format,
+                    write_mask: wgpu::ColorWrite::ALL,
+                    blend: Some(BlendState {
+                        color: BlendComponent::OVER,
+                        alpha: BlendComponent::REPLACE,
+                    }),
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                clamp_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        });
+
+        // The first update uploads the whole window, since the buffer starts out empty.
+        let uploaded = window.appended - window.samples.len() as u64;
+
+        Box::new(StreamLayerDrawable {
+            render_pipeline,
+            sample_buffer,
+            uniform_buffer,
+            bind_group,
+            window: self.window.clone(),
+            origin,
+            color: self.color,
+            width: self.width,
+            uploaded: Cell::new(uploaded),
+            num_samples: Cell::new(0),
+        })
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, pixel_size: f32, _ids: &mut SvgIds) -> fmt::Result {
+        let window = self.window.lock().unwrap();
+        if window.samples.len() < 2 {
+            return Ok(());
+        }
+
+        write!(out, r#"<polyline points=""#)?;
+        for [x, y] in &window.samples {
+            write!(out, "{},{} ", x, y)?;
+        }
+        let (stroke, opacity) = svg_paint(self.color);
+        writeln!(
+            out,
+            r#"" fill="none" stroke="{}" stroke-opacity="{}" stroke-width="{}" stroke-linejoin="round"/>"#,
+            stroke,
+            opacity,
+            self.width * pixel_size
+        )
+    }
+}
@@ -0,0 +1,18 @@
+#version 450
+
+layout(location=0) in float v_offset;
+
+layout(location=0) out vec4 f_color;
+
+layout(set=1, binding=1)
+uniform StreamUniforms {
+    vec4 u_color;
+    uint u_start;
+    uint u_capacity;
+    float u_width;
+};
+
+void main() {
+    float alpha = clamp(u_width / 2. + 0.5 - abs(v_offset), 0., 1.) * u_color.a;
+    f_color = vec4(u_color.rgb * alpha, alpha);
+}
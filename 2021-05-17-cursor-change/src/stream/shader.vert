@@ -0,0 +1,67 @@
+#version 450
+
+layout(location=0) out float v_offset;
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_transform;
+    vec2 u_viewport_size;
+};
+
+// The ring buffer of samples, relative to the upload origin.
+layout(set=1, binding=0)
+readonly buffer Samples {
+    vec2 samples[];
+};
+
+layout(set=1, binding=1)
+uniform StreamUniforms {
+    vec4 u_color;
+    // Slot of the oldest sample in the window.
+    uint u_start;
+    uint u_capacity;
+    float u_width;
+};
+
+void main() {
+    // Every instance is the segment from one sample of the window to the next.
+    uint i = u_start + gl_InstanceIndex;
+    vec2 a = samples[i % u_capacity];
+    vec2 b = samples[(i + 1) % u_capacity];
+
+    // End points in pixels from the center of the viewport.
+    vec2 half_viewport = u_viewport_size / 2.;
+    vec2 pa = (u_transform * vec4(a, 0., 1.)).xy * half_viewport;
+    vec2 pb = (u_transform * vec4(b, 0., 1.)).xy * half_viewport;
+
+    float len = length(pb - pa);
+    vec2 dir = len > 0. ? (pb - pa) / len : vec2(1., 0.);
+    vec2 perp = vec2(-dir.y, dir.x);
+    // Half a width past the ends closes the gaps at the joins, and a pixel of margin
+    // across the line leaves room for antialiasing.
+    vec2 along = dir * u_width / 2.;
+    float extent = u_width / 2. + 1.;
+
+    vec2 position;
+    switch (gl_VertexIndex) {
+        case 0:
+            position = pa - along - perp * extent;
+            v_offset = -extent;
+            break;
+        case 1:
+        case 3:
+            position = pa - along + perp * extent;
+            v_offset = extent;
+            break;
+        case 2:
+        case 4:
+            position = pb + along - perp * extent;
+            v_offset = -extent;
+            break;
+        case 5:
+            position = pb + along + perp * extent;
+            v_offset = extent;
+    }
+
+    gl_Position = vec4(position / half_viewport, 0., 1.);
+}
@@ -0,0 +1,101 @@
+use std::fmt::{self, Write};
+use std::io;
+use std::path::Path;
+
+use crate::layer::Layer;
+use crate::zoom::ZoomState;
+
+// The shaders work in linear color and the swap chain converts to sRGB on write,
+// so colors are converted the same way to make the SVG match the screen.
+pub fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.max(0.).min(1.);
+    let srgb = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    };
+    (srgb * 255.).round() as u8
+}
+
+/// Formats an RGBA color as an SVG paint (`#rrggbb`) and an opacity.
+pub fn svg_paint(color: [f32; 4]) -> (String, f32) {
+    (
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            linear_to_srgb(color[0]),
+            linear_to_srgb(color[1]),
+            linear_to_srgb(color[2])
+        ),
+        color[3],
+    )
+}
+
+/// Hands out element IDs that are unique within one exported document, such as the IDs of
+/// gradient definitions. IDs only depend on the order of the layers, so exporting the same
+/// scene twice gives the same document.
+#[derive(Default)]
+pub struct SvgIds {
+    next: usize,
+}
+
+impl SvgIds {
+    /// A new ID starting with `prefix`.
+    pub fn unique(&mut self, prefix: &str) -> String {
+        self.next += 1;
+        format!("{}-{}", prefix, self.next)
+    }
+}
+
+/// Writes the stroke attributes of a shape, if it has an outline.
+pub fn write_stroke(out: &mut dyn Write, color: [f32; 4], width: f32) -> fmt::Result {
+    if width <= 0. {
+        return Ok(());
+    }
+
+    let (stroke, opacity) = svg_paint(color);
+    write!(
+        out,
+        r#" stroke="{}" stroke-opacity="{}" stroke-width="{}""#,
+        stroke, opacity, width
+    )
+}
+
+/// Writes the layers as an SVG document showing the same region of the scene as the window.
+pub fn write_svg(
+    layers: &[Box<dyn Layer>],
+    zoom_state: &ZoomState,
+    out: &mut dyn Write,
+) -> fmt::Result {
+    let size = zoom_state.size();
+    let [x, y, width, height] = zoom_state.view_box();
+
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+        size.width,
+        size.height,
+        x,
+        -(y + height),
+        width,
+        height
+    )?;
+
+    // Scene coordinates have Y going up, SVG has Y going down.
+    writeln!(out, r#"<g transform="scale(1 -1)">"#)?;
+    let mut ids = SvgIds::default();
+    for layer in layers {
+        layer.write_svg(out, zoom_state.pixel_size(), &mut ids)?;
+    }
+    writeln!(out, "</g>")?;
+    writeln!(out, "</svg>")
+}
+
+pub fn export_svg(
+    path: &Path,
+    layers: &[Box<dyn Layer>],
+    zoom_state: &ZoomState,
+) -> io::Result<()> {
+    let mut svg = String::new();
+    write_svg(layers, zoom_state, &mut svg).expect("Writing to a String cannot fail");
+    std::fs::write(path, svg)
+}
@@ -0,0 +1,954 @@
+use std::error::Error;
+use std::path::Path;
+
+use roxmltree::{Document, Node};
+
+use crate::circle::{Circle, CirclesLayer};
+use crate::layer::Layer;
+use crate::line::{Line, LinesLayer};
+use crate::rectangle::{Rectangle, RectanglesLayer};
+
+// SVG transform matrix `[a, b, c, d, e, f]`, mapping (x, y) to (ax + cy + e, bx + dy + f).
+type Affine = [f32; 6];
+
+const IDENTITY: Affine = [1., 0., 0., 1., 0., 0.];
+
+/// Layers loaded from an SVG document, along with warnings about anything that
+/// could not be represented.
+pub struct ImportedSvg {
+    pub layers: Vec<Box<dyn Layer>>,
+    pub warnings: Vec<String>,
+}
+
+pub fn load_svg(path: &Path) -> Result<ImportedSvg, Box<dyn Error>> {
+    let source = std::fs::read_to_string(path)?;
+    Ok(import_svg(&source)?)
+}
+
+/// Converts an SVG document into shape layers. SVG user space (Y down) is mapped onto
+/// scene coordinates (Y up) by flipping the Y axis, so that the drawing appears upright.
+pub fn import_svg(source: &str) -> Result<ImportedSvg, roxmltree::Error> {
+    let document = Document::parse(source)?;
+
+    let mut importer = Importer {
+        layers: Vec::new(),
+        shapes: Shapes::None,
+        warnings: Vec::new(),
+    };
+    let root = document.root_element();
+    let style = importer.style(root, &Style::default());
+    importer.visit_children(root, IDENTITY, &style);
+    importer.flush();
+
+    Ok(ImportedSvg {
+        layers: importer.layers,
+        warnings: importer.warnings,
+    })
+}
+
+fn multiply(m: &Affine, n: &Affine) -> Affine {
+    [
+        m[0] * n[0] + m[2] * n[1],
+        m[1] * n[0] + m[3] * n[1],
+        m[0] * n[2] + m[2] * n[3],
+        m[1] * n[2] + m[3] * n[3],
+        m[0] * n[4] + m[2] * n[5] + m[4],
+        m[1] * n[4] + m[3] * n[5] + m[5],
+    ]
+}
+
+// Applies the transform, then flips into scene coordinates.
+fn to_scene(m: &Affine, [x, y]: [f32; 2]) -> [f32; 2] {
+    [m[0] * x + m[2] * y + m[4], -(m[1] * x + m[3] * y + m[5])]
+}
+
+fn to_f64([x, y]: [f32; 2]) -> [f64; 2] {
+    [x as f64, y as f64]
+}
+
+// How much the transform scales lengths, on average.
+fn length_scale(m: &Affine) -> f32 {
+    (m[0] * m[3] - m[1] * m[2]).abs().sqrt()
+}
+
+fn is_axis_aligned(m: &Affine) -> bool {
+    m[1] == 0. && m[2] == 0.
+}
+
+fn is_uniform(m: &Affine) -> bool {
+    let x_scale = (m[0] * m[0] + m[1] * m[1]).sqrt();
+    let y_scale = (m[2] * m[2] + m[3] * m[3]).sqrt();
+    (x_scale - y_scale).abs() <= 1e-4 * x_scale.max(y_scale)
+}
+
+fn parse_numbers(value: &str) -> Vec<f32> {
+    Tokens::new(value)
+        .filter_map(|token| match token {
+            Token::Number(n) => Some(n),
+            Token::Command(_) => None,
+        })
+        .collect()
+}
+
+fn parse_length(value: &str) -> Option<f32> {
+    value.trim().trim_end_matches("px").trim().parse().ok()
+}
+
+fn parse_transform(value: &str, warnings: &mut Vec<String>) -> Affine {
+    let mut result = IDENTITY;
+
+    for part in value.split(')') {
+        let part = part.trim().trim_start_matches(',').trim();
+        if part.is_empty() {
+            continue;
+        }
+
+        let (name, args) = match part.find('(') {
+            Some(index) => (part[..index].trim(), parse_numbers(&part[index + 1..])),
+            None => {
+                warnings.push(format!("Ignoring malformed transform `{}`", value));
+                return result;
+            }
+        };
+
+        let transform = match (name, args.as_slice()) {
+            ("matrix", &[a, b, c, d, e, f]) => [a, b, c, d, e, f],
+            ("translate", &[x]) => [1., 0., 0., 1., x, 0.],
+            ("translate", &[x, y]) => [1., 0., 0., 1., x, y],
+            ("scale", &[s]) => [s, 0., 0., s, 0., 0.],
+            ("scale", &[x, y]) => [x, 0., 0., y, 0., 0.],
+            ("rotate", &[angle]) => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                [cos, sin, -sin, cos, 0., 0.]
+            }
+            ("rotate", &[angle, cx, cy]) => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let rotate = [cos, sin, -sin, cos, 0., 0.];
+                multiply(
+                    &multiply(&[1., 0., 0., 1., cx, cy], &rotate),
+                    &[1., 0., 0., 1., -cx, -cy],
+                )
+            }
+            ("skewX", &[angle]) => [1., 0., angle.to_radians().tan(), 1., 0., 0.],
+            ("skewY", &[angle]) => [1., angle.to_radians().tan(), 0., 1., 0., 0.],
+            _ => {
+                warnings.push(format!("Ignoring unsupported transform `{})`", part));
+                continue;
+            }
+        };
+
+        result = multiply(&result, &transform);
+    }
+
+    result
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn named_color(name: &str) -> Option<[u8; 3]> {
+    Some(match name {
+        "black" => [0, 0, 0],
+        "white" => [255, 255, 255],
+        "red" => [255, 0, 0],
+        "green" => [0, 128, 0],
+        "lime" => [0, 255, 0],
+        "blue" => [0, 0, 255],
+        "yellow" => [255, 255, 0],
+        "cyan" | "aqua" => [0, 255, 255],
+        "magenta" | "fuchsia" => [255, 0, 255],
+        "gray" | "grey" => [128, 128, 128],
+        "silver" => [192, 192, 192],
+        "maroon" => [128, 0, 0],
+        "olive" => [128, 128, 0],
+        "navy" => [0, 0, 128],
+        "purple" => [128, 0, 128],
+        "teal" => [0, 128, 128],
+        "orange" => [255, 165, 0],
+        _ => return None,
+    })
+}
+
+// Returns `Some(None)` for `none`, and `None` if the paint could not be parsed.
+fn parse_paint(value: &str) -> Option<Option<[f32; 3]>> {
+    let value = value.trim();
+
+    let rgb = if value == "none" {
+        return Some(None);
+    } else if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()?;
+        match digits.as_slice() {
+            &[r, g, b] => [r * 17, g * 17, b * 17],
+            &[r1, r2, g1, g2, b1, b2] => [r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2],
+            _ => return None,
+        }
+    } else if let Some(args) = value
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let channels: Vec<u8> = args
+            .split(',')
+            .map(|channel| {
+                let channel = channel.trim();
+                match channel.strip_suffix('%') {
+                    Some(percent) => percent
+                        .trim()
+                        .parse::<f32>()
+                        .ok()
+                        .map(|p| (p.max(0.).min(100.) * 2.55).round() as u8),
+                    None => channel
+                        .parse::<f32>()
+                        .ok()
+                        .map(|c| c.max(0.).min(255.) as u8),
+                }
+            })
+            .collect::<Option<_>>()?;
+        match channels.as_slice() {
+            &[r, g, b] => [r, g, b],
+            _ => return None,
+        }
+    } else {
+        named_color(value)?
+    };
+
+    Some(Some([
+        srgb_to_linear(rgb[0]),
+        srgb_to_linear(rgb[1]),
+        srgb_to_linear(rgb[2]),
+    ]))
+}
+
+// Presentation properties, inherited from parent groups.
+#[derive(Clone)]
+struct Style {
+    fill: Option<[f32; 3]>,
+    stroke: Option<[f32; 3]>,
+    stroke_width: f32,
+    opacity: f32,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    dash_array: [f32; 4],
+    dash_offset: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fill: Some([0., 0., 0.]),
+            stroke: None,
+            stroke_width: 1.,
+            opacity: 1.,
+            fill_opacity: 1.,
+            stroke_opacity: 1.,
+            dash_array: [0.; 4],
+            dash_offset: 0.,
+        }
+    }
+}
+
+impl Style {
+    fn fill_color(&self) -> Option<[f32; 4]> {
+        let [r, g, b] = self.fill?;
+        Some([r, g, b, self.opacity * self.fill_opacity])
+    }
+
+    fn stroke_color(&self) -> Option<[f32; 4]> {
+        let [r, g, b] = self.stroke?;
+        Some([r, g, b, self.opacity * self.stroke_opacity])
+    }
+}
+
+// Looks a property up in the `style` attribute first, then in the presentation attribute.
+fn property<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute("style")
+        .and_then(|style| {
+            style.split(';').find_map(|declaration| {
+                let (key, value) = declaration.split_at(declaration.find(':')?);
+                if key.trim() == name {
+                    Some(value[1..].trim())
+                } else {
+                    None
+                }
+            })
+        })
+        .or_else(|| node.attribute(name))
+}
+
+enum Token {
+    Command(char),
+    Number(f32),
+}
+
+// Splits path data and number lists into commands and numbers. Numbers may be
+// separated by whitespace, commas, a sign, or a second decimal point.
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(source: &'a str) -> Self {
+        Tokens { rest: source }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        loop {
+            self.rest = self
+                .rest
+                .trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+            let first = self.rest.chars().next()?;
+
+            if first.is_ascii_alphabetic() && first != 'e' && first != 'E' {
+                self.rest = &self.rest[1..];
+                return Some(Token::Command(first));
+            }
+
+            match number_length(self.rest.as_bytes()) {
+                Some(end) => {
+                    let number = self.rest[..end].parse().expect("Validated number");
+                    self.rest = &self.rest[end..];
+                    return Some(Token::Number(number));
+                }
+                // Skip a character we don't understand.
+                None => self.rest = &self.rest[first.len_utf8()..],
+            }
+        }
+    }
+}
+
+// Length of the number at the start of `bytes`, if there is one. An exponent is only
+// part of the number if it has digits, so `1e` is read as `1` followed by a stray `e`.
+fn number_length(bytes: &[u8]) -> Option<usize> {
+    let digits = |start: usize| {
+        bytes[start.min(bytes.len())..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let sign = |start: usize| match bytes.get(start) {
+        Some(b'+') | Some(b'-') => 1,
+        _ => 0,
+    };
+
+    let mut end = sign(0);
+    let integer = digits(end);
+    end += integer;
+    let mut fraction = 0;
+    if bytes.get(end) == Some(&b'.') {
+        fraction = digits(end + 1);
+        end += 1 + fraction;
+    }
+    if integer == 0 && fraction == 0 {
+        return None;
+    }
+
+    if let Some(b'e') | Some(b'E') = bytes.get(end) {
+        let exponent_sign = sign(end + 1);
+        let exponent = digits(end + 1 + exponent_sign);
+        if exponent > 0 {
+            end += 1 + exponent_sign + exponent;
+        }
+    }
+
+    Some(end)
+}
+
+// Straight-line subpaths of a path, as points in user space.
+struct Subpath {
+    points: Vec<[f32; 2]>,
+    closed: bool,
+}
+
+fn parse_path(data: &str, warnings: &mut Vec<String>) -> Vec<Subpath> {
+    let mut subpaths: Vec<Subpath> = Vec::new();
+    let mut numbers: Vec<f32> = Vec::new();
+    let mut command = None;
+    let mut current = [0f32, 0.];
+    let mut start = [0f32, 0.];
+    let mut warned_curves = false;
+
+    let mut tokens = Tokens::new(data);
+    loop {
+        match tokens.next() {
+            Some(Token::Command(c)) => {
+                command = Some(c);
+                numbers.clear();
+                if c == 'Z' || c == 'z' {
+                    if let Some(subpath) = subpaths.last_mut() {
+                        subpath.closed = true;
+                    }
+                    current = start;
+                }
+                continue;
+            }
+            Some(Token::Number(n)) => numbers.push(n),
+            None => break,
+        }
+
+        let c = match command {
+            Some(c) => c,
+            None => {
+                warnings.push("Path data does not start with a command".to_string());
+                break;
+            }
+        };
+
+        let arity = match c.to_ascii_uppercase() {
+            'M' | 'L' | 'T' => 2,
+            'H' | 'V' => 1,
+            'S' | 'Q' => 4,
+            'C' => 6,
+            'A' => 7,
+            _ => {
+                warnings.push(format!("Unknown path command `{}`", c));
+                break;
+            }
+        };
+        if numbers.len() < arity {
+            continue;
+        }
+
+        let relative = c.is_ascii_lowercase();
+        let offset = if relative { current } else { [0., 0.] };
+        let point = match c.to_ascii_uppercase() {
+            'H' => [numbers[0] + offset[0], current[1]],
+            'V' => [current[0], numbers[0] + offset[1]],
+            _ => [
+                numbers[arity - 2] + offset[0],
+                numbers[arity - 1] + offset[1],
+            ],
+        };
+        numbers.clear();
+
+        match c.to_ascii_uppercase() {
+            'M' => {
+                subpaths.push(Subpath {
+                    points: vec![point],
+                    closed: false,
+                });
+                start = point;
+                // Further coordinate pairs after a move are implicit line-tos.
+                command = Some(if relative { 'l' } else { 'L' });
+            }
+            'L' | 'H' | 'V' => {}
+            _ => {
+                if !warned_curves {
+                    warnings.push(
+                        "Curved path segments are not supported and were replaced by straight lines"
+                            .to_string(),
+                    );
+                    warned_curves = true;
+                }
+            }
+        }
+
+        if c.to_ascii_uppercase() != 'M' {
+            match subpaths.last_mut() {
+                Some(subpath) if !subpath.closed => subpath.points.push(point),
+                // Drawing after a close starts a new subpath at the close point.
+                _ => subpaths.push(Subpath {
+                    points: vec![current, point],
+                    closed: false,
+                }),
+            }
+        }
+
+        current = point;
+    }
+
+    subpaths
+}
+
+enum Shapes {
+    None,
+    Circles(Vec<Circle>),
+    Rectangles(Vec<Rectangle>),
+    Lines(Vec<Line>),
+}
+
+struct Importer {
+    layers: Vec<Box<dyn Layer>>,
+    shapes: Shapes,
+    warnings: Vec<String>,
+}
+
+impl Importer {
+    // Shapes of the same kind are batched into one layer until a different kind
+    // is encountered, so that the SVG painting order is preserved.
+    fn flush(&mut self) {
+        match std::mem::replace(&mut self.shapes, Shapes::None) {
+            Shapes::None => {}
+            Shapes::Circles(data) => self.layers.push(Box::new(CirclesLayer::new(data))),
+            Shapes::Rectangles(data) => self.layers.push(Box::new(RectanglesLayer::new(data))),
+            Shapes::Lines(data) => self.layers.push(Box::new(LinesLayer::new(data))),
+        }
+    }
+
+    fn push_circle(&mut self, circle: Circle) {
+        if !matches!(self.shapes, Shapes::Circles(_)) {
+            self.flush();
+            self.shapes = Shapes::Circles(Vec::new());
+        }
+        if let Shapes::Circles(data) = &mut self.shapes {
+            data.push(circle);
+        }
+    }
+
+    fn push_rectangle(&mut self, rectangle: Rectangle) {
+        if !matches!(self.shapes, Shapes::Rectangles(_)) {
+            self.flush();
+            self.shapes = Shapes::Rectangles(Vec::new());
+        }
+        if let Shapes::Rectangles(data) = &mut self.shapes {
+            data.push(rectangle);
+        }
+    }
+
+    fn push_line(&mut self, line: Line) {
+        if !matches!(self.shapes, Shapes::Lines(_)) {
+            self.flush();
+            self.shapes = Shapes::Lines(Vec::new());
+        }
+        if let Shapes::Lines(data) = &mut self.shapes {
+            data.push(line);
+        }
+    }
+
+    fn warn(&mut self, message: String) {
+        self.warnings.push(message);
+    }
+
+    fn length(&mut self, node: Node, name: &str) -> f32 {
+        match node.attribute(name) {
+            None => 0.,
+            Some(value) => parse_length(value).unwrap_or_else(|| {
+                self.warn(format!(
+                    "Unsupported length `{}` for `{}` on <{}>",
+                    value,
+                    name,
+                    node.tag_name().name()
+                ));
+                0.
+            }),
+        }
+    }
+
+    fn paint(&mut self, node: Node, name: &str, inherited: Option<[f32; 3]>) -> Option<[f32; 3]> {
+        match property(node, name) {
+            None => inherited,
+            Some(value) => parse_paint(value).unwrap_or_else(|| {
+                self.warn(format!("Unsupported {} `{}`", name, value));
+                inherited
+            }),
+        }
+    }
+
+    fn number(&mut self, node: Node, name: &str, inherited: f32) -> f32 {
+        match property(node, name) {
+            None => inherited,
+            Some(value) => parse_length(value).unwrap_or_else(|| {
+                self.warn(format!("Unsupported {} `{}`", name, value));
+                inherited
+            }),
+        }
+    }
+
+    fn dash_array(&mut self, node: Node, inherited: [f32; 4]) -> [f32; 4] {
+        let value = match property(node, "stroke-dasharray") {
+            None => return inherited,
+            Some(value) if value.trim() == "none" => return [0.; 4],
+            Some(value) => value,
+        };
+
+        let mut dashes = parse_numbers(value);
+        // An odd number of values is repeated to give dash and gap pairs.
+        if dashes.len() % 2 == 1 {
+            dashes.extend(dashes.clone());
+        }
+        if dashes.len() > 4 {
+            self.warn(format!(
+                "Dash array `{}` has more than four values and was truncated",
+                value
+            ));
+        }
+
+        let mut dash_array = [0.; 4];
+        for (target, dash) in dash_array.iter_mut().zip(dashes) {
+            *target = dash;
+        }
+        dash_array
+    }
+
+    fn style(&mut self, node: Node, parent: &Style) -> Style {
+        Style {
+            fill: self.paint(node, "fill", parent.fill),
+            stroke: self.paint(node, "stroke", parent.stroke),
+            stroke_width: self.number(node, "stroke-width", parent.stroke_width),
+            // Group opacity is approximated by applying it to each shape.
+            opacity: parent.opacity * self.number(node, "opacity", 1.),
+            fill_opacity: self.number(node, "fill-opacity", parent.fill_opacity),
+            stroke_opacity: self.number(node, "stroke-opacity", parent.stroke_opacity),
+            dash_array: self.dash_array(node, parent.dash_array),
+            dash_offset: self.number(node, "stroke-dashoffset", parent.dash_offset),
+        }
+    }
+
+    fn visit_children(&mut self, node: Node, transform: Affine, style: &Style) {
+        for child in node.children().filter(Node::is_element) {
+            let transform = match child.attribute("transform") {
+                Some(value) => multiply(&transform, &parse_transform(value, &mut self.warnings)),
+                None => transform,
+            };
+            let style = self.style(child, style);
+
+            match child.tag_name().name() {
+                "g" | "svg" | "a" => self.visit_children(child, transform, &style),
+                "rect" => self.import_rect(child, &transform, &style),
+                "circle" => self.import_circle(child, &transform, &style),
+                "line" => {
+                    let points = [
+                        [self.length(child, "x1"), self.length(child, "y1")],
+                        [self.length(child, "x2"), self.length(child, "y2")],
+                    ];
+                    self.import_stroke(&points, false, &transform, &style);
+                }
+                "polyline" | "polygon" => {
+                    let closed = child.tag_name().name() == "polygon";
+                    let numbers = parse_numbers(child.attribute("points").unwrap_or(""));
+                    let points: Vec<[f32; 2]> =
+                        numbers.chunks_exact(2).map(|p| [p[0], p[1]]).collect();
+                    if closed && style.fill.is_some() {
+                        self.warn(
+                            "Polygon fills are not supported; only the outline is drawn"
+                                .to_string(),
+                        );
+                    }
+                    self.import_stroke(&points, closed, &transform, &style);
+                }
+                "path" => {
+                    let subpaths =
+                        parse_path(child.attribute("d").unwrap_or(""), &mut self.warnings);
+                    if style.fill.is_some() && subpaths.iter().any(|s| s.closed) {
+                        self.warn(
+                            "Path fills are not supported; only the outline is drawn".to_string(),
+                        );
+                    }
+                    for subpath in subpaths {
+                        self.import_stroke(&subpath.points, subpath.closed, &transform, &style);
+                    }
+                }
+                // Elements that never render anything by themselves.
+                "title" | "desc" | "metadata" | "defs" => {}
+                name => self.warn(format!("Unsupported element <{}> was skipped", name)),
+            }
+        }
+    }
+
+    fn import_rect(&mut self, node: Node, transform: &Affine, style: &Style) {
+        let x = self.length(node, "x");
+        let y = self.length(node, "y");
+        let width = self.length(node, "width");
+        let height = self.length(node, "height");
+
+        // A missing rx or ry defaults to the other one.
+        let rx = node.attribute("rx").map(|_| self.length(node, "rx"));
+        let ry = node.attribute("ry").map(|_| self.length(node, "ry"));
+        let corner_radius = match (rx, ry) {
+            (Some(rx), Some(ry)) => {
+                if rx != ry {
+                    self.warn("Elliptical rectangle corners were drawn as circular".to_string());
+                }
+                rx.min(ry)
+            }
+            (Some(r), None) | (None, Some(r)) => r,
+            (None, None) => 0.,
+        };
+
+        let corners = [
+            [x, y],
+            [x + width, y],
+            [x + width, y + height],
+            [x, y + height],
+        ];
+
+        let fill = style.fill_color();
+        // Rectangles are axis-aligned, so a rotated outline is drawn with lines instead, over
+        // the fill like SVG paints strokes.
+        let aligned = is_axis_aligned(transform);
+        let stroke = if aligned { style.stroke_color() } else { None };
+
+        if fill.is_some() || stroke.is_some() {
+            if fill.is_some() && !aligned {
+                self.warn("Rotated or skewed <rect> was replaced by its bounding box".to_string());
+            }
+
+            let scene: Vec<[f32; 2]> = corners.iter().map(|&c| to_scene(transform, c)).collect();
+            let min_x = scene.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min);
+            let max_x = scene.iter().map(|p| p[0]).fold(f32::NEG_INFINITY, f32::max);
+            let min_y = scene.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min);
+            let max_y = scene.iter().map(|p| p[1]).fold(f32::NEG_INFINITY, f32::max);
+
+            self.push_rectangle(Rectangle {
+                upper_left: to_f64([min_x, max_y]),
+                bottom_right: to_f64([max_x, min_y]),
+                color: fill.unwrap_or([0., 0., 0., 0.]),
+                stroke_color: stroke.unwrap_or([0., 0., 0., 0.]),
+                stroke_width: if stroke.is_some() {
+                    style.stroke_width * length_scale(transform)
+                } else {
+                    0.
+                },
+                corner_radius: corner_radius * length_scale(transform),
+                flags: 0,
+            });
+        }
+        if !aligned {
+            self.import_stroke(&corners, true, transform, style);
+        }
+    }
+
+    fn import_circle(&mut self, node: Node, transform: &Affine, style: &Style) {
+        let center = [self.length(node, "cx"), self.length(node, "cy")];
+        let radius = self.length(node, "r");
+
+        let fill = style.fill_color();
+        let stroke = style.stroke_color();
+        if fill.is_none() && stroke.is_none() {
+            return;
+        }
+
+        if !is_uniform(transform) {
+            self.warn("Non-uniformly scaled <circle> was drawn as a circle".to_string());
+        }
+
+        let scale = length_scale(transform);
+        self.push_circle(Circle {
+            position: to_f64(to_scene(transform, center)),
+            color: fill.unwrap_or([0., 0., 0., 0.]),
+            radius: radius * scale,
+            stroke_color: stroke.unwrap_or([0., 0., 0., 0.]),
+            stroke_width: if stroke.is_some() {
+                style.stroke_width * scale
+            } else {
+                0.
+            },
+            flags: 0,
+        });
+    }
+
+    fn import_stroke(
+        &mut self,
+        points: &[[f32; 2]],
+        closed: bool,
+        transform: &Affine,
+        style: &Style,
+    ) {
+        let color = match style.stroke_color() {
+            Some(color) => color,
+            None => return,
+        };
+        let scale = length_scale(transform);
+        // The line shader extends the line by `width` on either side.
+        let width = style.stroke_width * scale / 2.;
+        let mut dash_array = style.dash_array;
+        for dash in dash_array.iter_mut() {
+            *dash *= scale;
+        }
+        let mut dash_offset = style.dash_offset * scale;
+
+        let closing = if closed && points.len() > 2 {
+            Some((points[points.len() - 1], points[0]))
+        } else {
+            None
+        };
+
+        for (start, end) in points
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .chain(closing)
+        {
+            let start = to_scene(transform, start);
+            let end = to_scene(transform, end);
+            self.push_line(Line {
+                start: to_f64(start),
+                end: to_f64(end),
+                color,
+                width,
+                dash_array,
+                dash_offset,
+                flags: 0,
+            });
+            // Continue the dash pattern across segments.
+            dash_offset += ((end[0] - start[0]).powi(2) + (end[1] - start[1]).powi(2)).sqrt();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(data: &str) -> (Vec<(Vec<[f32; 2]>, bool)>, Vec<String>) {
+        let mut warnings = Vec::new();
+        let subpaths = parse_path(data, &mut warnings)
+            .into_iter()
+            .map(|subpath| (subpath.points, subpath.closed))
+            .collect();
+        (subpaths, warnings)
+    }
+
+    fn transform(value: &str) -> (Affine, Vec<String>) {
+        let mut warnings = Vec::new();
+        (parse_transform(value, &mut warnings), warnings)
+    }
+
+    fn assert_affine_eq(actual: Affine, expected: Affine) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_numbers("1 2,3 , 4"), vec![1., 2., 3., 4.]);
+        assert_eq!(parse_numbers("10-5+2"), vec![10., -5., 2.]);
+        assert_eq!(parse_numbers("-.5.5"), vec![-0.5, 0.5]);
+        assert_eq!(parse_numbers("1e-3"), vec![0.001]);
+        assert_eq!(parse_numbers("1e+2 3E1"), vec![100., 30.]);
+        assert_eq!(parse_numbers("5."), vec![5.]);
+    }
+
+    #[test]
+    fn exponent_without_digits() {
+        assert_eq!(parse_numbers("1e"), vec![1.]);
+        assert_eq!(parse_numbers("1e-"), vec![1.]);
+        assert_eq!(parse_numbers("1e 2"), vec![1., 2.]);
+    }
+
+    #[test]
+    fn unrecognised_characters() {
+        assert_eq!(parse_numbers(". - # 3"), vec![3.]);
+
+        // Long runs of junk used to recurse once per character.
+        let junk = format!("1{}2", "#".repeat(1_000_000));
+        assert_eq!(parse_numbers(&junk), vec![1., 2.]);
+    }
+
+    #[test]
+    fn absolute_path() {
+        let (subpaths, warnings) = path("M 10 20 L 30 40 H 50 V 60 Z");
+        assert_eq!(
+            subpaths,
+            vec![(vec![[10., 20.], [30., 40.], [50., 40.], [50., 60.]], true)]
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn relative_path() {
+        let (subpaths, _) = path("m 10 20 l 5 5 h 5 v -10 z");
+        assert_eq!(
+            subpaths,
+            vec![(vec![[10., 20.], [15., 25.], [20., 25.], [20., 15.]], true)]
+        );
+    }
+
+    #[test]
+    fn implicit_repeats() {
+        // Extra pairs after a move are line-tos, in the same coordinate mode.
+        let (subpaths, _) = path("M0,0 10,0 10,10");
+        assert_eq!(
+            subpaths,
+            vec![(vec![[0., 0.], [10., 0.], [10., 10.]], false)]
+        );
+
+        let (subpaths, _) = path("m 10 10 20 0 0 20");
+        assert_eq!(
+            subpaths,
+            vec![(vec![[10., 10.], [30., 10.], [30., 30.]], false)]
+        );
+
+        let (subpaths, _) = path("M 0 0 h 1 2 3");
+        assert_eq!(
+            subpaths,
+            vec![(vec![[0., 0.], [1., 0.], [3., 0.], [6., 0.]], false)]
+        );
+    }
+
+    #[test]
+    fn arcs() {
+        let (subpaths, warnings) = path("M 0 0 A 5 5 0 0 1 10 0 a 5 5 0 0 1 10 0 5 5 0 1 0 10 0");
+        assert_eq!(
+            subpaths,
+            vec![(vec![[0., 0.], [10., 0.], [20., 0.], [30., 0.]], false)]
+        );
+        // Curves are flattened to their end points, with a single warning.
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn drawing_after_close() {
+        let (subpaths, _) = path("M 0 0 L 10 0 L 10 10 Z l 0 -20");
+        assert_eq!(
+            subpaths,
+            vec![
+                (vec![[0., 0.], [10., 0.], [10., 10.]], true),
+                (vec![[0., 0.], [0., -20.]], false),
+            ]
+        );
+    }
+
+    #[test]
+    fn path_without_command() {
+        let (subpaths, warnings) = path("10 10");
+        assert!(subpaths.is_empty());
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn transforms() {
+        assert_affine_eq(transform("translate(10)").0, [1., 0., 0., 1., 10., 0.]);
+        assert_affine_eq(transform("scale(2, 3)").0, [2., 0., 0., 3., 0., 0.]);
+        assert_affine_eq(transform("rotate(90)").0, [0., 1., -1., 0., 0., 0.]);
+        assert_affine_eq(transform("matrix(1 2 3 4 5 6)").0, [1., 2., 3., 4., 5., 6.]);
+        assert_affine_eq(transform("skewX(45)").0, [1., 0., 1., 1., 0., 0.]);
+    }
+
+    #[test]
+    fn composed_transforms() {
+        // Transforms apply right to left: scale first, then translate.
+        assert_affine_eq(
+            transform("translate(10, 20) scale(2)").0,
+            [2., 0., 0., 2., 10., 20.],
+        );
+
+        // Rotating about (10, 10) keeps that point fixed.
+        let (m, _) = transform("rotate(90 10 10)");
+        let round = |[x, y]: [f32; 2]| [x.round(), y.round()];
+        assert_eq!(round(to_scene(&m, [10., 10.])), [10., -10.]);
+        assert_eq!(round(to_scene(&m, [20., 10.])), [10., -20.]);
+    }
+
+    #[test]
+    fn unsupported_transforms() {
+        let (m, warnings) = transform("translate(5) frobnicate(1)");
+        assert_affine_eq(m, [1., 0., 0., 1., 5., 0.]);
+        assert_eq!(warnings.len(), 1);
+
+        let (m, warnings) = transform("translate 5");
+        assert_affine_eq(m, IDENTITY);
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -0,0 +1,5 @@
+pub use export::{export_svg, linear_to_srgb, svg_paint, write_stroke, SvgIds};
+pub use import::load_svg;
+
+mod export;
+mod import;
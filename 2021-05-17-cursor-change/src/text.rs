@@ -0,0 +1,85 @@
+use futures::executor::LocalPool;
+use futures::task::SpawnExt;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureFormat, TextureView};
+use wgpu_glyph::{ab_glyph, GlyphBrush, GlyphBrushBuilder, Section, Text};
+use winit::dpi::PhysicalSize;
+
+// Offsets of the copies of halo text drawn behind the text itself, in pixels.
+const HALO_OFFSETS: [(f32, f32); 4] = [(-1., -1.), (1., -1.), (-1., 1.), (1., 1.)];
+
+/// Screen-space text drawn with `wgpu_glyph`, as in `2021-05-11-gpu-text`. Text is queued
+/// during the frame, then drawn on top of everything else.
+pub struct TextRenderer {
+    glyph_brush: GlyphBrush<()>,
+    staging_belt: StagingBelt,
+    local_pool: LocalPool,
+}
+
+impl TextRenderer {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let font = ab_glyph::FontArc::try_from_slice(include_bytes!("Inconsolata-Regular.ttf"))
+            .expect("Bundled font is valid");
+
+        TextRenderer {
+            glyph_brush: GlyphBrushBuilder::using_font(font).build(device, format),
+            staging_belt: StagingBelt::new(1024),
+            local_pool: LocalPool::new(),
+        }
+    }
+
+    /// Queues `text` with its upper left corner at `position`, in window pixels.
+    pub fn queue(&mut self, position: (f32, f32), text: &str, color: [f32; 4], size: f32) {
+        self.glyph_brush.queue(Section {
+            screen_position: position,
+            text: vec![Text::new(text).with_color(color).with_scale(size)],
+            ..Section::default()
+        });
+    }
+
+    /// Like `queue`, with a halo of `halo_color` around the glyphs, so the text stays
+    /// readable over anything.
+    pub fn queue_with_halo(
+        &mut self,
+        (x, y): (f32, f32),
+        text: &str,
+        color: [f32; 4],
+        halo_color: [f32; 4],
+        size: f32,
+    ) {
+        for &(dx, dy) in &HALO_OFFSETS {
+            self.queue((x + dx, y + dy), text, halo_color, size);
+        }
+        self.queue((x, y), text, color, size);
+    }
+
+    /// Draws the queued text into `view`. Call `recall` once the encoder has been submitted.
+    pub fn draw(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        size: PhysicalSize<u32>,
+    ) {
+        self.glyph_brush
+            .draw_queued(
+                device,
+                &mut self.staging_belt,
+                encoder,
+                view,
+                size.width,
+                size.height,
+            )
+            .expect("Draw queued");
+        self.staging_belt.finish();
+    }
+
+    /// Lets the staging belt reuse the buffers of submitted text.
+    pub fn recall(&mut self) {
+        self.local_pool
+            .spawner()
+            .spawn(self.staging_belt.recall())
+            .expect("Recall staging belt");
+        self.local_pool.run_until_stalled();
+    }
+}
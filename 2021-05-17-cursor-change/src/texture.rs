@@ -0,0 +1,46 @@
+use std::num::NonZeroU32;
+
+use wgpu::{
+    Device, Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, Queue, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsage, TextureView, TextureViewDescriptor,
+};
+
+/// Uploads tightly packed RGBA sRGB pixels into a new texture for sampling.
+pub fn upload_rgba(
+    device: &Device,
+    queue: &Queue,
+    label: &str,
+    texels: &[u8],
+    width: u32,
+    height: u32,
+) -> TextureView {
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba8UnormSrgb,
+        usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+    });
+    queue.write_texture(
+        ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+        },
+        texels,
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: NonZeroU32::new(4 * width),
+            rows_per_image: None,
+        },
+        size,
+    );
+    texture.create_view(&TextureViewDescriptor::default())
+}
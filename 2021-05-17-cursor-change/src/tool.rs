@@ -0,0 +1,127 @@
+use wgpu::Queue;
+use winit::event::ModifiersState;
+
+use crate::edit::ShapeKind;
+use crate::layer::SIZE_IN_PIXELS;
+use crate::line::{Line, LinesLayer, DASH_IN_PIXELS};
+use crate::overlay::Overlay;
+use crate::selection::Region;
+
+const RUBBER_BAND_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.];
+const RUBBER_BAND_WIDTH_IN_PIXELS: f32 = 0.75;
+const RUBBER_BAND_DASHES_IN_PIXELS: [f32; 4] = [6., 4., 0., 0.];
+const MIN_RUBBER_BAND_CAPACITY: usize = 64;
+
+/// What dragging with the left mouse button does.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tool {
+    /// Moves the view, handled by `ZoomState`.
+    Pan,
+    /// Selects the instances inside a rectangle.
+    BoxSelect,
+    /// Selects the instances inside a freehand polygon.
+    Lasso,
+    /// Moves shapes, or reshapes them with the handles of the selection.
+    Edit,
+    /// Drags out new shapes of the given kind.
+    Create(ShapeKind),
+}
+
+impl Tool {
+    /// The tool used for a drag started with `modifiers` held: Shift selects a box and Ctrl
+    /// a lasso, whatever the current tool.
+    pub fn with_modifiers(self, modifiers: ModifiersState) -> Tool {
+        if modifiers.shift() {
+            Tool::BoxSelect
+        } else if modifiers.ctrl() {
+            Tool::Lasso
+        } else {
+            self
+        }
+    }
+}
+
+/// A selection in progress, outlined by a dashed rubber band that keeps its size on screen.
+pub struct SelectionDrag {
+    region: Region,
+    pub overlay: Overlay,
+    // Number of lines in the overlay, so it can be updated in place while the outline fits.
+    capacity: usize,
+}
+
+// The outline of `region`, padded to `capacity` lines with invisible ones.
+fn rubber_band(region: &Region, capacity: usize) -> Box<LinesLayer> {
+    let corners = region.outline();
+    let mut lines: Vec<Line> = corners
+        .iter()
+        .zip(corners.iter().cycle().skip(1))
+        .map(|(&start, &end)| Line {
+            start,
+            end,
+            color: RUBBER_BAND_COLOR,
+            width: RUBBER_BAND_WIDTH_IN_PIXELS,
+            dash_array: RUBBER_BAND_DASHES_IN_PIXELS,
+            dash_offset: 0.,
+            flags: DASH_IN_PIXELS | SIZE_IN_PIXELS,
+        })
+        .collect();
+    // Zero width lines cover no pixels, but still need a direction.
+    lines.resize(
+        capacity,
+        Line {
+            start: [0., 0.],
+            end: [1., 0.],
+            color: [0.; 4],
+            width: 0.,
+            dash_array: [0.; 4],
+            dash_offset: 0.,
+            flags: 0,
+        },
+    );
+    Box::new(LinesLayer::new(lines))
+}
+
+impl SelectionDrag {
+    /// Starts a selection with `tool` at scene position `start`. Panics for tools that
+    /// don't select.
+    pub fn new(tool: Tool, start: [f64; 2]) -> Self {
+        let region = match tool {
+            Tool::BoxSelect => Region::Box {
+                corners: [start, start],
+            },
+            Tool::Lasso => Region::Lasso {
+                points: vec![start],
+            },
+            _ => panic!("{:?} does not select", tool),
+        };
+        let capacity = region.outline().len().max(MIN_RUBBER_BAND_CAPACITY);
+        SelectionDrag {
+            overlay: Overlay::new(rubber_band(&region, capacity)),
+            region,
+            capacity,
+        }
+    }
+
+    /// Follows the cursor to scene position `position`, updating the uploaded overlay
+    /// relative to `origin`. Returns false if the outline outgrew the overlay, which then has
+    /// to be uploaded again.
+    pub fn extend(&mut self, position: [f64; 2], queue: &Queue, origin: [f64; 2]) -> bool {
+        match &mut self.region {
+            Region::Box { corners } => corners[1] = position,
+            Region::Lasso { points } => points.push(position),
+        }
+        let count = self.region.outline().len();
+        if count > self.capacity {
+            // Doubling keeps re-uploads rare as a lasso grows.
+            self.capacity = count.max(2 * self.capacity);
+            self.overlay = Overlay::new(rubber_band(&self.region, self.capacity));
+            return false;
+        }
+        self.overlay
+            .replace_layer(rubber_band(&self.region, self.capacity), queue, origin)
+    }
+
+    pub fn region(&self) -> &Region {
+        &self.region
+    }
+}
@@ -0,0 +1,189 @@
+use std::fmt::{self, Write};
+
+use crate::layer::{relative_to, Drawable, Layer};
+use crate::svg::{svg_paint, SvgIds};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, BlendComponent, BlendState, Buffer, Device, Queue, RenderPass,
+    RenderPipeline, SwapChainDescriptor,
+};
+
+/// A flat colored triangle, the building block for filled areas. Edges are not
+/// antialiased, so neighboring triangles of a shape meet without seams.
+#[derive(Copy, Clone, Debug)]
+pub struct Triangle {
+    pub vertices: [[f64; 2]; 3],
+    pub color: [f32; 4],
+}
+
+/// A `Triangle` as uploaded, with the vertices relative to the upload origin.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct TriangleInstance {
+    vertices: [[f32; 2]; 3],
+    color: [f32; 4],
+}
+
+impl Triangle {
+    fn instance(&self, origin: [f64; 2]) -> TriangleInstance {
+        let [a, b, c] = self.vertices;
+        TriangleInstance {
+            vertices: [
+                relative_to(a, origin),
+                relative_to(b, origin),
+                relative_to(c, origin),
+            ],
+            color: self.color,
+        }
+    }
+}
+
+pub struct TrianglesLayer {
+    data: Vec<Triangle>,
+}
+
+impl TrianglesLayer {
+    pub fn new(data: Vec<Triangle>) -> Self {
+        TrianglesLayer { data }
+    }
+}
+
+pub struct TrianglesLayerDrawable {
+    render_pipeline: RenderPipeline,
+    instance_buffer: Buffer,
+    num_triangles: u32,
+}
+
+impl Drawable for TrianglesLayerDrawable {
+    fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, bind_group: &'a BindGroup) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..3, 0..self.num_triangles);
+    }
+
+    fn write_instances(&self, queue: &Queue, first: usize, data: &[u8]) {
+        let offset = first * std::mem::size_of::<TriangleInstance>();
+        queue.write_buffer(&self.instance_buffer, offset as wgpu::BufferAddress, data);
+    }
+}
+
+impl Layer for TrianglesLayer {
+    fn init_drawable(
+        &self,
+        device: &Device,
+        _queue: &Queue,
+        sc_desc: &SwapChainDescriptor,
+        transform_layout: &BindGroupLayout,
+        origin: [f64; 2],
+    ) -> Box<dyn Drawable> {
+        let instance_buffer_desc = wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TriangleInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        };
+
+        let instances: Vec<TriangleInstance> =
+            self.data.iter().map(|d| d.instance(origin)).collect();
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance buffer"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[transform_layout],
+                push_constant_ranges: &[],
+            });
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("shader.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("shader.frag.spv"));
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &vs_module,
+                entry_point: "main",
+                buffers: &[instance_buffer_desc],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fs_module,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format: sc_desc.format,
+                    write_mask: wgpu::ColorWrite::ALL,
+                    blend: Some(BlendState {
+                        color: BlendComponent::OVER,
+                        alpha: BlendComponent::REPLACE,
+                    }),
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                clamp_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        });
+
+        Box::new(TrianglesLayerDrawable {
+            render_pipeline,
+            instance_buffer,
+            num_triangles: self.data.len() as u32,
+        })
+    }
+
+    fn rebase(&self, queue: &Queue, drawable: &dyn Drawable, origin: [f64; 2]) -> bool {
+        let instances: Vec<TriangleInstance> =
+            self.data.iter().map(|d| d.instance(origin)).collect();
+        drawable.write_instances(queue, 0, bytemuck::cast_slice(&instances));
+        true
+    }
+
+    fn write_svg(&self, out: &mut dyn Write, _pixel_size: f32, _ids: &mut SvgIds) -> fmt::Result {
+        for triangle in &self.data {
+            let [[x1, y1], [x2, y2], [x3, y3]] = triangle.vertices;
+            let (fill, opacity) = svg_paint(triangle.color);
+            writeln!(
+                out,
+                r#"<polygon points="{},{} {},{} {},{}" fill="{}" fill-opacity="{}"/>"#,
+                x1, y1, x2, y2, x3, y3, fill, opacity
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+#version 450
+
+layout(location=0) in vec2 a_a;
+layout(location=1) in vec2 a_b;
+layout(location=2) in vec2 a_c;
+layout(location=3) in vec4 a_color;
+
+layout(location=0) out vec4 v_color;
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_transform;
+    vec2 u_viewport_size;
+};
+
+void main() {
+    vec2 position;
+    switch (gl_VertexIndex) {
+        case 0:
+            position = a_a;
+            break;
+        case 1:
+            position = a_b;
+            break;
+        case 2:
+            position = a_c;
+    }
+
+    gl_Position = u_transform * vec4(position, 0., 1.);
+    v_color = a_color;
+}
@@ -6,27 +6,44 @@ use winit::window::{CursorIcon, Window};
 
 type Mat4 = [f32; 16];
 
-const ZOOM_FACTOR: f32 = 1.001;
+const ZOOM_FACTOR: f64 = 1.001;
+// An f32 offset of this many pixels is still accurate to about a hundredth of a pixel.
+const REBASE_DISTANCE_IN_PIXELS: f64 = 100_000.;
+// How much larger than the region the view is after `fit`.
+const FIT_MARGIN: f64 = 1.2;
+// Where `follow` keeps its target, from -1 at the left edge of the view to 1 at the right.
+const FOLLOW_POSITION: f64 = 0.8;
 
-fn size_to_vec(size: PhysicalSize<u32>) -> Vector2<f32> {
-    Vector2::new(size.width as f32, size.height as f32)
+/// Contents of the transform uniform buffer shared by all layers. The viewport size
+/// lets vertex shaders convert sizes given in pixels into scene units.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct TransformUniforms {
+    transform: Mat4,
+    viewport_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+fn size_to_vec(size: PhysicalSize<u32>) -> Vector2<f64> {
+    Vector2::new(size.width as f64, size.height as f64)
 }
 
 // Y increases going DOWN.
 #[derive(Debug)]
 struct WindowCoordinate(pub PhysicalPosition<f64>);
 
-// Y increases going UP.
+// Y increases going UP. Kept in double precision, so that deep zoom stays stable far from
+// the origin.
 #[derive(Debug, Clone, Copy)]
-struct SceneCoordinate(pub Vector2<f32>);
+struct SceneCoordinate(pub Vector2<f64>);
 
 // Y increases going UP.
 #[derive(Debug)]
-struct GPUCoordinate(pub Vector2<f32>);
+struct GPUCoordinate(pub Vector2<f64>);
 
 impl WindowCoordinate {
     pub fn to_gpu_coordinate(&self, size: PhysicalSize<u32>) -> GPUCoordinate {
-        let coordinate = Vector2::new(self.0.x as f32, size.height as f32 - self.0.y as f32);
+        let coordinate = Vector2::new(self.0.x, size.height as f64 - self.0.y);
         GPUCoordinate(
             2. * (ElementWise::div_element_wise(coordinate, size_to_vec(size)))
                 - Vector2::new(1., 1.),
@@ -44,7 +61,7 @@ impl GPUCoordinate {
     pub fn to_scene_coordinate(
         &self,
         center: SceneCoordinate,
-        scale: Vector2<f32>,
+        scale: Vector2<f64>,
         size: PhysicalSize<u32>,
     ) -> SceneCoordinate {
         let GPUCoordinate(coordinate) = *self;
@@ -59,7 +76,7 @@ impl GPUCoordinate {
 
 pub struct ZoomState {
     center: SceneCoordinate,
-    scale: Vector2<f32>,
+    scale: Vector2<f64>,
     size: PhysicalSize<u32>,
     last_position: WindowCoordinate,
     dragging: bool,
@@ -80,6 +97,64 @@ impl ZoomState {
         self.size = size;
     }
 
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    /// The scene coordinate at the center of the window.
+    pub fn center(&self) -> [f64; 2] {
+        [self.center.0.x, self.center.0.y]
+    }
+
+    /// The size of a screen pixel, in scene units.
+    pub fn pixel_size(&self) -> f32 {
+        (2. / self.scale.x) as f32
+    }
+
+    /// The visible region of the scene as `[min_x, min_y, width, height]`.
+    pub fn view_box(&self) -> [f64; 4] {
+        let half_extent = ElementWise::div_element_wise(size_to_vec(self.size), self.scale);
+        let min = self.center.0 - half_extent;
+        [min.x, min.y, 2. * half_extent.x, 2. * half_extent.y]
+    }
+
+    /// The scene coordinate at `position` in the window.
+    pub fn to_scene(&self, position: PhysicalPosition<f64>) -> [f64; 2] {
+        let gpu_coord = WindowCoordinate(position).to_gpu_coordinate(self.size);
+        let SceneCoordinate(scene_coord) =
+            gpu_coord.to_scene_coordinate(self.center, self.scale, self.size);
+        [scene_coord.x, scene_coord.y]
+    }
+
+    /// The last position of the cursor in the window, with Y going down.
+    pub fn cursor_position(&self) -> PhysicalPosition<f64> {
+        self.last_position.0
+    }
+
+    /// Whether a left drag is panning the view.
+    pub fn dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// Centers the view on the region from `min` to `max`, zoomed to fit it with a margin.
+    pub fn fit(&mut self, min: [f64; 2], max: [f64; 2]) {
+        let half_extent = Vector2::new(max[0] - min[0], max[1] - min[1]) * FIT_MARGIN / 2.;
+        let scale =
+            (self.size.width as f64 / half_extent.x).min(self.size.height as f64 / half_extent.y);
+        if !(scale.is_finite() && scale > 0.) {
+            return;
+        }
+
+        self.center = SceneCoordinate(Vector2::new((min[0] + max[0]) / 2., (min[1] + max[1]) / 2.));
+        self.scale = Vector2::new(scale, scale);
+    }
+
+    /// Pans horizontally so that scene coordinate `x` is near the right edge of the view.
+    pub fn follow(&mut self, x: f64) {
+        let half_extent = self.size.width as f64 / self.scale.x;
+        self.center.0.x = x - FOLLOW_POSITION * half_extent;
+    }
+
     pub fn handle_event(&mut self, event: &WindowEvent, window: &Window) -> bool {
         match event {
             WindowEvent::MouseInput {
@@ -103,7 +178,7 @@ impl ZoomState {
                 delta: MouseScrollDelta::PixelDelta(PhysicalPosition { y, .. }),
                 ..
             } => {
-                let zoom_multiplier = f32::powf(ZOOM_FACTOR, *y as f32);
+                let zoom_multiplier = f64::powf(ZOOM_FACTOR, *y);
 
                 let new_scale = self.scale * zoom_multiplier;
 
@@ -126,8 +201,8 @@ impl ZoomState {
             WindowEvent::CursorMoved { position, .. } => {
                 if self.dragging {
                     let delta = Vector2::new(
-                        self.last_position.0.x as f32 - position.x as f32,
-                        -(self.last_position.0.y as f32 - position.y as f32),
+                        self.last_position.0.x - position.x,
+                        -(self.last_position.0.y - position.y),
                     );
 
                     self.center = SceneCoordinate(
@@ -145,11 +220,15 @@ impl ZoomState {
         }
     }
 
-    pub fn matrix(&self) -> Mat4 {
-        let x_x = self.scale[0] / self.size.width as f32;
-        let y_y = self.scale[1] / self.size.height as f32;
-        let x_w = x_x * -self.center.0[0] as f32;
-        let y_w = y_y * -self.center.0[1] as f32;
+    /// The transform from scene coordinates relative to `origin` to GPU coordinates. Layers
+    /// upload their positions relative to the same origin, so the offsets in single precision
+    /// stay small as long as the origin is near the view.
+    pub fn matrix(&self, origin: [f64; 2]) -> Mat4 {
+        let x_x = self.scale[0] / self.size.width as f64;
+        let y_y = self.scale[1] / self.size.height as f64;
+        let x_w = x_x * (origin[0] - self.center.0[0]);
+        let y_w = y_y * (origin[1] - self.center.0[1]);
+        let [x_x, y_y, x_w, y_w] = [x_x as f32, y_y as f32, x_w as f32, y_w as f32];
 
         #[cfg_attr(rustfmt, rustfmt_skip)]
         [
@@ -159,4 +238,20 @@ impl ZoomState {
             x_w, y_w, 0., 1.,
         ]
     }
+
+    pub fn uniforms(&self, origin: [f64; 2]) -> TransformUniforms {
+        TransformUniforms {
+            transform: self.matrix(origin),
+            viewport_size: [self.size.width as f32, self.size.height as f32],
+            _padding: [0., 0.],
+        }
+    }
+
+    /// Whether positions relative to `origin` have become too large for single precision
+    /// near the center of the view, so layers should be uploaded again relative to `center()`.
+    pub fn needs_rebase(&self, origin: [f64; 2]) -> bool {
+        let distance = Vector2::new(origin[0], origin[1]) - self.center.0;
+        let distance_in_pixels = distance.x.abs().max(distance.y.abs()) * self.scale.x / 2.;
+        distance_in_pixels > REBASE_DISTANCE_IN_PIXELS
+    }
 }
@@ -0,0 +1,12 @@
+#version 450
+
+layout(location=0) out vec2 v_tex_coord;
+
+void main() {
+    // A single triangle covering the whole screen.
+    vec2 position = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2)) * 2. - 1.;
+    gl_Position = vec4(position, 0., 1.);
+
+    // Texture coordinates go down from the top of the texture.
+    v_tex_coord = vec2(position.x + 1., 1. - position.y) / 2.;
+}
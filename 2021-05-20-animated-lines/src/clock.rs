@@ -0,0 +1,88 @@
+use std::time::Instant;
+use winit::event::VirtualKeyCode;
+
+// Step used for single-stepping when the clock follows the wall clock.
+const DEFAULT_STEP: f32 = 1. / 60.;
+// Number of steps skipped by a single seek.
+const SEEK_STEPS: f32 = 60.;
+const SPEED_FACTOR: f32 = 2.;
+
+/// Source of animation time. Either follows the wall clock or advances by a fixed
+/// step on every tick, and can be paused, single-stepped, scaled and seeked.
+pub struct AnimationClock {
+    time: f32,
+    speed: f32,
+    paused: bool,
+    time_step: Option<f32>,
+    last_tick: Instant,
+}
+
+impl AnimationClock {
+    pub fn real_time() -> Self {
+        AnimationClock {
+            time: 0.,
+            speed: 1.,
+            paused: false,
+            time_step: None,
+            last_tick: Instant::now(),
+        }
+    }
+
+    pub fn fixed(time_step: f32) -> Self {
+        AnimationClock {
+            time_step: Some(time_step),
+            ..AnimationClock::real_time()
+        }
+    }
+
+    fn step_size(&self) -> f32 {
+        self.time_step.unwrap_or(DEFAULT_STEP)
+    }
+
+    /// Advances the clock by one frame and returns the new time.
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let elapsed = match self.time_step {
+            Some(time_step) => time_step,
+            None => (now - self.last_tick).as_secs_f32(),
+        };
+        self.last_tick = now;
+
+        if !self.paused {
+            self.time += elapsed * self.speed;
+        }
+
+        self.time
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Advances by exactly one step, regardless of speed. Mostly useful while paused.
+    pub fn step(&mut self) {
+        self.time += self.step_size();
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.max(0.);
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    /// Space pauses, `.` steps, up/down change speed, left/right seek and home rewinds.
+    pub fn handle_key(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Space => self.toggle_pause(),
+            VirtualKeyCode::Period => self.step(),
+            VirtualKeyCode::Up => self.set_speed(self.speed * SPEED_FACTOR),
+            VirtualKeyCode::Down => self.set_speed(self.speed / SPEED_FACTOR),
+            VirtualKeyCode::Right => self.seek(self.time + SEEK_STEPS * self.step_size()),
+            VirtualKeyCode::Left => self.seek(self.time - SEEK_STEPS * self.step_size()),
+            VirtualKeyCode::Home => self.seek(0.),
+            _ => {}
+        }
+    }
+}
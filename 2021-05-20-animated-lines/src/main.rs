@@ -1,6 +1,7 @@
 use std::iter;
 
-use std::time::{Instant};
+use clap::Clap;
+use std::path::PathBuf;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::{BlendComponent, BlendState, BufferUsage};
 use winit::dpi::PhysicalSize;
@@ -9,12 +10,45 @@ use winit::{
     event_loop::{ControlFlow, EventLoop},
     window::{Window, WindowBuilder},
 };
-use rand::{thread_rng, Rng, seq::SliceRandom};
+use rand::{Rng, SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha8Rng;
+
+use clock::AnimationClock;
+use recorder::Recorder;
+
+mod clock;
+mod recorder;
 
 const NUM_LINES: u32 = 30000;
 
 const OPTIONS: [f32; 11] = [6., 8., 10., 12., 15., 20., 24., 30., 40., 60., 120.];
 const OFFSET: f32 = std::f32::consts::PI / 3.;
+const RECORD_TIME_STEP: f32 = 1. / 30.;
+
+#[derive(Clap)]
+struct Opts {
+    /// Seed for the line generators. The same seed produces the same lines on every machine.
+    #[clap(short, long, default_value = "0")]
+    seed: u64,
+
+    /// Advance time by a fixed amount (in seconds) per frame instead of using the wall clock,
+    /// so that frames are reproducible.
+    #[clap(short, long)]
+    time_step: Option<f32>,
+
+    /// Write every frame to this directory as a numbered PNG file. Implies a fixed time step
+    /// (1/30 s unless `--time-step` is given).
+    #[clap(long)]
+    record: Option<PathBuf>,
+
+    /// Stop after recording this many frames.
+    #[clap(long, requires = "record")]
+    frames: Option<u32>,
+
+    /// Also write the recorded frames to an animated GIF.
+    #[clap(long, requires = "record")]
+    gif: bool,
+}
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
@@ -35,7 +69,8 @@ struct State {
     render_pipeline: wgpu::RenderPipeline,
 
     instance_buffer: wgpu::Buffer,
-    created: Instant,
+    clock: AnimationClock,
+    recorder: Option<Recorder>,
     generators: Vec<LineGenerator>,
 }
 
@@ -48,12 +83,10 @@ struct LineGenerator {
 }
 
 impl LineGenerator {
-    pub fn new_rand() -> Self {
-        let mut rng = thread_rng();
-
+    pub fn new_rand<R: Rng>(rng: &mut R) -> Self {
         LineGenerator {
             start: [rng.gen_range(-1.1..1.1), rng.gen_range(-1.1..1.1)],
-            speed: *OPTIONS.choose(&mut rng).unwrap(),
+            speed: *OPTIONS.choose(rng).unwrap(),
             color: [
                 rng.gen_range(0.0..1.0),
                 rng.gen_range(0.0..1.0),
@@ -87,7 +120,7 @@ impl State {
         generators.iter().map(|d| d.gen_line(time)).collect()
     }
 
-    async fn new(window: &Window) -> Self {
+    async fn new(window: &Window, opts: &Opts) -> Self {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
@@ -112,7 +145,8 @@ impl State {
             .await
             .unwrap();
 
-        let generators: Vec<LineGenerator> = (0..NUM_LINES).map(|_| LineGenerator::new_rand()).collect();
+        let mut rng = ChaCha8Rng::seed_from_u64(opts.seed);
+        let generators: Vec<LineGenerator> = (0..NUM_LINES).map(|_| LineGenerator::new_rand(&mut rng)).collect();
 
         let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Instance buffer"),
@@ -203,7 +237,31 @@ impl State {
             },
         });
 
-        let created = Instant::now();
+        // Recordings always use a fixed time step so that they play back smoothly,
+        // however long each frame took to render and save.
+        let time_step = match (&opts.record, opts.time_step) {
+            (_, Some(time_step)) => Some(time_step),
+            (Some(_), None) => Some(RECORD_TIME_STEP),
+            (None, None) => None,
+        };
+
+        let clock = match time_step {
+            Some(time_step) => AnimationClock::fixed(time_step),
+            None => AnimationClock::real_time(),
+        };
+
+        let recorder = opts.record.as_ref().map(|output_dir| {
+            Recorder::new(
+                &device,
+                sc_desc.format,
+                size,
+                output_dir.clone(),
+                opts.frames,
+                opts.gif,
+                (time_step.unwrap() * 1000.) as u32,
+            )
+            .expect("Create recorder")
+        });
 
         Self {
             surface,
@@ -214,7 +272,8 @@ impl State {
             swap_chain,
             render_pipeline,
             instance_buffer,
-            created,
+            clock,
+            recorder,
             generators,
         }
     }
@@ -235,7 +294,7 @@ impl State {
                 label: Some("Render Encoder"),
             });
 
-        let time = self.created.elapsed().as_secs_f32();
+        let time = self.clock.tick();
 
         let lines = Self::gen_lines(&self.generators, time);
         let tmp_buffer = self.device.create_buffer_init(&BufferInitDescriptor {
@@ -252,61 +311,102 @@ impl State {
             std::mem::size_of::<Line>() as u64 * NUM_LINES as u64,
         );
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &frame.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.,
-                            g: 0.,
-                            b: 0.,
-                            a: 1.,
-                        }),
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment: None,
-            });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
-            render_pass.draw(0..6, 0..NUM_LINES);
+        match &self.recorder {
+            Some(recorder) => {
+                self.draw(&mut encoder, recorder.view());
+                recorder.copy_frame(&mut encoder);
+                recorder.present(&mut encoder, &frame.view);
+            }
+            None => self.draw(&mut encoder, &frame.view),
         }
 
         self.queue.submit(iter::once(encoder.finish()));
 
+        if let Some(recorder) = &mut self.recorder {
+            recorder.save_frame(&self.device).expect("Save frame");
+        }
+
         Ok(())
     }
+
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.,
+                        g: 0.,
+                        b: 0.,
+                        a: 1.,
+                    }),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..NUM_LINES);
+    }
+
+    fn recording_finished(&self) -> bool {
+        self.recorder.as_ref().map_or(false, Recorder::is_finished)
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.finish();
+        }
+    }
 }
 
 fn main() {
     env_logger::init();
+
+    let opts = Opts::parse();
+
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_inner_size(PhysicalSize::new(800, 800))
         .with_title("Alignment")
+        // The capture texture is sized once, so recordings keep the window size fixed.
+        .with_resizable(opts.record.is_none())
         .build(&event_loop)
         .unwrap();
 
     use futures::executor::block_on;
 
-    let mut state = block_on(State::new(&window));
+    let mut state = block_on(State::new(&window, &opts));
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             ref event,
             window_id,
         } if window_id == window.id() => match event {
-            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::CloseRequested => {
+                state.stop_recording();
+                *control_flow = ControlFlow::Exit
+            }
             WindowEvent::KeyboardInput { input, .. } => match input {
                 KeyboardInput {
                     state: ElementState::Pressed,
                     virtual_keycode: Some(VirtualKeyCode::Escape),
                     ..
-                } => *control_flow = ControlFlow::Exit,
+                } => {
+                    state.stop_recording();
+                    *control_flow = ControlFlow::Exit
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                } => {
+                    state.clock.handle_key(*key);
+                }
                 _ => {}
             },
             WindowEvent::Resized(physical_size) => {
@@ -318,7 +418,12 @@ fn main() {
             _ => {}
         },
         Event::RedrawRequested(_) => match state.render() {
-            Ok(_) => {}
+            Ok(_) => {
+                if state.recording_finished() {
+                    state.stop_recording();
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
             Err(wgpu::SwapChainError::Lost) => state.resize(state.size),
             Err(wgpu::SwapChainError::OutOfMemory) => *control_flow = ControlFlow::Exit,
             Err(e) => eprintln!("{:?}", e),
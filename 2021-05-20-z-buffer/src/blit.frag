@@ -0,0 +1,12 @@
+#version 450
+
+layout(location=0) in vec2 v_tex_coord;
+
+layout(location=0) out vec4 f_color;
+
+layout(set=0, binding=0) uniform texture2D t_frame;
+layout(set=0, binding=1) uniform sampler s_frame;
+
+void main() {
+    f_color = texture(sampler2D(t_frame, s_frame), v_tex_coord);
+}
@@ -1,6 +1,7 @@
 use std::iter;
 
 use clap::Clap;
+use std::path::PathBuf;
 use std::time::Instant;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::{
@@ -17,9 +18,29 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
+use clock::AnimationClock;
+use recorder::Recorder;
+
+mod clock;
+mod recorder;
+
 const FPS_RESET_FRAMES: u32 = 10;
 const DEFAULT_RADIUS: f32 = 0.05;
 const SCALE_AMOUNT: f32 = 1.05;
+// The animation advances one time unit per frame, made for 60 FPS. GIF delays are in
+// hundredths of a second, and viewers slow down anything shorter than two, so recordings
+// play back at 50 FPS and advance 1.2 units per frame to keep the same speed.
+const RECORD_FRAME_DELAY_MS: u32 = 20;
+const RECORD_TIME_STEP: f32 = 1.2;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct Uniforms {
+    time: f32,
+    radius: f32,
+    seed: u32,
+    _padding: u32,
+}
 
 #[derive(Clap)]
 struct Opts {
@@ -28,6 +49,22 @@ struct Opts {
 
     #[clap(short, long, default_value = "40000")]
     num_circles: u32,
+
+    /// Seed for the per-circle orbit parameters.
+    #[clap(short, long, default_value = "0")]
+    seed: u32,
+
+    /// Write every frame to this directory as a numbered PNG file.
+    #[clap(long)]
+    record: Option<PathBuf>,
+
+    /// Stop after recording this many frames.
+    #[clap(long, requires = "record")]
+    frames: Option<u32>,
+
+    /// Also write the recorded frames to an animated GIF.
+    #[clap(long, requires = "record")]
+    gif: bool,
 }
 
 fn create_depth_texture_view(device: &Device, sc_desc: &SwapChainDescriptor) -> TextureView {
@@ -66,14 +103,18 @@ struct State {
     uniform_buffer: Buffer,
     uniform_bind_group: BindGroup,
     frame: u32,
+    clock: AnimationClock,
+    recorder: Option<Recorder>,
     num_circles: u32,
+    seed: u32,
     last_time: Instant,
     radius: f32,
 }
 
 impl State {
-    async fn new(window: &Window, num_circles: u32, enable_depth: bool) -> Self {
+    async fn new(window: &Window, opts: &Opts) -> Self {
         let size = window.inner_size();
+        let enable_depth = !opts.disable_depth;
 
         let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
         let surface = unsafe { instance.create_surface(window) };
@@ -111,7 +152,12 @@ impl State {
 
         let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("time"),
-            contents: &bytemuck::cast_slice(&[0.0f32, DEFAULT_RADIUS]),
+            contents: &bytemuck::cast_slice(&[Uniforms {
+                time: 0.,
+                radius: DEFAULT_RADIUS,
+                seed: opts.seed,
+                _padding: 0,
+            }]),
             usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
         });
 
@@ -200,6 +246,24 @@ impl State {
             },
         });
 
+        let time_step = if opts.record.is_some() {
+            RECORD_TIME_STEP
+        } else {
+            1.
+        };
+        let recorder = opts.record.as_ref().map(|output_dir| {
+            Recorder::new(
+                &device,
+                sc_desc.format,
+                size,
+                output_dir.clone(),
+                opts.frames,
+                opts.gif,
+                RECORD_FRAME_DELAY_MS,
+            )
+            .expect("Create recorder")
+        });
+
         Self {
             surface,
             device,
@@ -212,7 +276,12 @@ impl State {
             uniform_buffer,
             uniform_bind_group,
             frame: 0,
-            num_circles,
+            // Time is measured in frames, so the animation advances at the
+            // same rate regardless of how fast frames are rendered.
+            clock: AnimationClock::fixed(time_step),
+            recorder,
+            num_circles: opts.num_circles,
+            seed: opts.seed,
             last_time: Instant::now(),
             radius: DEFAULT_RADIUS,
         }
@@ -250,51 +319,78 @@ impl State {
         self.queue.write_buffer(
             &self.uniform_buffer,
             0,
-            &bytemuck::cast_slice(&[self.frame as f32, self.radius]),
+            &bytemuck::cast_slice(&[Uniforms {
+                time: self.clock.tick(),
+                radius: self.radius,
+                seed: self.seed,
+                _padding: 0,
+            }]),
         );
 
-        {
-            let depth_stencil_attachment =
-                if let Some(depth_texture_view) = &self.depth_texture_view {
-                    Some(RenderPassDepthStencilAttachment {
-                        view: &depth_texture_view,
-                        depth_ops: Some(Operations {
-                            load: LoadOp::Clear(1.0),
-                            store: true,
-                        }),
-                        stencil_ops: None,
-                    })
-                } else {
-                    None
-                };
-
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &frame.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.01,
-                            g: 0.01,
-                            b: 0.01,
-                            a: 1.,
-                        }),
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment,
-            });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.draw(0..6, 0..self.num_circles);
+        match &self.recorder {
+            Some(recorder) => {
+                self.draw(&mut encoder, recorder.view());
+                recorder.copy_frame(&mut encoder);
+                recorder.present(&mut encoder, &frame.view);
+            }
+            None => self.draw(&mut encoder, &frame.view),
         }
 
         self.queue.submit(iter::once(encoder.finish()));
 
+        if let Some(recorder) = &mut self.recorder {
+            recorder.save_frame(&self.device).expect("Save frame");
+        }
+
         Ok(())
     }
+
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &TextureView) {
+        let depth_stencil_attachment = if let Some(depth_texture_view) = &self.depth_texture_view {
+            Some(RenderPassDepthStencilAttachment {
+                view: &depth_texture_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            })
+        } else {
+            None
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.01,
+                        g: 0.01,
+                        b: 0.01,
+                        a: 1.,
+                    }),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        render_pass.draw(0..6, 0..self.num_circles);
+    }
+
+    fn recording_finished(&self) -> bool {
+        self.recorder.as_ref().map_or(false, Recorder::is_finished)
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.finish();
+        }
+    }
 }
 
 fn main() {
@@ -306,25 +402,33 @@ fn main() {
     let window = WindowBuilder::new()
         .with_title("Z-buffer test")
         .with_inner_size(PhysicalSize::new(900, 900))
+        // The capture texture is sized once, so recordings keep the window size fixed.
+        .with_resizable(opts.record.is_none())
         .build(&event_loop)
         .unwrap();
 
     use futures::executor::block_on;
 
-    let mut state = block_on(State::new(&window, opts.num_circles, !opts.disable_depth));
+    let mut state = block_on(State::new(&window, &opts));
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             ref event,
             window_id,
         } if window_id == window.id() => match event {
-            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::CloseRequested => {
+                state.stop_recording();
+                *control_flow = ControlFlow::Exit
+            }
             WindowEvent::KeyboardInput { input, .. } => match input {
                 KeyboardInput {
                     state: ElementState::Pressed,
                     virtual_keycode: Some(VirtualKeyCode::Escape),
                     ..
-                } => *control_flow = ControlFlow::Exit,
+                } => {
+                    state.stop_recording();
+                    *control_flow = ControlFlow::Exit
+                }
                 KeyboardInput {
                     state: ElementState::Pressed,
                     virtual_keycode: Some(VirtualKeyCode::A),
@@ -341,6 +445,13 @@ fn main() {
                     state.radius *= 1. / SCALE_AMOUNT;
                     println!("New radius: {}", state.radius);
                 }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                } => {
+                    state.clock.handle_key(*key);
+                }
                 _ => {}
             },
             WindowEvent::Resized(physical_size) => {
@@ -352,7 +463,12 @@ fn main() {
             _ => {}
         },
         Event::RedrawRequested(_) => match state.render() {
-            Ok(_) => {}
+            Ok(_) => {
+                if state.recording_finished() {
+                    state.stop_recording();
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
             Err(wgpu::SwapChainError::Lost) => state.resize(state.size),
             Err(wgpu::SwapChainError::OutOfMemory) => *control_flow = ControlFlow::Exit,
             Err(e) => eprintln!("{:?}", e),
@@ -7,11 +7,29 @@ layout(set=0, binding=0)
 uniform Uniforms {
     float u_time;
     float u_radius;
+    uint u_seed;
 };
 
+// Integer hash (lowbias32), used instead of fract(sin(x)) because sin is not
+// computed identically on every GPU.
+uint hash(uint x) {
+    x ^= x >> 16;
+    x *= 0x7feb352dU;
+    x ^= x >> 15;
+    x *= 0x846ca68bU;
+    x ^= x >> 16;
+    return x;
+}
+
+// Maps a hash to [0, 1).
+float unit(uint h) {
+    return float(h >> 8) / 16777216.0;
+}
+
 void main() {
-    float speed = (fract(sin(gl_InstanceIndex+1) * 99999.0) - 0.5) / 100.;
-    float r_ = fract(sin(gl_InstanceIndex+1) * 99998.0);
+    uint h = hash(uint(gl_InstanceIndex) ^ hash(u_seed));
+    float speed = (unit(h) - 0.5) / 100.;
+    float r_ = unit(hash(h));
     float r = (1 - r_ * r_) * (1-u_radius);
 
     float x = r * cos(speed * u_time);
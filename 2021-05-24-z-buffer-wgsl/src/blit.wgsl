@@ -0,0 +1,29 @@
+struct VertexOutput {
+    [[location(0)]] tex_coord: vec2<f32>;
+    [[builtin(position)]] position: vec4<f32>;
+};
+
+// A single triangle covering the whole screen.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] in_vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    let x: f32 = f32((in_vertex_index << 1u) & 2u) * 2. - 1.;
+    let y: f32 = f32(in_vertex_index & 2u) * 2. - 1.;
+    out.position = vec4<f32>(x, y, 0., 1.);
+
+    // Texture coordinates go down from the top of the texture.
+    out.tex_coord = vec2<f32>(x + 1., 1. - y) / 2.;
+
+    return out;
+}
+
+[[group(0), binding(0)]]
+var t_frame: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_frame: sampler;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_frame, s_frame, in.tex_coord);
+}
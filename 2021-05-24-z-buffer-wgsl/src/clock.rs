@@ -0,0 +1,65 @@
+use winit::event::VirtualKeyCode;
+
+// Number of steps skipped by a single seek.
+const SEEK_STEPS: f32 = 60.;
+const SPEED_FACTOR: f32 = 2.;
+
+/// Source of animation time. Advances by a fixed step on every tick, and can be
+/// paused, single-stepped, scaled and seeked.
+pub struct AnimationClock {
+    time: f32,
+    speed: f32,
+    paused: bool,
+    time_step: f32,
+}
+
+impl AnimationClock {
+    pub fn fixed(time_step: f32) -> Self {
+        AnimationClock {
+            time: 0.,
+            speed: 1.,
+            paused: false,
+            time_step,
+        }
+    }
+
+    /// Advances the clock by one frame and returns the new time.
+    pub fn tick(&mut self) -> f32 {
+        if !self.paused {
+            self.time += self.time_step * self.speed;
+        }
+
+        self.time
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Advances by exactly one step, regardless of speed. Mostly useful while paused.
+    pub fn step(&mut self) {
+        self.time += self.time_step;
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.max(0.);
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    /// Space pauses, `.` steps, up/down change speed, left/right seek and home rewinds.
+    pub fn handle_key(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Space => self.toggle_pause(),
+            VirtualKeyCode::Period => self.step(),
+            VirtualKeyCode::Up => self.set_speed(self.speed * SPEED_FACTOR),
+            VirtualKeyCode::Down => self.set_speed(self.speed / SPEED_FACTOR),
+            VirtualKeyCode::Right => self.seek(self.time + SEEK_STEPS * self.time_step),
+            VirtualKeyCode::Left => self.seek(self.time - SEEK_STEPS * self.time_step),
+            VirtualKeyCode::Home => self.seek(0.),
+            _ => {}
+        }
+    }
+}
@@ -2,6 +2,7 @@ use std::borrow::Cow;
 use std::iter;
 
 use clap::Clap;
+use std::path::PathBuf;
 use std::time::Instant;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::ShaderFlags;
@@ -9,8 +10,9 @@ use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
     BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType,
     BufferUsage, CompareFunction, DepthBiasState, DepthStencilState, Device, Extent3d, LoadOp,
-    Operations, RenderPassDepthStencilAttachment, ShaderModuleDescriptor, ShaderSource, ShaderStage,
-    StencilState, SwapChainDescriptor, TextureDescriptor, TextureFormat, TextureUsage, TextureView,
+    Operations, PipelineLayout, RenderPassDepthStencilAttachment, RenderPipeline,
+    ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStage, StencilState,
+    SwapChainDescriptor, TextureDescriptor, TextureFormat, TextureUsage, TextureView,
     TextureViewDescriptor,
 };
 use winit::dpi::PhysicalSize;
@@ -20,17 +22,77 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
+use clock::AnimationClock;
+use recorder::Recorder;
+
+mod clock;
+mod recorder;
+
 const FPS_RESET_FRAMES: u32 = 10;
 const DEFAULT_RADIUS: f32 = 0.05;
 const SCALE_AMOUNT: f32 = 1.05;
+// The animation advances one time unit per frame, made for 60 FPS. GIF delays are in
+// hundredths of a second, and viewers slow down anything shorter than two, so recordings
+// play back at 50 FPS and advance 1.2 units per frame to keep the same speed.
+const RECORD_FRAME_DELAY_MS: u32 = 20;
+const RECORD_TIME_STEP: f32 = 1.2;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct Uniforms {
+    time: f32,
+    radius: f32,
+    seed: u32,
+    _padding: u32,
+}
 
 #[derive(Clap)]
 struct Opts {
     #[clap(short, long)]
     disable_depth: bool,
 
+    /// Run a depth-only pre-pass that does the discard test, then shade
+    /// with an equal depth test and no discard.
+    #[clap(short, long, conflicts_with = "disable_depth")]
+    prepass: bool,
+
     #[clap(short, long, default_value = "40000")]
     num_circles: u32,
+
+    /// Seed for the per-circle orbit parameters.
+    #[clap(short, long, default_value = "0")]
+    seed: u32,
+
+    /// Write every frame to this directory as a numbered PNG file.
+    #[clap(long)]
+    record: Option<PathBuf>,
+
+    /// Stop after recording this many frames.
+    #[clap(long, requires = "record")]
+    frames: Option<u32>,
+
+    /// Also write the recorded frames to an animated GIF.
+    #[clap(long, requires = "record")]
+    gif: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DepthMode {
+    Disabled,
+    Discard,
+    PrePass,
+}
+
+impl DepthMode {
+    fn from_opts(opts: &Opts) -> Self {
+        if opts.disable_depth {
+            DepthMode::Disabled
+        } else if opts.prepass {
+            DepthMode::PrePass
+        } else {
+            DepthMode::Discard
+        }
+    }
 }
 
 fn create_depth_texture_view(device: &Device, sc_desc: &SwapChainDescriptor) -> TextureView {
@@ -57,6 +119,45 @@ fn create_depth_texture_view(device: &Device, sc_desc: &SwapChainDescriptor) ->
     view
 }
 
+fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader_module: &ShaderModule,
+    fs_entry_point: &str,
+    targets: &[wgpu::ColorTargetState],
+    depth_stencil: Option<DepthStencilState>,
+) -> RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader_module,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader_module,
+            entry_point: fs_entry_point,
+            targets,
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            clamp_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
+
 struct State {
     surface: wgpu::Surface,
     device: wgpu::Device,
@@ -65,18 +166,24 @@ struct State {
     swap_chain: wgpu::SwapChain,
     size: winit::dpi::PhysicalSize<u32>,
     render_pipeline: wgpu::RenderPipeline,
+    depth_prepass_pipeline: Option<wgpu::RenderPipeline>,
+    depth_mode: DepthMode,
     depth_texture_view: Option<TextureView>,
     uniform_buffer: Buffer,
     uniform_bind_group: BindGroup,
     frame: u32,
+    clock: AnimationClock,
+    recorder: Option<Recorder>,
     num_circles: u32,
+    seed: u32,
     last_time: Instant,
     radius: f32,
 }
 
 impl State {
-    async fn new(window: &Window, num_circles: u32, enable_depth: bool) -> Self {
+    async fn new(window: &Window, opts: &Opts) -> Self {
         let size = window.inner_size();
+        let depth_mode = DepthMode::from_opts(opts);
 
         let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
         let surface = unsafe { instance.create_surface(window) };
@@ -117,7 +224,12 @@ impl State {
 
         let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("time"),
-            contents: &bytemuck::cast_slice(&[0.0f32, DEFAULT_RADIUS]),
+            contents: &bytemuck::cast_slice(&[Uniforms {
+                time: 0.,
+                radius: DEFAULT_RADIUS,
+                seed: opts.seed,
+                _padding: 0,
+            }]),
             usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
         });
 
@@ -151,54 +263,94 @@ impl State {
                 push_constant_ranges: &[],
             });
 
-        let depth_texture_view = if enable_depth {
+        let depth_texture_view = if depth_mode != DepthMode::Disabled {
             Some(create_depth_texture_view(&device, &sc_desc))
         } else {
             None
         };
 
-        let depth_stencil = if enable_depth {
-            Some(DepthStencilState {
-                format: TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: CompareFunction::Less,
-                stencil: StencilState::default(),
-                bias: DepthBiasState::default(),
-            })
-        } else {
-            None
-        };
-
         let swapchain_format = adapter.get_swap_chain_preferred_format(&surface).unwrap();
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader_module,
-                entry_point: "vs_main",
-                buffers: &[],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &&shader_module,
-                entry_point: "fs_main",
-                targets: &[swapchain_format.into()],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                clamp_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
+        let (render_pipeline, depth_prepass_pipeline) = match depth_mode {
+            DepthMode::Disabled => (
+                create_pipeline(
+                    &device,
+                    &render_pipeline_layout,
+                    &shader_module,
+                    "fs_main",
+                    &[swapchain_format.into()],
+                    None,
+                ),
+                None,
+            ),
+            DepthMode::Discard => (
+                create_pipeline(
+                    &device,
+                    &render_pipeline_layout,
+                    &shader_module,
+                    "fs_main",
+                    &[swapchain_format.into()],
+                    Some(DepthStencilState {
+                        format: TextureFormat::Depth32Float,
+                        depth_write_enabled: true,
+                        depth_compare: CompareFunction::Less,
+                        stencil: StencilState::default(),
+                        bias: DepthBiasState::default(),
+                    }),
+                ),
+                None,
+            ),
+            DepthMode::PrePass => {
+                // The pre-pass is the only place that discards, so the color pass
+                // keeps early depth testing and only shades the visible fragment.
+                let prepass = create_pipeline(
+                    &device,
+                    &render_pipeline_layout,
+                    &shader_module,
+                    "fs_depth",
+                    &[],
+                    Some(DepthStencilState {
+                        format: TextureFormat::Depth32Float,
+                        depth_write_enabled: true,
+                        depth_compare: CompareFunction::Less,
+                        stencil: StencilState::default(),
+                        bias: DepthBiasState::default(),
+                    }),
+                );
+                let color = create_pipeline(
+                    &device,
+                    &render_pipeline_layout,
+                    &shader_module,
+                    "fs_color",
+                    &[swapchain_format.into()],
+                    Some(DepthStencilState {
+                        format: TextureFormat::Depth32Float,
+                        depth_write_enabled: false,
+                        depth_compare: CompareFunction::Equal,
+                        stencil: StencilState::default(),
+                        bias: DepthBiasState::default(),
+                    }),
+                );
+                (color, Some(prepass))
+            }
+        };
+
+        let time_step = if opts.record.is_some() {
+            RECORD_TIME_STEP
+        } else {
+            1.
+        };
+        let recorder = opts.record.as_ref().map(|output_dir| {
+            Recorder::new(
+                &device,
+                swapchain_format,
+                size,
+                output_dir.clone(),
+                opts.frames,
+                opts.gif,
+                RECORD_FRAME_DELAY_MS,
+            )
+            .expect("Create recorder")
         });
 
         Self {
@@ -209,11 +361,18 @@ impl State {
             sc_desc,
             swap_chain,
             render_pipeline,
+            depth_prepass_pipeline,
+            depth_mode,
             depth_texture_view,
             uniform_buffer,
             uniform_bind_group,
             frame: 0,
-            num_circles,
+            // Time is measured in frames, so the animation advances at the
+            // same rate regardless of how fast frames are rendered.
+            clock: AnimationClock::fixed(time_step),
+            recorder,
+            num_circles: opts.num_circles,
+            seed: opts.seed,
             last_time: Instant::now(),
             radius: DEFAULT_RADIUS,
         }
@@ -236,7 +395,10 @@ impl State {
         if self.frame % FPS_RESET_FRAMES == 0 {
             let duration = self.last_time.elapsed();
             let fps = FPS_RESET_FRAMES as f32 / duration.as_secs_f32();
-            println!("FPS of last {} frames: {}", FPS_RESET_FRAMES, fps);
+            println!(
+                "FPS of last {} frames ({:?}): {}",
+                FPS_RESET_FRAMES, self.depth_mode, fps
+            );
             self.last_time = Instant::now();
         }
 
@@ -251,16 +413,69 @@ impl State {
         self.queue.write_buffer(
             &self.uniform_buffer,
             0,
-            &bytemuck::cast_slice(&[self.frame as f32, self.radius]),
+            &bytemuck::cast_slice(&[Uniforms {
+                time: self.clock.tick(),
+                radius: self.radius,
+                seed: self.seed,
+                _padding: 0,
+            }]),
         );
 
+        match &self.recorder {
+            Some(recorder) => {
+                self.draw(&mut encoder, recorder.view());
+                recorder.copy_frame(&mut encoder);
+                recorder.present(&mut encoder, &frame.view);
+            }
+            None => self.draw(&mut encoder, &frame.view),
+        }
+
+        self.queue.submit(iter::once(encoder.finish()));
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.save_frame(&self.device).expect("Save frame");
+        }
+
+        Ok(())
+    }
+
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &TextureView) {
+        if let (Some(depth_prepass_pipeline), Some(depth_texture_view)) =
+            (&self.depth_prepass_pipeline, &self.depth_texture_view)
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Depth Pre-pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &depth_texture_view,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            render_pass.set_pipeline(depth_prepass_pipeline);
+            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            render_pass.draw(0..6, 0..self.num_circles);
+        }
+
         {
+            // When the pre-pass has run, the depth buffer already holds the final
+            // depth of every pixel and must not be cleared.
+            let depth_load = if self.depth_prepass_pipeline.is_some() {
+                LoadOp::Load
+            } else {
+                LoadOp::Clear(1.0)
+            };
+
             let depth_stencil_attachment =
                 if let Some(depth_texture_view) = &self.depth_texture_view {
                     Some(RenderPassDepthStencilAttachment {
                         view: &depth_texture_view,
                         depth_ops: Some(Operations {
-                            load: LoadOp::Clear(1.0),
+                            load: depth_load,
                             store: true,
                         }),
                         stencil_ops: None,
@@ -272,7 +487,7 @@ impl State {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &frame.view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -291,10 +506,16 @@ impl State {
             render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
             render_pass.draw(0..6, 0..self.num_circles);
         }
+    }
 
-        self.queue.submit(iter::once(encoder.finish()));
+    fn recording_finished(&self) -> bool {
+        self.recorder.as_ref().map_or(false, Recorder::is_finished)
+    }
 
-        Ok(())
+    fn stop_recording(&mut self) {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.finish();
+        }
     }
 }
 
@@ -307,25 +528,33 @@ fn main() {
     let window = WindowBuilder::new()
         .with_title("Z-buffer test")
         .with_inner_size(PhysicalSize::new(900, 900))
+        // The capture texture is sized once, so recordings keep the window size fixed.
+        .with_resizable(opts.record.is_none())
         .build(&event_loop)
         .unwrap();
 
     use futures::executor::block_on;
 
-    let mut state = block_on(State::new(&window, opts.num_circles, !opts.disable_depth));
+    let mut state = block_on(State::new(&window, &opts));
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             ref event,
             window_id,
         } if window_id == window.id() => match event {
-            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::CloseRequested => {
+                state.stop_recording();
+                *control_flow = ControlFlow::Exit
+            }
             WindowEvent::KeyboardInput { input, .. } => match input {
                 KeyboardInput {
                     state: ElementState::Pressed,
                     virtual_keycode: Some(VirtualKeyCode::Escape),
                     ..
-                } => *control_flow = ControlFlow::Exit,
+                } => {
+                    state.stop_recording();
+                    *control_flow = ControlFlow::Exit
+                }
                 KeyboardInput {
                     state: ElementState::Pressed,
                     virtual_keycode: Some(VirtualKeyCode::A),
@@ -342,6 +571,13 @@ fn main() {
                     state.radius *= 1. / SCALE_AMOUNT;
                     println!("New radius: {}", state.radius);
                 }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                } => {
+                    state.clock.handle_key(*key);
+                }
                 _ => {}
             },
             WindowEvent::Resized(physical_size) => {
@@ -353,7 +589,12 @@ fn main() {
             _ => {}
         },
         Event::RedrawRequested(_) => match state.render() {
-            Ok(_) => {}
+            Ok(_) => {
+                if state.recording_finished() {
+                    state.stop_recording();
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
             Err(wgpu::SwapChainError::Lost) => state.resize(state.size),
             Err(wgpu::SwapChainError::OutOfMemory) => *control_flow = ControlFlow::Exit,
             Err(e) => eprintln!("{:?}", e),
@@ -0,0 +1,316 @@
+use std::borrow::Cow;
+use std::fs::{create_dir_all, File};
+use std::io;
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+
+use futures::executor::block_on;
+use image::codecs::gif::GifEncoder;
+use image::{Delay, Frame, ImageResult, RgbaImage};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferDescriptor, BufferUsage,
+    CommandEncoder, Device, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, MapMode,
+    Origin3d, RenderPipeline, SamplerDescriptor, ShaderFlags, ShaderModuleDescriptor, ShaderSource,
+    ShaderStage, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+    TextureUsage, TextureView, TextureViewDescriptor, TextureViewDimension,
+};
+use winit::dpi::PhysicalSize;
+
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Renders frames into an offscreen texture and writes them out as numbered PNG files,
+/// and optionally as an animated GIF. The texture is also blitted to the window, so each
+/// frame is only rendered once.
+pub struct Recorder {
+    output_dir: PathBuf,
+    format: TextureFormat,
+    size: PhysicalSize<u32>,
+    texture: Texture,
+    view: TextureView,
+    blit_pipeline: RenderPipeline,
+    blit_bind_group: BindGroup,
+    buffer: Buffer,
+    padded_bytes_per_row: u32,
+    frame: u32,
+    max_frames: Option<u32>,
+    frame_delay_ms: u32,
+    gif: Option<GifEncoder<File>>,
+}
+
+impl Recorder {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        size: PhysicalSize<u32>,
+        output_dir: PathBuf,
+        max_frames: Option<u32>,
+        gif: bool,
+        frame_delay_ms: u32,
+    ) -> io::Result<Self> {
+        create_dir_all(&output_dir)?;
+
+        let gif = if gif {
+            Some(GifEncoder::new(File::create(
+                output_dir.join("animation.gif"),
+            )?))
+        } else {
+            None
+        };
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Capture texture"),
+            size: Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsage::RENDER_ATTACHMENT | TextureUsage::COPY_SRC | TextureUsage::SAMPLED,
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        let (blit_pipeline, blit_bind_group) = create_blit(device, &view, format);
+
+        // Rows copied out of a texture must be aligned to 256 bytes, so the buffer
+        // holds padded rows which are trimmed again when saving.
+        let unpadded_bytes_per_row = size.width * BYTES_PER_PIXEL;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (unpadded_bytes_per_row + align - 1) / align * align;
+
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Capture buffer"),
+            size: (padded_bytes_per_row * size.height) as wgpu::BufferAddress,
+            usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Ok(Recorder {
+            output_dir,
+            format,
+            size,
+            texture,
+            view,
+            blit_pipeline,
+            blit_bind_group,
+            buffer,
+            padded_bytes_per_row,
+            frame: 0,
+            max_frames,
+            frame_delay_ms,
+            gif,
+        })
+    }
+
+    /// The view to render the frame into, instead of the swap chain frame.
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    /// Draws the frame rendered into `view()` onto `target`, usually the swap chain frame.
+    pub fn present(&self, encoder: &mut CommandEncoder, target: &TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blit Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.blit_pipeline);
+        render_pass.set_bind_group(0, &self.blit_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Copies the rendered frame into the readback buffer. Must be called after the
+    /// frame has been drawn into `view()`, in the same encoder.
+    pub fn copy_frame(&self, encoder: &mut CommandEncoder) {
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(self.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: self.size.width,
+                height: self.size.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Reads back the frame copied by `copy_frame` and writes it out. Must be called
+    /// after the encoder has been submitted.
+    pub fn save_frame(&mut self, device: &Device) -> ImageResult<()> {
+        let slice = self.buffer.slice(..);
+        let map_future = slice.map_async(MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        block_on(map_future).expect("Map capture buffer");
+
+        let unpadded_bytes_per_row = (self.size.width * BYTES_PER_PIXEL) as usize;
+        let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * self.size.height as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+            }
+        }
+        self.buffer.unmap();
+
+        if matches!(
+            self.format,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_mut(BYTES_PER_PIXEL as usize) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        let image = RgbaImage::from_raw(self.size.width, self.size.height, pixels)
+            .expect("Capture buffer matches frame size");
+        image.save(self.output_dir.join(format!("frame-{:05}.png", self.frame)))?;
+
+        if let Some(gif) = &mut self.gif {
+            gif.encode_frame(Frame::from_parts(
+                image,
+                0,
+                0,
+                Delay::from_numer_denom_ms(self.frame_delay_ms, 1),
+            ))?;
+        }
+
+        self.frame += 1;
+
+        Ok(())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        match self.max_frames {
+            Some(max_frames) => self.frame >= max_frames,
+            None => false,
+        }
+    }
+
+    /// Flushes the GIF, if any. Should be called once, when recording stops.
+    pub fn finish(&mut self) {
+        self.gif = None;
+        println!(
+            "Wrote {} frames to {}",
+            self.frame,
+            self.output_dir.display()
+        );
+    }
+}
+
+fn create_blit(
+    device: &Device,
+    view: &TextureView,
+    format: TextureFormat,
+) -> (RenderPipeline, BindGroup) {
+    let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Blit bind group layout"),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: TextureViewDimension::D2,
+                    sample_type: TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Sampler {
+                    comparison: false,
+                    filtering: false,
+                },
+                count: None,
+            },
+        ],
+    });
+
+    // The capture texture has the same size as the window, so nearest sampling copies it exactly.
+    let sampler = device.create_sampler(&SamplerDescriptor::default());
+
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Blit bind group"),
+        layout: &bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Sampler(&sampler),
+            },
+        ],
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Blit Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let shader_module = device.create_shader_module(&ShaderModuleDescriptor {
+        label: None,
+        source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("blit.wgsl"))),
+        flags: ShaderFlags::all(),
+    });
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Blit Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader_module,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader_module,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format,
+                write_mask: wgpu::ColorWrite::ALL,
+                blend: None,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            clamp_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    });
+
+    (pipeline, bind_group)
+}
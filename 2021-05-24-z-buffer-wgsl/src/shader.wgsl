@@ -8,10 +8,28 @@ struct VertexOutput {
 struct Locals {
     time: f32;
     radius: f32;
+    seed: u32;
 };
 [[group(0), binding(0)]]
 var r_locals: Locals;
 
+// Integer hash (lowbias32), used instead of fract(sin(x)) because sin is not
+// computed identically on every GPU.
+fn hash(x: u32) -> u32 {
+    var h: u32 = x;
+    h = h ^ (h >> 16u);
+    h = h * 2146121005u;
+    h = h ^ (h >> 15u);
+    h = h * 2221713035u;
+    h = h ^ (h >> 16u);
+    return h;
+}
+
+// Maps a hash to [0, 1).
+fn unit(h: u32) -> f32 {
+    return f32(h >> 8u) / 16777216.0;
+}
+
 let corners: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
     vec2<f32>(-1., -1.),
     vec2<f32>(1., -1.),
@@ -30,8 +48,9 @@ fn vs_main([[builtin(vertex_index)]] in_vertex_index: u32,
     let u_radius = r_locals.radius;
     let u_time = r_locals.time;
 
-    let speed: f32 = (fract(sin(f32(in_instance_index)+1.) * 99999.0) - 0.5) / 100.;
-    let r_: f32 = fract(sin(f32(in_instance_index)+1.) * 99998.0);
+    let h: u32 = hash(in_instance_index ^ hash(r_locals.seed));
+    let speed: f32 = (unit(h) - 0.5) / 100.;
+    let r_: f32 = unit(hash(h));
     let r: f32 = (1. - r_ * r_) * (1. - u_radius);
     let x: f32 = r * cos(speed * u_time);
     let y: f32 = r * sin(speed * u_time);
@@ -39,7 +58,12 @@ fn vs_main([[builtin(vertex_index)]] in_vertex_index: u32,
     out.c = vec2<f32>(x, y);
     out.coord = corners[in_vertex_index];
     
-    out.position = vec4<f32>(x + u_radius * out.coord.x, y + u_radius * out.coord.y, 0., 1.);
+    // Give each circle a distinct depth so that the equal depth test in the
+    // color pass only matches the circle that won the pre-pass. Lower instances
+    // are still in front, as they were when every circle sat at z = 0.
+    let z: f32 = f32(in_instance_index) / 16777216.0;
+
+    out.position = vec4<f32>(x + u_radius * out.coord.x, y + u_radius * out.coord.y, z, 1.);
 
     return out;
 }
@@ -70,4 +94,45 @@ fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     }
 
     return vec4<f32>(0.0, 0.0, 0.0, 1.0);
-}
\ No newline at end of file
+}
+// Depth pre-pass: only the first iteration is needed to reproduce the
+// discard test in fs_main.
+[[stage(fragment)]]
+fn fs_depth(in: VertexOutput) {
+    let cx: f32 = (in.c.x - 0.5) * 1.2;
+    let cy: f32 = in.c.y * 1.2;
+
+    let x: f32 = in.coord.x * 2.;
+    let y: f32 = in.coord.y * 2.;
+
+    let zx: f32 = (x * x) - (y * y) + cx;
+    let zy: f32 = (2. * x * y) + cy;
+
+    if ((zx * zx) + (zy * zy) > 4.) {
+        discard;
+    }
+}
+
+// Color pass after the depth pre-pass. Same as fs_main, but without the
+// discard, so early depth testing stays enabled.
+[[stage(fragment)]]
+fn fs_color(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let cx: f32 = (in.c.x - 0.5) * 1.2;
+    let cy: f32 = in.c.y * 1.2;
+
+    var zx: f32 = in.coord.x * 2.;
+    var zy: f32 = in.coord.y * 2.;
+
+    for(var i: i32 = 0; i < ITERATIONS; i = i + 1) {
+        var xtemp: f32 = (zx * zx) - (zy * zy);
+        zy = (2. * zx * zy) + cy;
+        zx = xtemp + cx;
+
+        if ((zx * zx) + (zy * zy) > 4.) {
+            let frac: f32 = f32(i) / f32(ITERATIONS);
+            return vec4<f32>(12. * frac, 1.5 * frac, 3. * frac, 1.0);
+        }
+    }
+
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+}